@@ -0,0 +1,610 @@
+//! DTO (Data Transfer Object) for HTTP requests and responses.
+//!
+//! We use JSON as the DTO format for better readability. The DTOs are shared by the node and its
+//! clients, and documented in the OpenAPI document of the node.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use primitives::{BlockId, Hash, Signature, TxDirection};
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Tx {
+    pub from: String,
+    pub to: String,
+    pub value: u64,
+    pub nonce: u64,
+    pub gas: u64,
+    pub gas_price: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SignedTx {
+    pub tx: Tx,
+    pub sig: Signature,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BlockHeader {
+    pub parent_hash: Hash,
+    pub number: u64,
+    pub nonce: u64,
+    pub timestamp: u64,
+    pub author: String,
+    pub txs_hash: Hash,
+    pub state_root: Hash,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub txs: Vec<SignedTx>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BlockResp {
+    pub hash: Hash,
+    pub block: Block,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HeaderResp {
+    pub hash: Hash,
+    pub header: BlockHeader,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TxStatus {
+    /// Waiting in the tx pool.
+    Pending,
+    /// Included in a block.
+    Included,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TxResp {
+    pub hash: Hash,
+    pub status: TxStatus,
+    pub tx: SignedTx,
+    /// The receipt fields are `None` while the tx is pending.
+    pub block_number: Option<u64>,
+    pub block_hash: Option<Hash>,
+    pub index: Option<u32>,
+    /// The number of blocks from the including block to the tip, both inclusive.
+    pub confirmations: u64,
+    /// The gas cost paid to the author of the block.
+    pub fee: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetBlocksReq {
+    /// Defaults to 0, or to the tip if `reverse` is set.
+    pub from_number: Option<u64>,
+    /// Stop at the given number (inclusive).
+    pub to_number: Option<u64>,
+    /// Defaults to the server maximum.
+    pub limit: Option<usize>,
+    /// Walk towards the genesis block instead of the tip.
+    #[serde(default)]
+    pub reverse: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GetBlocksResp {
+    pub blocks: Vec<BlockResp>,
+    /// The `from_number` of the next page, or `None` if this is the last page.
+    pub next: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AccountTxsReq {
+    /// Defaults to both the sent and the received txs.
+    pub direction: Option<TxDirection>,
+    /// Defaults to 0.
+    pub from_number: Option<u64>,
+    /// The index of the first tx in the `from_number` block, defaults to 0.
+    pub from_index: Option<u32>,
+    /// Stop at the given block number (inclusive).
+    pub to_number: Option<u64>,
+    /// Defaults to the server maximum.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AccountTxsResp {
+    pub txs: Vec<TxResp>,
+    /// The `from_number` and `from_index` of the next page, or `None` if this is the last page.
+    pub next: Option<NextTxs>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NextTxs {
+    pub from_number: u64,
+    pub from_index: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NonceReq {
+    pub account: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BalancesReq {
+    /// The block number or hash, defaults to the last block.
+    pub at: Option<BlockId>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BalancesResp {
+    /// The block after which the accounts have the balances, `None` before the first block.
+    pub last_block_hash: Option<Hash>,
+    pub balances: HashMap<String, u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NonceResp {
+    pub nonce: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AccountAtReq {
+    pub account: String,
+    /// The block number or hash, defaults to the last block.
+    pub at: Option<BlockId>,
+}
+
+/// A light node can only query the latest state.
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct AccountReq {
+    pub account: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AccountResp {
+    pub account: String,
+    pub balance: u64,
+    pub nonce: u64,
+    /// The block after which the account has the state, `None` before the first block.
+    /// For a light node, this is the block whose state root the proof is verified against.
+    pub block_number: Option<u64>,
+    pub block_hash: Option<Hash>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AccountDetailResp {
+    pub account: String,
+    /// The balance and the next nonce after the last block.
+    pub balance: u64,
+    pub nonce: u64,
+    /// The balance and the next nonce after the pending txs, the latter being the nonce of
+    /// the next tx sent by the account.
+    pub pending_balance: u64,
+    pub pending_nonce: u64,
+    pub pending_txs: Vec<TxResp>,
+}
+
+/// The tx is accepted by the miner and broadcasted.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TxHashResp {
+    pub success: bool,
+    pub tx_hash: Hash,
+}
+
+/// The body of the responses of the failed requests, e.g.
+/// `{"code": "invalid_tx_nonce", "message": "...", "details": {"expected": 1, "got": 0, ...}}`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResp {
+    pub code: ErrorCode,
+    /// A human-readable message, which may change between versions unlike the `code`.
+    pub message: String,
+    /// The fields of the error, if any, e.g. the expected nonce.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+/// The machine-readable code of an error, which is stable between versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// 400, the request is malformed, e.g. an invalid hex string.
+    InvalidRequest,
+    /// 400, the query params can't be decoded.
+    InvalidQuery,
+    /// 400, the path params can't be decoded.
+    InvalidPath,
+    /// 400, 415 or 422, the JSON body can't be decoded.
+    InvalidBody,
+    /// 404, there is no such endpoint.
+    NotFound,
+    /// 404, the block is not found.
+    UnknownBlock,
+    /// 404, the tx is neither pending nor included.
+    UnknownTx,
+    /// 404, the node doesn't keep the key of the account.
+    UnknownAccount,
+    /// 404, the state of the block is not kept, see the history mode.
+    HistoryUnavailable,
+    /// 409, the nonce of the tx is not the next nonce of the sender.
+    InvalidTxNonce,
+    /// 422, the tx is malformed or not signed by its sender.
+    InvalidTx,
+    /// 422, the sender can't afford the value and the gas of the tx.
+    InsufficientBalance,
+    /// 500, the node failed to handle the request.
+    Internal,
+    /// 503, the miner is not running.
+    MinerUnavailable,
+    /// 503, no peer of a light node can prove the state.
+    ProofUnavailable,
+    /// A code added by a newer node.
+    #[serde(other)]
+    Unknown,
+}
+
+/// A tx signed outside the node, either as a hex-encoded protobuf `SignedTx`, or as JSON with
+/// a 65-byte `sig`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum SubmitTxReq {
+    Raw { raw: String },
+    Json(SignedTx),
+}
+
+/// A request from a WebSocket client, e.g.
+/// `{"action": "subscribe", "topic": "account", "address": "0x..."}`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WsReq {
+    pub action: WsAction,
+    #[serde(flatten)]
+    pub topic: WsTopic,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WsAction {
+    Subscribe,
+    Unsubscribe,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "topic", rename_all = "snake_case")]
+pub enum WsTopic {
+    /// The headers of the new blocks.
+    NewHeads,
+    /// The txs accepted into the tx pool.
+    PendingTxs,
+    /// The pending and the included txs sent or received by the account.
+    Account { address: String },
+}
+
+/// A message to a WebSocket client, e.g. `{"event": "new_head", "data": {...}}`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum WsEvent {
+    NewHead(HeaderResp),
+    PendingTx(TxResp),
+    AccountTx {
+        address: String,
+        tx: TxResp,
+    },
+    /// The client is too slow, and missed some events.
+    Lagged {
+        missed: u64,
+    },
+    /// The request of the client is invalid.
+    Error {
+        message: String,
+    },
+}
+
+/// A JSON-RPC 2.0 request, whose `params` are positional.
+/// A request without an `id` is a notification, which is not responded to, while an `id` of
+/// `null` is kept as `Some(Value::Null)` and responded to.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RpcReq {
+    pub jsonrpc: String,
+    #[serde(
+        default,
+        deserialize_with = "present_value",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub id: Option<serde_json::Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Vec<serde_json::Value>,
+}
+
+/// Deserialize a field that is present, even if it's `null`, as `Some`, leaving `None` to the
+/// `default` of a missing field.
+fn present_value<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<serde_json::Value>, D::Error> {
+    serde_json::Value::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RpcResp {
+    pub jsonrpc: String,
+    pub id: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// A block in the format of the Ethereum JSON-RPC, whose numbers are hex strings.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EthBlock {
+    pub number: String,
+    pub hash: Hash,
+    pub parent_hash: Hash,
+    pub nonce: String,
+    pub timestamp: String,
+    pub miner: String,
+    pub transactions_root: Hash,
+    pub state_root: Hash,
+    pub transactions: EthBlockTxs,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum EthBlockTxs {
+    Hashes(Vec<Hash>),
+    Full(Vec<EthTx>),
+}
+
+/// A tx in the format of the Ethereum JSON-RPC, whose block fields are `None` while pending.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EthTx {
+    pub hash: Hash,
+    pub from: String,
+    pub to: String,
+    pub value: String,
+    pub nonce: String,
+    pub gas: String,
+    pub gas_price: String,
+    pub block_hash: Option<Hash>,
+    pub block_number: Option<String>,
+    pub transaction_index: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TxReq {
+    pub from: String,
+    pub to: String,
+    pub value: u64,
+    pub nonce: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const HASH: &str = "0x000036755a024ef491b6710fe765e06e33a616f83b8a33c6a1963ab20f6e5bdb";
+
+    /// Decode the JSON as a `T`, and check that it's encoded back to the same JSON.
+    fn round_trip<T: Serialize + DeserializeOwned>(value: Value) -> T {
+        let decoded: T = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), value);
+        decoded
+    }
+
+    fn signed_tx() -> Value {
+        json!({
+            "tx": {
+                "from": "0x01",
+                "to": "0x02",
+                "value": 100,
+                "nonce": 0,
+                "gas": 21,
+                "gas_price": 1,
+                "timestamp": 1700000000,
+            },
+            "sig": format!("0x{}", "11".repeat(65)),
+        })
+    }
+
+    fn tx_resp() -> Value {
+        json!({
+            "hash": HASH,
+            "status": "pending",
+            "tx": signed_tx(),
+            "block_number": null,
+            "block_hash": null,
+            "index": null,
+            "confirmations": 0,
+            "fee": null,
+        })
+    }
+
+    fn header_resp() -> Value {
+        json!({
+            "hash": HASH,
+            "header": {
+                "parent_hash": HASH,
+                "number": 1,
+                "nonce": 7,
+                "timestamp": 1700000000,
+                "author": "0x01",
+                "txs_hash": HASH,
+                "state_root": HASH,
+            },
+        })
+    }
+
+    #[test]
+    fn resps_round_trip() {
+        let header = header_resp()["header"].clone();
+        let block = json!({"hash": HASH, "block": {"header": header, "txs": [signed_tx()]}});
+        let resp: GetBlocksResp = round_trip(json!({"blocks": [block], "next": 2}));
+        assert_eq!(resp.blocks[0].block.header.number, 1);
+
+        let resp: AccountTxsResp = round_trip(json!({
+            "txs": [tx_resp()],
+            "next": {"from_number": 1, "from_index": 2},
+        }));
+        assert!(matches!(resp.txs[0].status, TxStatus::Pending));
+
+        round_trip::<AccountResp>(json!({
+            "account": "0x01",
+            "balance": 100,
+            "nonce": 1,
+            "block_number": null,
+            "block_hash": null,
+        }));
+        round_trip::<TxHashResp>(json!({"success": true, "tx_hash": HASH}));
+    }
+
+    #[test]
+    fn errors_round_trip() {
+        let resp: ErrorResp = round_trip(json!({
+            "code": "invalid_tx_nonce",
+            "message": "Invalid tx nonce",
+            "details": {"expected": 1, "got": 0},
+        }));
+        assert_eq!(resp.code, ErrorCode::InvalidTxNonce);
+        let resp: ErrorResp = round_trip(json!({"code": "not_found", "message": "Not found"}));
+        assert_eq!(resp.details, None);
+
+        // The codes added by a newer node are still decoded.
+        let resp: ErrorResp =
+            serde_json::from_value(json!({"code": "rate_limited", "message": ""})).unwrap();
+        assert_eq!(resp.code, ErrorCode::Unknown);
+    }
+
+    #[test]
+    fn submitted_txs_round_trip() {
+        let req: SubmitTxReq = round_trip(json!({"raw": "0a0b"}));
+        assert!(matches!(req, SubmitTxReq::Raw { raw } if raw == "0a0b"));
+        let req: SubmitTxReq = round_trip(signed_tx());
+        assert!(matches!(req, SubmitTxReq::Json(tx) if tx.tx.value == 100));
+
+        // The signature must have 65 bytes.
+        let mut tx = signed_tx();
+        tx["sig"] = json!(HASH);
+        assert!(serde_json::from_value::<SubmitTxReq>(tx).is_err());
+    }
+
+    #[test]
+    fn ws_messages_round_trip() {
+        let req: WsReq = round_trip(json!({
+            "action": "subscribe",
+            "topic": "account",
+            "address": "0x01",
+        }));
+        assert!(matches!(req.action, WsAction::Subscribe));
+        assert_eq!(
+            req.topic,
+            WsTopic::Account {
+                address: "0x01".to_string()
+            }
+        );
+        let req: WsReq = round_trip(json!({"action": "unsubscribe", "topic": "new_heads"}));
+        assert_eq!(req.topic, WsTopic::NewHeads);
+
+        round_trip::<WsEvent>(json!({"event": "new_head", "data": header_resp()}));
+        round_trip::<WsEvent>(json!({
+            "event": "account_tx",
+            "data": {"address": "0x01", "tx": tx_resp()},
+        }));
+        round_trip::<WsEvent>(json!({"event": "lagged", "data": {"missed": 3}}));
+    }
+
+    #[test]
+    fn rpc_messages_round_trip() {
+        let req: RpcReq = round_trip(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getBalance",
+            "params": ["0x01", "latest"],
+        }));
+        assert_eq!(req.id, Some(json!(1)));
+
+        // A `null` id is told apart from a notification, which has no id.
+        let req: RpcReq = round_trip(json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "method": "eth_blockNumber",
+            "params": [],
+        }));
+        assert_eq!(req.id, Some(Value::Null));
+        let req: RpcReq = round_trip(json!({
+            "jsonrpc": "2.0",
+            "method": "eth_blockNumber",
+            "params": [],
+        }));
+        assert_eq!(req.id, None);
+
+        round_trip::<RpcResp>(json!({"jsonrpc": "2.0", "id": "a", "result": "0x0"}));
+        round_trip::<RpcResp>(json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": {"code": -32700, "message": "Parse error"},
+        }));
+
+        let block: EthBlock = round_trip(json!({
+            "number": "0x1",
+            "hash": HASH,
+            "parentHash": HASH,
+            "nonce": "0x7",
+            "timestamp": "0x6553f100",
+            "miner": "0x01",
+            "transactionsRoot": HASH,
+            "stateRoot": HASH,
+            "transactions": [HASH],
+        }));
+        assert!(matches!(block.transactions, EthBlockTxs::Hashes(hashes) if hashes.len() == 1));
+    }
+
+    #[test]
+    fn query_params_are_encoded() {
+        let req = GetBlocksReq {
+            from_number: Some(10),
+            limit: Some(5),
+            reverse: true,
+            ..Default::default()
+        };
+        let query = serde_urlencoded::to_string(&req).unwrap();
+        assert_eq!(query, "from_number=10&limit=5&reverse=true");
+        let decoded: GetBlocksReq = serde_urlencoded::from_str(&query).unwrap();
+        assert_eq!((decoded.from_number, decoded.to_number), (Some(10), None));
+
+        let query = serde_urlencoded::to_string(BalancesReq {
+            at: Some(BlockId::Number(120)),
+        })
+        .unwrap();
+        assert_eq!(query, "at=120");
+        let decoded: BalancesReq = serde_urlencoded::from_str(&format!("at={HASH}")).unwrap();
+        assert!(matches!(decoded.at, Some(BlockId::Hash(_))));
+
+        let req = AccountTxsReq {
+            direction: Some(TxDirection::Sent),
+            ..Default::default()
+        };
+        let query = serde_urlencoded::to_string(&req).unwrap();
+        let decoded: AccountTxsReq = serde_urlencoded::from_str(&query).unwrap();
+        assert_eq!(decoded.direction, Some(TxDirection::Sent));
+    }
+}
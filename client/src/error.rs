@@ -0,0 +1,53 @@
+use serde_json::Value;
+
+use crate::dto::{ErrorCode, ErrorResp};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClientError {
+    #[error("Failed to reach the node: {0}")]
+    Transport(Box<ureq::Transport>),
+    #[error("Node responded with {status}: {message}")]
+    Api {
+        status: u16,
+        code: ErrorCode,
+        message: String,
+        details: Option<Value>,
+    },
+    #[error("JSON-RPC error {code}: {message}")]
+    Rpc { code: i64, message: String },
+    #[error("Failed to decode the response")]
+    InvalidResponse(#[from] std::io::Error),
+    #[error("Failed to encode the query")]
+    InvalidQuery(#[from] serde_urlencoded::ser::Error),
+}
+
+impl ClientError {
+    /// The code of the error responded by the node, if any.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Api { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl From<ureq::Error> for ClientError {
+    fn from(err: ureq::Error) -> Self {
+        match err {
+            ureq::Error::Status(status, resp) => {
+                let (code, message, details) = match resp.into_json::<ErrorResp>() {
+                    Ok(body) => (body.code, body.message, body.details),
+                    // Not a response of the node, e.g. of a proxy in between.
+                    Err(err) => (ErrorCode::Unknown, err.to_string(), None),
+                };
+                Self::Api {
+                    status,
+                    code,
+                    message,
+                    details,
+                }
+            }
+            ureq::Error::Transport(transport) => Self::Transport(Box::new(transport)),
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! The blocking client over `ureq`.
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{dto::*, BlockId, ClientError, Hash};
+
+/// A blocking client, which is cheap to clone as the connections are pooled and shared.
+#[derive(Debug, Clone)]
+pub struct Client {
+    agent: ureq::Agent,
+    base_url: String,
+}
+
+impl Client {
+    /// Create a client of the node at the `base_url`, e.g. `http://localhost:8000`.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Get a page of blocks, and the `from_number` of the next page.
+    pub fn get_blocks(&self, req: &GetBlocksReq) -> Result<GetBlocksResp, ClientError> {
+        self.get("/blocks", req)
+    }
+
+    pub fn get_block(&self, number: u64) -> Result<Option<BlockResp>, ClientError> {
+        let resp = self.get(&format!("/blocks/{number}"), &());
+        optional(resp, ErrorCode::UnknownBlock)
+    }
+
+    /// Get the balances after the block `at`, or after the last block by default.
+    pub fn get_balances(&self, at: Option<BlockId>) -> Result<BalancesResp, ClientError> {
+        self.get("/balances", &BalancesReq { at })
+    }
+
+    /// Get the account after the block `at`, or after the last block by default.
+    /// A light node only serves the latest state, which is proven by its peers.
+    pub fn get_account(
+        &self,
+        account: &str,
+        at: Option<BlockId>,
+    ) -> Result<AccountResp, ClientError> {
+        let req = AccountAtReq {
+            account: account.to_string(),
+            at,
+        };
+        self.get("/account/balance", &req)
+    }
+
+    pub fn next_account_nonce(&self, account: &str) -> Result<u64, ClientError> {
+        let req = NonceReq {
+            account: account.to_string(),
+        };
+        let resp: NonceResp = self.get("/account/nonce", &req)?;
+        Ok(resp.nonce)
+    }
+
+    /// Transfer with a key kept by the wallet of the node.
+    pub fn transfer(&self, req: &TxReq) -> Result<Hash, ClientError> {
+        let resp: TxHashResp = self.post("/transfer", req)?;
+        Ok(resp.tx_hash)
+    }
+
+    /// Submit a tx signed outside the node.
+    pub fn submit_tx(&self, req: &SubmitTxReq) -> Result<Hash, ClientError> {
+        let resp: TxHashResp = self.post("/txs", req)?;
+        Ok(resp.tx_hash)
+    }
+
+    pub fn get_tx(&self, hash: &Hash) -> Result<Option<TxResp>, ClientError> {
+        optional(self.get(&format!("/txs/{hash}"), &()), ErrorCode::UnknownTx)
+    }
+
+    pub fn get_account_detail(&self, address: &str) -> Result<AccountDetailResp, ClientError> {
+        self.get(&format!("/accounts/{address}"), &())
+    }
+
+    /// Get a page of the txs of the account, and the cursor of the next page.
+    pub fn get_account_txs(
+        &self,
+        address: &str,
+        req: &AccountTxsReq,
+    ) -> Result<AccountTxsResp, ClientError> {
+        self.get(&format!("/accounts/{address}/txs"), req)
+    }
+
+    /// Get a header from a light node.
+    pub fn get_header(&self, number: u64) -> Result<Option<HeaderResp>, ClientError> {
+        let resp = self.get(&format!("/headers/{number}"), &());
+        optional(resp, ErrorCode::UnknownBlock)
+    }
+
+    /// Call a JSON-RPC method, see `/rpc` for the supported ones.
+    pub fn rpc(&self, method: &str, params: Vec<Value>) -> Result<Value, ClientError> {
+        let req = RpcReq {
+            jsonrpc: "2.0".to_string(),
+            id: Some(Value::from(1)),
+            method: method.to_string(),
+            params,
+        };
+        let resp: RpcResp = self.post("/rpc", &req)?;
+        match resp.error {
+            Some(RpcError { code, message }) => Err(ClientError::Rpc { code, message }),
+            None => Ok(resp.result.unwrap_or_default()),
+        }
+    }
+
+    /// Get the OpenAPI document of the node.
+    pub fn openapi(&self) -> Result<Value, ClientError> {
+        self.get("/openapi.json", &())
+    }
+
+    fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &impl Serialize,
+    ) -> Result<T, ClientError> {
+        let query = serde_urlencoded::to_string(query)?;
+        let mut url = format!("{}{path}", self.base_url);
+        if !query.is_empty() {
+            url = format!("{url}?{query}");
+        }
+
+        Ok(self.agent.get(&url).call()?.into_json()?)
+    }
+
+    fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<T, ClientError> {
+        let url = format!("{}{path}", self.base_url);
+        Ok(self.agent.post(&url).send_json(body)?.into_json()?)
+    }
+}
+
+/// Turn the error of a missing resource into `None`.
+fn optional<T>(resp: Result<T, ClientError>, missing: ErrorCode) -> Result<Option<T>, ClientError> {
+    match resp {
+        Ok(resp) => Ok(Some(resp)),
+        Err(err) if err.code() == Some(missing) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc::{channel, Receiver},
+        thread,
+    };
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// A stub of the node, which answers the requests with the given statuses and bodies in
+    /// turn, and sends back the request line and the body of each request.
+    fn stub_node(resps: Vec<(u16, String)>) -> (Client, Receiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = Client::new(&format!("http://{}/", listener.local_addr().unwrap()));
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            for (status, body) in resps {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut req_body = vec![0; content_length];
+                reader.read_exact(&mut req_body).unwrap();
+                let req_body = String::from_utf8(req_body).unwrap();
+                sender
+                    .send((request_line.trim_end().to_string(), req_body))
+                    .unwrap();
+
+                let resp = format!(
+                    "HTTP/1.1 {status} Stub\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                reader.into_inner().write_all(resp.as_bytes()).unwrap();
+            }
+        });
+        (client, receiver)
+    }
+
+    #[test]
+    fn reqs_are_encoded() {
+        let hash = Hash::default();
+        let (client, reqs) = stub_node(vec![
+            (200, json!({"blocks": [], "next": null}).to_string()),
+            (200, json!({"success": true, "tx_hash": hash}).to_string()),
+            (
+                200,
+                json!({"jsonrpc": "2.0", "id": 1, "result": "0x2"}).to_string(),
+            ),
+        ]);
+
+        let req = GetBlocksReq {
+            from_number: Some(3),
+            reverse: true,
+            ..Default::default()
+        };
+        assert!(client.get_blocks(&req).unwrap().blocks.is_empty());
+        assert_eq!(
+            reqs.recv().unwrap(),
+            (
+                "GET /blocks?from_number=3&reverse=true HTTP/1.1".to_string(),
+                String::new()
+            )
+        );
+
+        let req = TxReq {
+            from: "0x01".to_string(),
+            to: "0x02".to_string(),
+            value: 100,
+            nonce: 0,
+        };
+        assert_eq!(client.transfer(&req).unwrap(), hash);
+        let (request_line, body) = reqs.recv().unwrap();
+        assert_eq!(request_line, "POST /transfer HTTP/1.1");
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!({"from": "0x01", "to": "0x02", "value": 100, "nonce": 0})
+        );
+
+        assert_eq!(client.rpc("eth_blockNumber", vec![]).unwrap(), "0x2");
+        let (request_line, body) = reqs.recv().unwrap();
+        assert_eq!(request_line, "POST /rpc HTTP/1.1");
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!({"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber", "params": []})
+        );
+    }
+
+    #[test]
+    fn errors_are_decoded() {
+        let (client, _reqs) = stub_node(vec![
+            (
+                404,
+                json!({"code": "unknown_block", "message": "Unknown block"}).to_string(),
+            ),
+            (
+                409,
+                json!({
+                    "code": "invalid_tx_nonce",
+                    "message": "Invalid tx nonce",
+                    "details": {"expected": 1, "got": 0},
+                })
+                .to_string(),
+            ),
+            (502, "<html>Bad Gateway</html>".to_string()),
+            (
+                200,
+                json!({"jsonrpc": "2.0", "id": 1, "error": {"code": -32601, "message": "nope"}})
+                    .to_string(),
+            ),
+        ]);
+
+        // A missing resource is `None`.
+        assert!(client.get_block(1).unwrap().is_none());
+
+        let req = SubmitTxReq::Raw {
+            raw: "0a".to_string(),
+        };
+        match client.submit_tx(&req).unwrap_err() {
+            ClientError::Api {
+                status,
+                code,
+                details,
+                ..
+            } => {
+                assert_eq!((status, code), (409, ErrorCode::InvalidTxNonce));
+                assert_eq!(details, Some(json!({"expected": 1, "got": 0})));
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+
+        // Not a response of the node.
+        let err = client.get_tx(&Hash::default()).unwrap_err();
+        assert!(matches!(err, ClientError::Api { status: 502, .. }));
+        assert_eq!(err.code(), Some(ErrorCode::Unknown));
+
+        let err = client.rpc("eth_mining", vec![]).unwrap_err();
+        assert!(matches!(err, ClientError::Rpc { code: -32601, .. }));
+    }
+
+    #[test]
+    fn unreachable_nodes_are_transport_errors() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = Client::new(&format!("http://{addr}"));
+        assert!(matches!(
+            client.next_account_nonce("0x01"),
+            Err(ClientError::Transport(_))
+        ));
+    }
+}
@@ -0,0 +1,18 @@
+//! A typed client of the HTTP API of a node.
+//!
+//! The requests and the responses are the same DTOs that the node serves, see `dto`. The
+//! WebSocket subscriptions are not covered, but their messages are `WsReq` and `WsEvent`.
+//!
+//! The node only uses the DTOs, so the `Client` is behind the default `http` feature.
+
+pub mod dto;
+#[cfg(feature = "http")]
+mod error;
+#[cfg(feature = "http")]
+mod http;
+
+#[cfg(feature = "http")]
+pub use error::ClientError;
+#[cfg(feature = "http")]
+pub use http::Client;
+pub use primitives::{BlockId, Bytes, Hash, Signature, TxDirection};
@@ -0,0 +1,293 @@
+//! Types shared by the node and its clients, without depending on either of them.
+//!
+//! When the bytes are serialized or logged, they are represented as a '0x'-prefixed hex string.
+//! Their OpenAPI schemas are behind the `openapi` feature.
+
+#[cfg(feature = "openapi")]
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Deref;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "openapi")]
+use utoipa::{
+    openapi::{schema::Schema, ObjectBuilder, RefOr, Type},
+    PartialSchema, ToSchema,
+};
+
+pub type Hash = Bytes<32>;
+pub type Signature = Bytes<65>;
+
+// Serialize and deserialize Bytes as a '0x'-prefixed hex string.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Bytes<const T: usize>([u8; T]);
+
+impl<const T: usize> Bytes<T> {
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|&b| b == 0)
+    }
+
+    fn fmt_as_hex(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x")?;
+
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+// `Deref` makes `&Bytes` behave like a `&[u8; T]`.
+impl<const T: usize> Deref for Bytes<T> {
+    type Target = [u8; T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const T: usize> From<[u8; T]> for Bytes<T> {
+    fn from(bytes: [u8; T]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<const T: usize> From<Vec<u8>> for Bytes<T> {
+    fn from(bytes: Vec<u8>) -> Self {
+        let mut array = [0u8; T];
+        array.copy_from_slice(bytes.as_slice());
+        Self(array)
+    }
+}
+
+impl<const T: usize> TryFrom<String> for Bytes<T> {
+    type Error = hex::FromHexError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let val = if let Some(val) = value.strip_prefix("0x") {
+            val
+        } else {
+            &value
+        };
+
+        // Unlike `From<Vec<u8>>`, a string of a wrong length is an error instead of a panic.
+        let mut bytes = [0u8; T];
+        hex::decode_to_slice(val, &mut bytes)?;
+        Ok(Self(bytes))
+    }
+}
+
+impl<const T: usize> From<Bytes<T>> for [u8; T] {
+    fn from(bytes: Bytes<T>) -> Self {
+        bytes.0
+    }
+}
+
+impl<const T: usize> From<Bytes<T>> for Vec<u8> {
+    fn from(bytes: Bytes<T>) -> Self {
+        bytes.0.to_vec()
+    }
+}
+
+impl<const T: usize> From<Bytes<T>> for String {
+    fn from(bytes: Bytes<T>) -> Self {
+        String::from("0x") + &hex::encode(bytes.0)
+    }
+}
+
+impl<const T: usize> Default for Bytes<T> {
+    fn default() -> Self {
+        Self([0u8; T])
+    }
+}
+
+// For better logging.
+impl<const T: usize> fmt::Debug for Bytes<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_as_hex(f)
+    }
+}
+
+// For better logging.
+impl<const T: usize> fmt::Display for Bytes<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_as_hex(f)
+    }
+}
+
+// The bytes are documented as hex strings, named after their aliases.
+#[cfg(feature = "openapi")]
+impl PartialSchema for Hash {
+    fn schema() -> RefOr<Schema> {
+        hex_schema(32, "A 32-byte hash, e.g. of a block or a tx.")
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl ToSchema for Hash {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("Hash")
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl PartialSchema for Signature {
+    fn schema() -> RefOr<Schema> {
+        hex_schema(
+            65,
+            "A 65-byte signature, the last byte being the recovery id.",
+        )
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl ToSchema for Signature {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("Signature")
+    }
+}
+
+#[cfg(feature = "openapi")]
+fn hex_schema(len: usize, description: &str) -> RefOr<Schema> {
+    ObjectBuilder::new()
+        .schema_type(Type::String)
+        .pattern(Some(format!("^0x[0-9a-fA-F]{{{}}}$", len * 2)))
+        .description(Some(description))
+        .into()
+}
+
+/// Whether an account sends or receives a tx.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum TxDirection {
+    Sent,
+    Received,
+}
+
+/// A block referred by its number or its hash, e.g. `120` or `0x1234...`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum BlockId {
+    Number(u64),
+    Hash(Hash),
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{number}"),
+            Self::Hash(hash) => write!(f, "{hash}"),
+        }
+    }
+}
+
+/// A string that is neither a block number nor a block hash.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid block id: '{0}', expected a number or a hash")]
+pub struct InvalidBlockId(pub String);
+
+impl TryFrom<String> for BlockId {
+    type Error = InvalidBlockId;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value.starts_with("0x") {
+            return Hash::try_from(value.clone())
+                .map(Self::Hash)
+                .map_err(|_| InvalidBlockId(value));
+        }
+
+        value
+            .parse()
+            .map(Self::Number)
+            .map_err(|_| InvalidBlockId(value))
+    }
+}
+
+impl From<BlockId> for String {
+    fn from(id: BlockId) -> Self {
+        id.to_string()
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl PartialSchema for BlockId {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .description(Some("A block number, or a '0x'-prefixed block hash."))
+            .examples(["120"])
+            .into()
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl ToSchema for BlockId {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_value_is_zero() {
+        let bytes = Bytes::<32>::default();
+        assert!(bytes.is_zero());
+    }
+
+    #[test]
+    fn bytes_from() {
+        let s = "0x000036755a024ef491b6710fe765e06e33a616f83b8a33c6a1963ab20f6e5bdb";
+        let v = hex::decode(&s[2..]).unwrap();
+        let arr: [u8; 32] = v.as_slice().try_into().unwrap();
+
+        let b1 = Bytes::<32>::from(arr);
+        assert_eq!(&b1[..], &arr[..]);
+
+        let b2 = Bytes::<32>::from(v);
+        assert_eq!(b1, b2);
+
+        let b3 = Bytes::<32>::try_from(s.to_string()).unwrap();
+        assert_eq!(b2, b3);
+        assert!(Bytes::<32>::try_from(s[..64].to_string()).is_err());
+    }
+
+    #[test]
+    fn bytes_into() {
+        let s = "0x000036755a024ef491b6710fe765e06e33a616f83b8a33c6a1963ab20f6e5bdb";
+        let b = Bytes::<32>::try_from(s.to_string()).unwrap();
+
+        let arr: [u8; 32] = b.into();
+        assert_eq!(&arr[..], &b[..]);
+
+        let v: Vec<u8> = b.into();
+        assert_eq!(v.as_slice(), b.as_slice());
+
+        let s2: String = b.into();
+        assert_eq!(s, s2);
+    }
+
+    #[test]
+    fn block_id_from_string() {
+        let s = "0x000036755a024ef491b6710fe765e06e33a616f83b8a33c6a1963ab20f6e5bdb";
+        let hash = Hash::try_from(s.to_string()).unwrap();
+        assert_eq!(
+            BlockId::try_from(s.to_string()).unwrap(),
+            BlockId::Hash(hash)
+        );
+        assert_eq!(
+            BlockId::try_from("120".to_string()).unwrap(),
+            BlockId::Number(120)
+        );
+        assert!(BlockId::try_from(s[..64].to_string()).is_err());
+        assert!(BlockId::try_from("latest".to_string()).is_err());
+    }
+
+    #[test]
+    fn bytes_logging_format() {
+        let s = "0x000036755a024ef491b6710fe765e06e33a616f83b8a33c6a1963ab20f6e5bdb";
+        let b = Bytes::<32>::try_from(s.to_string()).unwrap();
+        assert_eq!(s, format!("{:?}", b));
+        assert_eq!(s, format!("{}", b));
+    }
+}
@@ -0,0 +1,83 @@
+//! The bus of the chain events, which are emitted by the miner when it adds a block or a pending
+//! tx, and watched by any number of subscribers, e.g. the WebSocket clients.
+//!
+//! The bus never blocks the miner: a subscriber that falls behind by more than
+//! `EVENT_BUS_CAPACITY` events misses the oldest ones, and is told how many it missed.
+
+use tokio::sync::broadcast;
+
+use crate::schema::{Block, SignedTx};
+
+/// The number of events buffered for the slowest subscriber.
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A block becomes the head, either added on top of the chain or imported with a state
+    /// snapshot. As the first block seen at a height wins, a head is never replaced.
+    NewBlock(Block),
+    /// A tx is accepted into the tx pool.
+    PendingTx(SignedTx),
+}
+
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emit an event to the current subscribers, it's dropped if there is none.
+    pub fn emit(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribe to the events emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use super::*;
+
+    #[test]
+    fn events_are_broadcast_to_the_subscribers() {
+        let bus = EventBus::new();
+        // Nobody is listening yet, so the event is dropped.
+        bus.emit(Event::NewBlock(Block::default()));
+
+        let mut first = bus.subscribe();
+        let mut second = bus.subscribe();
+        bus.emit(Event::PendingTx(SignedTx::default()));
+        for receiver in [&mut first, &mut second] {
+            assert!(matches!(receiver.try_recv(), Ok(Event::PendingTx(_))));
+            assert!(matches!(receiver.try_recv(), Err(TryRecvError::Empty)));
+        }
+    }
+
+    #[test]
+    fn slow_subscribers_miss_the_oldest_events() {
+        let bus = EventBus::new();
+        let mut receiver = bus.subscribe();
+        for _ in 0..EVENT_BUS_CAPACITY + 3 {
+            bus.emit(Event::PendingTx(SignedTx::default()));
+        }
+
+        assert!(matches!(receiver.try_recv(), Err(TryRecvError::Lagged(3))));
+        assert!(matches!(receiver.try_recv(), Ok(Event::PendingTx(_))));
+    }
+}
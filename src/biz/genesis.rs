@@ -1,12 +1,67 @@
-use std::{collections::HashMap, fs};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+};
 
 use serde::Deserialize;
 
-use crate::error::Error;
+use crate::{error::Error, schema::BlockHeader, types::Hash, utils};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Genesis {
+    /// The ID of the chain, which distinguishes different networks.
+    #[serde(default = "default_chain_id")]
+    chain_id: String,
     balances: HashMap<String, u64>,
+    /// The trusted block hashes indexed by the block numbers, which are not part of the genesis
+    /// hash, so that new checkpoints can be added to a running chain.
+    #[serde(default)]
+    checkpoints: Checkpoints,
+}
+
+/// Identifies the chain that a node is on. Peers must have the same `ChainSpec`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainSpec {
+    pub chain_id: String,
+    pub genesis_hash: Hash,
+    pub checkpoints: Checkpoints,
+}
+
+/// The blocks that every valid chain must pass through, which protect new nodes from a fake
+/// chain built from scratch by an attacker.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Checkpoints(BTreeMap<u64, Hash>);
+
+impl Checkpoints {
+    /// Check the header against the checkpoint at its number, if any.
+    pub fn check(&self, header: &BlockHeader) -> Result<(), Error> {
+        match self.0.get(&header.number) {
+            Some(expected) if *expected != header.hash() => Err(Error::CheckpointMismatch(
+                header.number,
+                *expected,
+                header.hash(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the last checkpoint below the `height`.
+    pub fn last_below(&self, height: u64) -> Option<(u64, Hash)> {
+        self.0
+            .range(..height)
+            .next_back()
+            .map(|(number, hash)| (*number, *hash))
+    }
+}
+
+impl FromIterator<(u64, Hash)> for Checkpoints {
+    fn from_iter<T: IntoIterator<Item = (u64, Hash)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+fn default_chain_id() -> String {
+    "tinychain".to_string()
 }
 
 impl Genesis {
@@ -17,7 +72,53 @@ impl Genesis {
         serde_json::from_str(&content).map_err(|_| Error::InvalidGenesis)
     }
 
+    /// The hash of the genesis, which commits to the chain ID and the initial balances.
+    pub fn hash(&self) -> Hash {
+        let mut balances: Vec<_> = self.balances.iter().collect();
+        balances.sort();
+
+        let mut msg = self.chain_id.as_bytes().to_vec();
+        for (account, balance) in balances {
+            msg.extend_from_slice(account.as_bytes());
+            msg.extend_from_slice(&balance.to_be_bytes());
+        }
+        utils::hash_message(&msg)
+    }
+
+    pub fn chain_spec(&self) -> ChainSpec {
+        ChainSpec {
+            chain_id: self.chain_id.clone(),
+            genesis_hash: self.hash(),
+            checkpoints: self.checkpoints.clone(),
+        }
+    }
+
     pub fn into_balances(self) -> HashMap<String, u64> {
         self.balances
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::Block;
+
+    #[test]
+    fn checkpoints_work() {
+        let block = Block::new(Hash::default(), 8, "0x01234567".to_string(), vec![]);
+        let json = format!(
+            r#"{{"balances": {{}}, "checkpoints": {{"8": "{}"}}}}"#,
+            block.hash()
+        );
+        let genesis: Genesis = serde_json::from_str(&json).unwrap();
+        let checkpoints = genesis.chain_spec().checkpoints;
+
+        assert!(checkpoints.check(block.header()).is_ok());
+        let mut other = block.clone();
+        other.update_nonce_and_time();
+        assert!(checkpoints.check(other.header()).is_err());
+
+        assert_eq!(checkpoints.last_below(8), None);
+        assert_eq!(checkpoints.last_below(9), Some((8, block.hash())));
+    }
+}
@@ -0,0 +1,493 @@
+//! The light mode, where a node only syncs and validates the block headers (number, parent hash,
+//! PoW and checkpoints), and doesn't mine.
+//!
+//! A light node has no state, so it asks the full peers to prove the state of an account at
+//! their heads, and verifies the proof against the `state_root` of a header it has synced. A proof
+//! at a block that is not synced yet is rejected, and the peer is synced from for the next time.
+//!
+//! The headers are only kept in memory, and synced again from the peers on restart.
+
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use crossbeam_channel::{select, tick, Receiver, Sender, TryRecvError};
+use log::{error, info};
+
+use crate::{
+    error::Error,
+    schema::{AccountState, BlockHeader, StatusResp},
+    types::Hash,
+};
+
+use super::{
+    syncer::{download_headers, handshake},
+    verify_account_proof, PeerBook, PeerClient,
+};
+
+const SYNC_INTERVAL: u64 = 30;
+/// The number of headers requested from a peer at a time.
+const HEADERS_BATCH_SIZE: u64 = 512;
+
+/// The headers synced by a light node, which are only written by the `LightSyncer`.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderChain {
+    inner: Arc<RwLock<HeaderChainInner>>,
+}
+
+#[derive(Debug, Default)]
+struct HeaderChainInner {
+    /// The headers indexed by their numbers.
+    headers: Vec<BlockHeader>,
+    /// The numbers of the headers indexed by their hashes.
+    numbers: HashMap<Hash, u64>,
+}
+
+impl HeaderChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of the last header plus one.
+    pub fn height(&self) -> u64 {
+        self.inner.read().unwrap().headers.len() as u64
+    }
+
+    pub fn head_hash(&self) -> Option<Hash> {
+        self.inner.read().unwrap().headers.last().map(|h| h.hash())
+    }
+
+    pub fn get(&self, number: u64) -> Option<BlockHeader> {
+        self.inner
+            .read()
+            .unwrap()
+            .headers
+            .get(number as usize)
+            .cloned()
+    }
+
+    pub fn get_by_hash(&self, hash: &Hash) -> Option<BlockHeader> {
+        let inner = self.inner.read().unwrap();
+        let number = *inner.numbers.get(hash)?;
+        inner.headers.get(number as usize).cloned()
+    }
+
+    /// Get at most `count` headers, starting from the `from_number`.
+    pub fn get_headers(&self, from_number: u64, count: usize) -> Vec<BlockHeader> {
+        self.inner
+            .read()
+            .unwrap()
+            .headers
+            .iter()
+            .skip(from_number as usize)
+            .take(count)
+            .cloned()
+            .collect()
+    }
+
+    /// Append the headers, which must have been checked to extend the chain.
+    fn append(&self, headers: Vec<BlockHeader>) {
+        let mut inner = self.inner.write().unwrap();
+        for header in headers {
+            inner.numbers.insert(header.hash(), header.number);
+            inner.headers.push(header);
+        }
+    }
+}
+
+/// Sync the headers from the full peers periodically, or immediately when asked to sync from a
+/// peer, e.g. a new peer is connected, or a peer announced a new block.
+#[derive(Debug)]
+pub struct LightSyncer<P: PeerClient> {
+    /// The client to interact with other peers.
+    peer_client: P,
+    /// The cached statuses of the peers.
+    peer_book: PeerBook,
+    /// The synced headers.
+    headers: HeaderChain,
+    /// The receiver of the peers to sync from immediately.
+    sync_receiver: Receiver<String>,
+    /// The shutdown signal, which is triggered by closing the channel.
+    shutdown: Receiver<()>,
+}
+
+impl<P: PeerClient> LightSyncer<P> {
+    pub fn new(
+        peer_client: P,
+        peer_book: PeerBook,
+        headers: HeaderChain,
+        sync_receiver: Receiver<String>,
+        shutdown: Receiver<()>,
+    ) -> Self {
+        Self {
+            peer_client,
+            peer_book,
+            headers,
+            sync_receiver,
+            shutdown,
+        }
+    }
+
+    pub fn sync(&self) {
+        let ticker = tick(Duration::from_secs(SYNC_INTERVAL));
+
+        loop {
+            select! {
+                recv(self.shutdown) -> _ => {
+                    info!("📣 Light syncer stopped");
+                    return;
+                }
+                recv(self.sync_receiver) -> msg => {
+                    if let Ok(peer) = msg {
+                        // The announced blocks are synced from the peers already handshaked.
+                        if self.peer_book.contains(&peer)
+                            || handshake(&self.peer_client, &self.peer_book, &peer)
+                        {
+                            self.sync_once(Some(&peer));
+                        }
+                    }
+                }
+                recv(ticker) -> _ => {
+                    for peer in self.peer_client.known_peers() {
+                        if !self.peer_book.contains(&peer) {
+                            handshake(&self.peer_client, &self.peer_book, &peer);
+                        }
+                    }
+                    self.sync_once(None);
+                }
+            }
+        }
+    }
+
+    /// Sync the headers from all the full peers, or only from the `target` peer if given.
+    fn sync_once(&self, target: Option<&str>) {
+        let local_height = self.headers.height();
+        let mut peers = self.peer_book.peers_ahead(local_height);
+        if let Some(target) = target {
+            peers.retain(|(peer, _)| peer == target);
+        }
+        let Some((_, target_height)) = peers.first().cloned() else {
+            return;
+        };
+
+        let mut next_number = local_height;
+        info!(
+            "📣 Syncing headers from {} to {} with {} peers",
+            local_height,
+            target_height,
+            peers.len()
+        );
+
+        while next_number < target_height {
+            if let Err(TryRecvError::Disconnected) = self.shutdown.try_recv() {
+                return;
+            }
+
+            let count = HEADERS_BATCH_SIZE.min(target_height - next_number);
+            let parent_hash = self.headers.head_hash().unwrap_or_default();
+            let Some(headers) = download_headers(
+                &self.peer_client,
+                &self.peer_book,
+                &peers,
+                parent_hash,
+                next_number,
+                count,
+            ) else {
+                error!("❌ Failed to download headers from {}", next_number);
+                return;
+            };
+
+            next_number += headers.len() as u64;
+            self.headers.append(headers);
+        }
+
+        info!("📣 Synced headers up to {}", next_number);
+    }
+}
+
+/// The state of an account proven by a full peer.
+#[derive(Debug, Clone)]
+pub struct ProvenAccount {
+    pub state: AccountState,
+    /// The header whose state root the proof is verified against.
+    pub header: BlockHeader,
+}
+
+#[derive(Debug, Clone)]
+pub struct LightNode<P: PeerClient> {
+    inner: Arc<LightNodeInner<P>>,
+}
+
+impl<P: PeerClient> LightNode<P> {
+    pub fn new(
+        peer_client: P,
+        peer_book: PeerBook,
+        headers: HeaderChain,
+        sync_sender: Sender<String>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(LightNodeInner {
+                peer_client,
+                peer_book,
+                headers,
+                sync_sender,
+            }),
+        }
+    }
+}
+
+impl<P: PeerClient> Deref for LightNode<P> {
+    type Target = LightNodeInner<P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[derive(Debug)]
+pub struct LightNodeInner<P: PeerClient> {
+    // The client to ask the full peers for proofs.
+    peer_client: P,
+    // The cached statuses of the peers.
+    peer_book: PeerBook,
+    // The headers synced by the light syncer.
+    headers: HeaderChain,
+    // A channel to ask the light syncer to sync from a peer.
+    sync_sender: Sender<String>,
+}
+
+impl<P: PeerClient> LightNode<P> {
+    pub fn block_height(&self) -> u64 {
+        self.headers.height()
+    }
+
+    pub fn get_header(&self, number: u64) -> Option<BlockHeader> {
+        self.headers.get(number)
+    }
+
+    pub fn get_headers(&self, from_number: u64, count: usize) -> Vec<BlockHeader> {
+        self.headers.get_headers(from_number, count)
+    }
+
+    /// Ask the full peers to prove the state of the account, trying the best peer first.
+    /// This blocks on the p2p requests, so it must not be called from the p2p event loop.
+    pub fn get_account(&self, account: &str) -> Result<ProvenAccount, Error> {
+        for (peer, _) in self.peer_book.peers_ahead(0) {
+            let proof = match self.peer_client.get_account_proof(&peer, account) {
+                Ok(Some(proof)) if proof.block_hash.len() == 32 => proof,
+                Ok(_) => continue,
+                Err(err) => {
+                    error!("❌ Failed to get account proof from {}: {:?}", peer, err);
+                    continue;
+                }
+            };
+
+            let block_hash = Hash::from(proof.block_hash.clone());
+            let Some(header) = self.headers.get_by_hash(&block_hash) else {
+                // The peer is ahead of the synced headers, or on another fork.
+                info!(
+                    "📣 Account proof from {} at unknown block {}",
+                    peer, block_hash
+                );
+                let _ = self.sync_sender.send(peer);
+                continue;
+            };
+
+            match verify_account_proof(&proof, &header, account) {
+                Ok(state) => return Ok(ProvenAccount { state, header }),
+                Err(err) => error!("❌ Bad account proof from {}: {:?}", peer, err),
+            }
+        }
+
+        Err(Error::AccountProofUnavailable(account.to_string()))
+    }
+
+    /// The status of this node, which tells the peers not to sync from it.
+    pub fn status(&self) -> StatusResp {
+        self.peer_book
+            .light_status(self.headers.height(), self.headers.head_hash())
+    }
+
+    /// A peer announced a block, sync the headers from it if it is ahead.
+    pub fn handle_broadcast_header(&self, header: &BlockHeader, source: String) {
+        self.peer_book.update_head(&source, header);
+        if header.number >= self.headers.height() {
+            let _ = self.sync_sender.send(source);
+        }
+    }
+
+    /// A new peer is connected, exchange the status with it, and sync from it if it is ahead.
+    pub fn handle_new_peer(&self, peer_id: String) {
+        let _ = self.sync_sender.send(peer_id);
+    }
+
+    pub fn handle_peer_disconnected(&self, peer_id: &str) {
+        self.peer_book.remove(peer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_channel::{bounded, unbounded};
+
+    use super::*;
+    use crate::{
+        biz::{
+            account_proof, apply_block, state_chunks, ChainSpec, Checkpoints, State, StateChunks,
+        },
+        data::MemoryState,
+        schema::{AccountProof, Block, BlockBody, SignedTx, SnapshotManifest, StateChunk},
+    };
+
+    const ACCOUNT: &str = "0x0000aaaa";
+
+    /// Full peers on the same chain, one of which forges the balances in its proofs.
+    #[derive(Debug, Clone)]
+    struct MockFullPeers {
+        state: MemoryState,
+        book: PeerBook,
+    }
+
+    impl PeerClient for MockFullPeers {
+        fn known_peers(&self) -> Vec<String> {
+            vec!["honest".to_string(), "liar".to_string()]
+        }
+
+        fn get_status(&self, _: &str) -> Result<StatusResp, Error> {
+            Ok(self.book.local_status(&self.state))
+        }
+
+        fn disconnect(&self, _: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_headers(
+            &self,
+            _: &str,
+            from_number: u64,
+            count: u64,
+        ) -> Result<Vec<BlockHeader>, Error> {
+            Ok(self.state.get_headers(from_number, count as usize))
+        }
+
+        fn get_block_bodies(&self, _: &str, _: &[Hash]) -> Result<Vec<BlockBody>, Error> {
+            Ok(vec![])
+        }
+
+        fn get_block_txs(&self, _: &str, _: Hash, _: &[u32]) -> Result<Vec<SignedTx>, Error> {
+            Ok(vec![])
+        }
+
+        fn get_pending_tx_hashes(&self, _: &str) -> Result<Vec<Hash>, Error> {
+            Ok(vec![])
+        }
+
+        fn get_pending_txs(&self, _: &str, _: &[Hash]) -> Result<Vec<SignedTx>, Error> {
+            Ok(vec![])
+        }
+
+        fn get_snapshot_manifest(&self, _: &str) -> Result<Option<SnapshotManifest>, Error> {
+            Ok(None)
+        }
+
+        fn get_snapshot_chunk(
+            &self,
+            _: &str,
+            _: Hash,
+            _: u32,
+        ) -> Result<Option<StateChunk>, Error> {
+            Ok(None)
+        }
+
+        fn get_account_proof(
+            &self,
+            peer_id: &str,
+            account: &str,
+        ) -> Result<Option<AccountProof>, Error> {
+            let Some(block_hash) = self.state.last_block_hash() else {
+                return Ok(None);
+            };
+            let mut balances = self.state.get_balances();
+            if peer_id == "liar" {
+                *balances.entry(account.to_string()).or_default() += 1000;
+            }
+            let chunks = state_chunks(&balances, &self.state.get_account2nonce());
+            Ok(Some(account_proof(block_hash, &chunks, account)))
+        }
+
+        fn broadcast_tx(&self, _: SignedTx) {}
+
+        fn broadcast_block(&self, _: Block) {}
+    }
+
+    /// A full state with a chain of empty blocks, whose state roots include the rewards.
+    fn new_full_state(len: u64) -> MemoryState {
+        let mut balances = HashMap::from([(ACCOUNT.to_string(), 100)]);
+        let mut account2nonce = HashMap::new();
+        let state = MemoryState::new(balances.clone());
+        let mut parent_hash = Hash::default();
+        for number in 0..len {
+            let mut block = Block::new(parent_hash, number, "0x01234567".to_string(), vec![]);
+            apply_block(&mut balances, &mut account2nonce, &block).unwrap();
+            block.set_state_root(StateChunks::new(&balances, &account2nonce).state_root());
+            parent_hash = block.hash();
+            state.add_block(block).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn light_node_verifies_proofs_against_synced_headers() {
+        let chain_spec = ChainSpec {
+            chain_id: "test".to_string(),
+            genesis_hash: Hash::default(),
+            checkpoints: Checkpoints::default(),
+        };
+        let state = new_full_state(5);
+        let peers = MockFullPeers {
+            state: state.clone(),
+            book: PeerBook::new(chain_spec.clone(), 0),
+        };
+
+        let peer_book = PeerBook::new(chain_spec, 0);
+        let headers = HeaderChain::new();
+        let (sync_sender, sync_receiver) = unbounded();
+        let (_shutdown, shutdown) = bounded(0);
+        let syncer = LightSyncer::new(
+            peers.clone(),
+            peer_book.clone(),
+            headers.clone(),
+            sync_receiver,
+            shutdown,
+        );
+        let node = LightNode::new(peers.clone(), peer_book.clone(), headers, sync_sender);
+
+        for peer in peers.known_peers() {
+            assert!(handshake(&peers, &peer_book, &peer));
+        }
+
+        // The proofs are at a block that is not synced yet.
+        assert!(node.get_account(ACCOUNT).is_err());
+
+        syncer.sync_once(None);
+        assert_eq!(node.block_height(), 5);
+        assert_eq!(
+            node.get_header(4).map(|h| h.hash()),
+            state.last_block_hash()
+        );
+        assert!(node.status().light);
+
+        // The forged proof is rejected whichever peer is asked first.
+        let proven = node.get_account(ACCOUNT).unwrap();
+        assert_eq!(proven.state.balance, 100);
+        assert_eq!(proven.header.number, 4);
+        let proven = node.get_account("0x0000bbbb").unwrap();
+        assert_eq!(proven.state.balance, 0);
+
+        peer_book.remove("honest");
+        assert!(node.get_account(ACCOUNT).is_err());
+    }
+}
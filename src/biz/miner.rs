@@ -3,13 +3,20 @@
 //! When a new block is mined, it is broadcasted to other peers.
 //! When a new block is received from other peers, it is added to
 //! the local state and the mining process is canceled.
+//! If the parent of the block is unknown, the block is buffered as an orphan,
+//! and the syncer is asked to sync from the peer that sent it.
+//! A state snapshot is taken every `SNAPSHOT_INTERVAL` blocks, and the snapshots downloaded
+//! by the syncer are imported by the miner, which is the only writer of the state.
+//! The added blocks and pending txs are emitted to the event bus.
+//!
+//! The miner stops when the shutdown channel is closed.
 
 use std::{
     collections::HashMap,
     time::{self, Duration},
 };
 
-use crossbeam_channel::{select, tick, Receiver};
+use crossbeam_channel::{select, tick, Receiver, Sender, TryRecvError};
 use log::{error, info};
 
 use super::*;
@@ -21,6 +28,8 @@ use crate::{
 };
 
 const MINE_INTERVAL: u64 = 20;
+/// The maximum number of orphan blocks to buffer.
+const MAX_ORPHANS: usize = 64;
 
 /// A transaction may be from users or from other peers.
 #[derive(Debug, Default)]
@@ -28,16 +37,49 @@ pub struct TxMsg {
     pub tx: SignedTx,
     /// Transactions from users need to be broadcasted.
     pub need_broadcast: bool,
+    /// The result of the check is sent back to the user, if any.
+    pub reply: Option<Sender<Result<(), Error>>>,
+}
+
+/// A block may be from the syncer or from other peers.
+#[derive(Debug, Default)]
+pub struct BlockMsg {
+    pub block: Block,
+    /// The peer that sent the block, which is asked for the missing parents
+    /// if the block is an orphan.
+    pub source: Option<String>,
+}
+
+/// A state snapshot downloaded and verified by the syncer.
+#[derive(Debug)]
+pub struct SnapshotMsg {
+    /// The block that the snapshot is taken at, which becomes the last block.
+    pub block: Block,
+    pub balances: HashMap<String, u64>,
+    pub account2nonce: HashMap<String, u64>,
+    /// The result of the import is sent back to the syncer.
+    pub reply: Sender<Result<(), Error>>,
 }
 
 #[derive(Debug)]
 pub struct Miner<S: State, P: PeerClient> {
     /// The pending transactions that are not yet included in a block.
-    pending_txs: HashMap<Hash, SignedTx>,
-    /// The pending state that is used to check if a transaction is valid.
+    tx_pool: TxPool,
+    /// The state merging the current `state` and the pending txs in the `tx_pool`, which is
+    /// used to check if a transaction is valid.
     pending_state: PendingState,
+    /// The blocks whose parents are unknown yet, indexed by their hashes.
+    orphans: HashMap<Hash, Block>,
+    /// The chunks of the state at the head, which are updated by every added block.
+    chunks: StateChunks,
+    /// The latest state snapshot and the state at the head, which are served to other peers.
+    snapshot_store: SnapshotStore,
+    /// The bus that the added blocks and pending txs are emitted to.
+    event_bus: EventBus,
     /// The mining difficulty of the blockchain.
     mining_difficulty: usize,
+    /// The trusted blocks that the chain must pass through.
+    checkpoints: Checkpoints,
     // The state of the blockchain.
     state: S,
     // The client to interact with other peers.
@@ -47,38 +89,59 @@ pub struct Miner<S: State, P: PeerClient> {
     /// The receiver of the tx from users and other peers.
     tx_receiver: Receiver<TxMsg>,
     /// The receiver of the block from other peers.
-    block_receiver: Receiver<Block>,
-}
-
-/// `PendingState` merges the current `state` and the `pending_txs`.
-/// It is used to check if an incoming transaction is valid.
-#[derive(Debug, Default)]
-struct PendingState {
-    balances: HashMap<String, u64>,
-    account2nonce: HashMap<String, u64>,
+    block_receiver: Receiver<BlockMsg>,
+    /// The receiver of the state snapshots from the syncer.
+    snapshot_receiver: Receiver<SnapshotMsg>,
+    /// The channel to ask the syncer to sync from a peer.
+    sync_sender: Sender<String>,
+    /// The shutdown signal, which is triggered by closing the channel.
+    shutdown: Receiver<()>,
 }
 
 impl<S: State, P: PeerClient> Miner<S, P> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         state: S,
         peer_client: P,
         author: String,
         mining_difficulty: usize,
+        checkpoints: Checkpoints,
+        tx_pool: TxPool,
+        pending_state: PendingState,
+        snapshot_store: SnapshotStore,
+        event_bus: EventBus,
         tx_receiver: Receiver<TxMsg>,
-        block_receiver: Receiver<Block>,
+        block_receiver: Receiver<BlockMsg>,
+        snapshot_receiver: Receiver<SnapshotMsg>,
+        sync_sender: Sender<String>,
+        shutdown: Receiver<()>,
     ) -> Self {
+        let chunks = StateChunks::new(&state.get_balances(), &state.get_account2nonce());
         let mut state = Self {
-            pending_txs: HashMap::new(),
-            pending_state: PendingState::default(),
+            tx_pool,
+            pending_state,
+            orphans: HashMap::new(),
+            chunks,
+            snapshot_store,
+            event_bus,
             mining_difficulty,
+            checkpoints,
             state,
             peer_client,
             author,
             tx_receiver,
             block_receiver,
+            snapshot_receiver,
+            sync_sender,
+            shutdown,
         };
 
         state.reset_pending_state();
+        // Serve a snapshot of the current state until the next one is taken.
+        if let Some(block) = state.last_block() {
+            state.set_head(&block);
+            state.take_snapshot(&block);
+        }
         state
     }
 
@@ -87,6 +150,11 @@ impl<S: State, P: PeerClient> Miner<S, P> {
 
         loop {
             select! {
+                // The node is shutting down.
+                recv(self.shutdown) -> _ => {
+                    info!("📣 Miner stopped");
+                    return;
+                }
                 // A new transaction is received.
                 recv(self.tx_receiver) -> msg => {
                     if let Ok(tx_msg) = msg {
@@ -95,16 +163,23 @@ impl<S: State, P: PeerClient> Miner<S, P> {
                 }
                 // It's time to mine a new block.
                 recv(ticker) -> _ => {
-                    if self.pending_txs.is_empty() {
+                    if self.tx_pool.is_empty() {
                         continue;
                     }
 
-                    let block = Block::new(
+                    let mut block = Block::new(
                         self.state.last_block_hash().unwrap_or_default(),
                         self.state.block_height(),
                         self.author.clone(),
-                        self.get_sorted_txs(),
+                        self.tx_pool.sorted_txs(),
                     );
+                    match self.chunks.apply_block(&block) {
+                        Ok(chunks) => block.set_state_root(chunks.state_root()),
+                        Err(err) => {
+                            error!("❌ Failed to compute the state root: {:?}", err);
+                            continue;
+                        }
+                    }
 
                     if let Some(block) = self.pow(block) {
                         if self.add_block(block.clone()).is_ok() {
@@ -114,8 +189,14 @@ impl<S: State, P: PeerClient> Miner<S, P> {
                 },
                 // A new block is received.
                 recv(self.block_receiver) -> msg => {
-                    if let Ok(block) = msg {
-                        let _ = self.add_block(block);
+                    if let Ok(block_msg) = msg {
+                        self.handle_block(block_msg);
+                    }
+                }
+                // A state snapshot is downloaded.
+                recv(self.snapshot_receiver) -> msg => {
+                    if let Ok(snapshot_msg) = msg {
+                        self.import_snapshot(snapshot_msg);
                     }
                 }
             }
@@ -130,9 +211,21 @@ impl<S: State, P: PeerClient> Miner<S, P> {
         while !utils::is_valid_hash(&block.hash(), mining_difficulty) {
             // Every time before a new attempt, check if there are any blocks from other peers,
             // if so, cancel this mining.
-            if let Ok(block) = self.block_receiver.try_recv() {
+            if let Ok(block_msg) = self.block_receiver.try_recv() {
                 info!("📣 Received a block from other peers, cancel mining.");
-                let _ = self.add_block(block);
+                self.handle_block(block_msg);
+                return None;
+            }
+
+            // Keep accepting txs, so that the users waiting for the results are not blocked by
+            // the mining. They are mined in the next block.
+            while let Ok(tx_msg) = self.tx_receiver.try_recv() {
+                self.add_pending_tx(tx_msg);
+            }
+
+            // Also cancel this mining if the node is shutting down.
+            if let Err(TryRecvError::Disconnected) = self.shutdown.try_recv() {
+                info!("📣 The node is shutting down, cancel mining.");
                 return None;
             }
 
@@ -162,9 +255,9 @@ impl<S: State, P: PeerClient> Miner<S, P> {
     }
 
     fn check_tx(&self, tx: &SignedTx) -> Result<(), Error> {
-        utils::verify_tx_signature(tx)?;
+        tx.validate()?;
 
-        let from_balance = self.get_pending_balance(&tx.from);
+        let from_balance = self.pending_state.balance(&tx.from);
         if from_balance < tx.cost() {
             return Err(Error::BalanceInsufficient(
                 tx.from.clone(),
@@ -173,7 +266,7 @@ impl<S: State, P: PeerClient> Miner<S, P> {
             ));
         }
 
-        let expected_nonce = self.get_pending_nonce(&tx.from);
+        let expected_nonce = self.pending_state.next_nonce(&tx.from);
         if expected_nonce != tx.nonce {
             return Err(Error::InvalidTxNonce(
                 tx.from.clone(),
@@ -185,59 +278,46 @@ impl<S: State, P: PeerClient> Miner<S, P> {
         Ok(())
     }
 
-    fn update_pending_state(&mut self, tx: &SignedTx) {
-        self.pending_state.balances.insert(
-            tx.from.clone(),
-            self.get_pending_balance(&tx.from) - tx.cost(),
-        );
-
-        self.pending_state
-            .balances
-            .insert(tx.to.clone(), self.get_pending_balance(&tx.to) + tx.value);
-
-        self.pending_state
-            .account2nonce
-            .insert(tx.from.clone(), tx.nonce + 1);
-    }
-
     fn reset_pending_state(&mut self) {
-        // load from `state`
-        self.pending_state.balances = self.state.get_balances();
-        self.pending_state.account2nonce = self.state.get_account2nonce();
-
-        // load from `pending_txs`
-        for tx in self.get_sorted_txs() {
-            self.update_pending_state(&tx);
-        }
+        self.pending_state.reset(
+            self.state.get_balances(),
+            self.state.get_account2nonce(),
+            &self.tx_pool.sorted_txs(),
+        );
     }
 
     fn add_pending_tx(&mut self, tx_msg: TxMsg) {
-        let TxMsg { tx, need_broadcast } = tx_msg;
-        if let Err(err) = self.check_tx(&tx) {
-            error!("❌ Bad tx: {:?}", err);
-            return;
+        let TxMsg {
+            tx,
+            need_broadcast,
+            reply,
+        } = tx_msg;
+        let result = self.check_tx(&tx);
+        match &result {
+            Ok(()) => {
+                self.pending_state.apply_tx(&tx);
+                self.tx_pool.insert(tx.clone());
+                self.event_bus.emit(Event::PendingTx(tx.clone()));
+                if need_broadcast {
+                    self.peer_client.broadcast_tx(tx);
+                }
+            }
+            Err(err) => error!("❌ Bad tx: {:?}", err),
         }
 
-        self.update_pending_state(&tx);
-        self.pending_txs.insert(tx.hash(), tx.clone());
-        if need_broadcast {
-            self.peer_client.broadcast_tx(tx);
+        if let Some(reply) = reply {
+            let _ = reply.send(result);
         }
     }
 
-    fn get_sorted_txs(&self) -> Vec<SignedTx> {
-        let mut txs: Vec<SignedTx> = self.pending_txs.values().cloned().collect();
-        txs.sort_by_key(|tx| tx.timestamp);
-        txs
-    }
-
     fn remove_mined_txs(&mut self, block: &Block) {
         for tx in &block.txs {
-            self.pending_txs.remove(&tx.hash());
+            self.tx_pool.remove(&tx.hash());
         }
     }
 
-    fn check_block(&self, block: &Block) -> Result<(), Error> {
+    /// Check the block itself, regardless of the local chain.
+    fn check_block_hash(&self, block: &Block) -> Result<(), Error> {
         if !utils::is_valid_hash(&block.hash(), self.mining_difficulty) {
             return Err(Error::InvalidBlockHash(
                 block.hash(),
@@ -245,6 +325,19 @@ impl<S: State, P: PeerClient> Miner<S, P> {
             ));
         }
 
+        self.checkpoints.check(block.header())?;
+
+        if Block::compute_txs_hash(&block.txs) != block.txs_hash() {
+            return Err(Error::InvalidBlockTxsHash(block.hash()));
+        }
+
+        Ok(())
+    }
+
+    /// Check the block against the local chain, and return the state chunks after it.
+    fn check_block(&self, block: &Block) -> Result<StateChunks, Error> {
+        self.check_block_hash(block)?;
+
         let last_block_hash = self.state.last_block_hash().unwrap_or_default();
         if last_block_hash != block.parent_hash() {
             return Err(Error::InvalidBlockParent(
@@ -258,37 +351,390 @@ impl<S: State, P: PeerClient> Miner<S, P> {
             return Err(Error::InvalidBlockNumber(expected_number, block.number()));
         }
 
-        Ok(())
+        let chunks = self.chunks.apply_block(block)?;
+        if chunks.state_root() != block.state_root() {
+            return Err(Error::InvalidBlockStateRoot(
+                block.hash(),
+                chunks.state_root(),
+                block.state_root(),
+            ));
+        }
+
+        Ok(chunks)
     }
 
-    fn add_block(&mut self, block: Block) -> Result<(), Error> {
-        if let Err(err) = self.check_block(&block) {
-            error!("❌ Bad block: {:?}", err);
-            return Err(err);
+    fn last_block(&self) -> Option<Block> {
+        self.state
+            .block_height()
+            .checked_sub(1)
+            .and_then(|number| self.state.get_block(number))
+    }
+
+    /// Serve the account proofs from the state at the new head.
+    fn set_head(&self, block: &Block) {
+        self.snapshot_store
+            .set_head(Snapshot::new(block, self.chunks.clone()));
+    }
+
+    fn take_snapshot(&self, block: &Block) {
+        let snapshot = Snapshot::new(block, self.chunks.clone());
+        info!(
+            "📣 Took a state snapshot at block {} with {} chunks",
+            snapshot.number,
+            snapshot.state.chunks().len()
+        );
+        self.snapshot_store.set(snapshot);
+    }
+
+    fn import_snapshot(&mut self, snapshot_msg: SnapshotMsg) {
+        let SnapshotMsg {
+            block,
+            balances,
+            account2nonce,
+            reply,
+        } = snapshot_msg;
+
+        let chunks = StateChunks::new(&balances, &account2nonce);
+        let result = self
+            .state
+            .import_snapshot(block.clone(), balances, account2nonce);
+        match &result {
+            Ok(()) => {
+                info!("📣 Imported the state snapshot at block {}", block.number());
+                self.chunks = chunks;
+                self.set_head(&block);
+                self.event_bus.emit(Event::NewBlock(block.clone()));
+                self.take_snapshot(&block);
+                self.remove_mined_txs(&block);
+                self.reset_pending_state();
+                self.connect_orphans();
+            }
+            Err(err) => error!("❌ Failed to import the state snapshot: {:?}", err),
+        }
+        let _ = reply.send(result);
+    }
+
+    fn handle_block(&mut self, block_msg: BlockMsg) {
+        let BlockMsg { block, source } = block_msg;
+
+        // The parent of a block beyond the tip is unknown, so buffer it until the parent arrives.
+        if block.number() > self.state.block_height() {
+            self.add_orphan(block, source);
+            return;
+        }
+
+        if self.add_block(block).is_ok() {
+            self.connect_orphans();
         }
+    }
+
+    fn add_orphan(&mut self, block: Block, source: Option<String>) {
+        if let Err(err) = self.check_block_hash(&block) {
+            error!("❌ Bad orphan block: {:?}", err);
+            return;
+        }
+
+        info!(
+            "📣 Buffered orphan block '{}' with number {}",
+            block.hash(),
+            block.number()
+        );
+
+        // Evict the block that is furthest from the tip if the buffer is full.
+        if self.orphans.len() >= MAX_ORPHANS {
+            let furthest = self
+                .orphans
+                .iter()
+                .max_by_key(|(_, block)| block.number())
+                .map(|(hash, _)| *hash);
+            if let Some(hash) = furthest {
+                self.orphans.remove(&hash);
+            }
+        }
+        self.orphans.insert(block.hash(), block);
+
+        // The peer that sent the block should have its parents.
+        if let Some(peer) = source {
+            let _ = self.sync_sender.send(peer);
+        }
+    }
+
+    /// Add the orphans that extend the local chain, until no more orphans can be connected.
+    fn connect_orphans(&mut self) {
+        loop {
+            let height = self.state.block_height();
+            self.orphans.retain(|_, block| block.number() >= height);
+
+            let last_block_hash = self.state.last_block_hash().unwrap_or_default();
+            let child = self
+                .orphans
+                .iter()
+                .find(|(_, block)| block.parent_hash() == last_block_hash)
+                .map(|(hash, _)| *hash);
+
+            let Some(block) = child.and_then(|hash| self.orphans.remove(&hash)) else {
+                return;
+            };
+            if self.add_block(block).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn add_block(&mut self, block: Block) -> Result<(), Error> {
+        let chunks = match self.check_block(&block) {
+            Ok(chunks) => chunks,
+            Err(err) => {
+                error!("❌ Bad block: {:?}", err);
+                return Err(err);
+            }
+        };
 
         let result = self.state.add_block(block.clone());
         if result.is_ok() {
+            self.chunks = chunks;
+            self.set_head(&block);
             self.remove_mined_txs(&block);
             self.reset_pending_state();
+            if block.number().is_multiple_of(SNAPSHOT_INTERVAL) {
+                self.take_snapshot(&block);
+            }
+            self.event_bus.emit(Event::NewBlock(block));
         }
 
         result
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_channel::{bounded, unbounded};
+
+    use super::*;
+    use crate::{
+        data::MemoryState,
+        schema::{
+            AccountProof, BlockBody, BlockHeader, SnapshotManifest, StateChunk, StatusResp, Tx,
+        },
+    };
+
+    #[derive(Debug, Clone)]
+    struct NoopPeers;
+
+    impl PeerClient for NoopPeers {
+        fn known_peers(&self) -> Vec<String> {
+            vec![]
+        }
+
+        fn get_status(&self, _: &str) -> Result<StatusResp, Error> {
+            Ok(StatusResp::default())
+        }
+
+        fn disconnect(&self, _: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_headers(&self, _: &str, _: u64, _: u64) -> Result<Vec<BlockHeader>, Error> {
+            Ok(vec![])
+        }
+
+        fn get_block_bodies(&self, _: &str, _: &[Hash]) -> Result<Vec<BlockBody>, Error> {
+            Ok(vec![])
+        }
+
+        fn get_block_txs(&self, _: &str, _: Hash, _: &[u32]) -> Result<Vec<SignedTx>, Error> {
+            Ok(vec![])
+        }
+
+        fn get_pending_tx_hashes(&self, _: &str) -> Result<Vec<Hash>, Error> {
+            Ok(vec![])
+        }
+
+        fn get_pending_txs(&self, _: &str, _: &[Hash]) -> Result<Vec<SignedTx>, Error> {
+            Ok(vec![])
+        }
+
+        fn get_snapshot_manifest(&self, _: &str) -> Result<Option<SnapshotManifest>, Error> {
+            Ok(None)
+        }
+
+        fn get_snapshot_chunk(
+            &self,
+            _: &str,
+            _: Hash,
+            _: u32,
+        ) -> Result<Option<StateChunk>, Error> {
+            Ok(None)
+        }
+
+        fn get_account_proof(&self, _: &str, _: &str) -> Result<Option<AccountProof>, Error> {
+            Ok(None)
+        }
+
+        fn broadcast_tx(&self, _: SignedTx) {}
+
+        fn broadcast_block(&self, _: Block) {}
+    }
 
-    fn get_pending_balance(&self, address: &str) -> u64 {
-        self.pending_state
-            .balances
-            .get(address)
-            .cloned()
-            .unwrap_or_default()
+    /// A miner that is driven by the test instead of its loop, and the receiver of its syncs.
+    fn new_miner(state: MemoryState) -> (Miner<MemoryState, NoopPeers>, Receiver<String>) {
+        let (_, block_receiver) = unbounded();
+        let (_, shutdown) = bounded(0);
+        new_miner_with(state, block_receiver, shutdown)
     }
 
-    fn get_pending_nonce(&self, address: &str) -> u64 {
-        self.pending_state
-            .account2nonce
-            .get(address)
-            .cloned()
-            .unwrap_or_default()
+    fn new_miner_with(
+        state: MemoryState,
+        block_receiver: Receiver<BlockMsg>,
+        shutdown: Receiver<()>,
+    ) -> (Miner<MemoryState, NoopPeers>, Receiver<String>) {
+        let (_, tx_receiver) = unbounded();
+        let (_, snapshot_receiver) = unbounded();
+        let (sync_sender, sync_receiver) = unbounded();
+        let miner = Miner::new(
+            state,
+            NoopPeers,
+            "0x01234567".to_string(),
+            0,
+            Checkpoints::default(),
+            TxPool::new(),
+            PendingState::new(),
+            SnapshotStore::new(),
+            EventBus::new(),
+            tx_receiver,
+            block_receiver,
+            snapshot_receiver,
+            sync_sender,
+            shutdown,
+        );
+        (miner, sync_receiver)
+    }
+
+    #[test]
+    fn rejected_txs_are_replied() {
+        let (mut miner, _) = new_miner(MemoryState::new(HashMap::new()));
+        let (reply, result) = bounded(1);
+        miner.add_pending_tx(TxMsg {
+            tx: SignedTx {
+                tx: Some(Tx::new("0x0000aaaa", "0x0000bbbb", 100, 0)),
+                sig: vec![0; 65],
+            },
+            need_broadcast: true,
+            reply: Some(reply),
+        });
+
+        assert!(matches!(result.recv().unwrap(), Err(Error::InvalidTx(_))));
+        assert!(miner.tx_pool.is_empty());
+    }
+
+    #[test]
+    fn orphan_blocks_are_buffered_and_connected() {
+        let root = StateChunks::default().state_root();
+        let mut parent_hash = Hash::default();
+        let chain: Vec<Block> = (0..3)
+            .map(|number| {
+                let mut block = Block::new(parent_hash, number, "0x01234567".to_string(), vec![]);
+                block.set_state_root(root);
+                parent_hash = block.hash();
+                block
+            })
+            .collect();
+
+        let state = MemoryState::new(HashMap::new());
+        let (mut miner, sync_receiver) = new_miner(state.clone());
+        let mut events = miner.event_bus.subscribe();
+
+        // The blocks beyond the tip are buffered, and a sync from the sender is triggered.
+        for block in chain[1..].iter().rev() {
+            miner.handle_block(BlockMsg {
+                block: block.clone(),
+                source: Some("peer".to_string()),
+            });
+        }
+        assert_eq!(state.block_height(), 0);
+        assert_eq!(miner.orphans.len(), 2);
+        assert_eq!(sync_receiver.try_iter().count(), 2);
+
+        // Once the missing parent arrives, the orphans are connected.
+        miner.handle_block(BlockMsg {
+            block: chain[0].clone(),
+            source: None,
+        });
+        assert_eq!(state.block_height(), 3);
+        assert_eq!(state.last_block_hash(), Some(chain[2].hash()));
+        assert!(miner.orphans.is_empty());
+
+        // The connected blocks are emitted in order.
+        for block in &chain {
+            assert!(matches!(events.try_recv(), Ok(Event::NewBlock(b)) if b == *block));
+        }
+    }
+    #[test]
+    fn received_blocks_are_emitted_to_the_bus() {
+        let (block_sender, block_receiver) = unbounded();
+        let (shutdown_sender, shutdown) = bounded(0);
+        let (mut miner, _) =
+            new_miner_with(MemoryState::new(HashMap::new()), block_receiver, shutdown);
+        let mut events = miner.event_bus.subscribe();
+        let worker = std::thread::spawn(move || miner.mine());
+
+        let mut block = Block::new(Hash::default(), 0, "0x01234567".to_string(), vec![]);
+        block.set_state_root(StateChunks::default().state_root());
+        block_sender
+            .send(BlockMsg {
+                block: block.clone(),
+                source: Some("peer".to_string()),
+            })
+            .unwrap();
+        let event = events.blocking_recv().unwrap();
+        assert!(matches!(event, Event::NewBlock(b) if b == block));
+
+        drop(shutdown_sender);
+        worker.join().unwrap();
+    }
+
+    #[test]
+    fn imported_snapshots_are_emitted_as_new_heads() {
+        let (mut miner, _) = new_miner(MemoryState::new(HashMap::new()));
+        let mut events = miner.event_bus.subscribe();
+
+        let mut block = Block::new(Hash::default(), 5, "0x01234567".to_string(), vec![]);
+        block.set_state_root(StateChunks::default().state_root());
+        let (reply, result) = unbounded();
+        miner.import_snapshot(SnapshotMsg {
+            block: block.clone(),
+            balances: HashMap::new(),
+            account2nonce: HashMap::new(),
+            reply,
+        });
+        assert!(result.recv().unwrap().is_ok());
+        assert!(matches!(events.try_recv(), Ok(Event::NewBlock(b)) if b == block));
+    }
+
+    #[test]
+    fn account_proofs_follow_the_head() {
+        let balances = HashMap::from([("0x0000aaaa".to_string(), 1000)]);
+        let state = MemoryState::new(balances.clone());
+        let (mut miner, _) = new_miner(state.clone());
+        assert!(miner.snapshot_store.account_proof("0x0000aaaa").is_none());
+
+        let mut balances = balances;
+        let mut account2nonce = HashMap::new();
+        let mut parent_hash = Hash::default();
+        for number in 0..2 {
+            let tx = SignedTx {
+                tx: Some(Tx::new("0x0000aaaa", "0x0000bbbb", 100, number)),
+                sig: vec![0; 65],
+            };
+            let mut block = Block::new(parent_hash, number, "0x01234567".to_string(), vec![tx]);
+            apply_block(&mut balances, &mut account2nonce, &block).unwrap();
+            block.set_state_root(StateChunks::new(&balances, &account2nonce).state_root());
+            parent_hash = block.hash();
+            miner.add_block(block.clone()).unwrap();
+
+            let proof = miner.snapshot_store.account_proof("0x0000bbbb").unwrap();
+            let account = verify_account_proof(&proof, block.header(), "0x0000bbbb").unwrap();
+            assert_eq!(account.balance, 100 * (number + 1));
+        }
     }
 }
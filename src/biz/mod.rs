@@ -1,43 +1,126 @@
-use std::thread;
+use std::thread::{self, JoinHandle};
 
-use crossbeam_channel::unbounded;
+use crossbeam_channel::{unbounded, Receiver};
 use wallet::Wallet;
 
+mod event_bus;
 mod genesis;
+mod light;
 mod miner;
 mod node;
+mod peer_book;
 mod peer_client;
+mod pending_state;
+mod snapshot;
 mod state;
 mod syncer;
+mod tx_pool;
 
-pub use self::{genesis::*, node::*, peer_client::*, state::*};
-use self::{miner::Miner, syncer::Syncer};
+pub use self::light::{HeaderChain, LightNode, ProvenAccount};
+pub use self::{
+    event_bus::*, genesis::*, node::*, peer_book::*, peer_client::*, pending_state::*, snapshot::*,
+    state::*, tx_pool::*,
+};
+use self::{light::LightSyncer, miner::Miner, syncer::Syncer};
 
 const MINING_DIFFICULTY: usize = 2;
 
 /// When new a node, we need to start the miner and the syncer in the background.
+/// They stop when the `shutdown` channel is closed, and the returned handles can be
+/// used to wait for them to finish.
 pub fn new_node<S: State, P: PeerClient>(
     author: String,
     state: S,
     peer_client: P,
     wallet: Wallet,
-) -> Node<S> {
+    chain_spec: ChainSpec,
+    snapshot_sync: bool,
+    shutdown: Receiver<()>,
+) -> (Node<S>, Vec<JoinHandle<()>>) {
+    let checkpoints = chain_spec.checkpoints.clone();
+    let peer_book = PeerBook::new(chain_spec, MINING_DIFFICULTY);
+    let tx_pool = TxPool::new();
+    let pending_state = PendingState::new();
+    let snapshot_store = SnapshotStore::new();
+    let event_bus = EventBus::new();
     let (tx_sender, tx_receiver) = unbounded();
     let (block_sender, block_receiver) = unbounded();
+    let (sync_sender, sync_receiver) = unbounded();
+    let (compact_block_sender, compact_block_receiver) = unbounded();
+    let (snapshot_sender, snapshot_receiver) = unbounded();
 
     let mut miner = Miner::new(
         state.clone(),
         peer_client.clone(),
         author,
         MINING_DIFFICULTY,
+        checkpoints,
+        tx_pool.clone(),
+        pending_state.clone(),
+        snapshot_store.clone(),
+        event_bus.clone(),
         tx_receiver,
         block_receiver,
+        snapshot_receiver,
+        sync_sender.clone(),
+        shutdown.clone(),
     );
 
-    let syncer = Syncer::new(state.clone(), peer_client, block_sender.clone());
+    let syncer = Syncer::new(
+        state.clone(),
+        peer_client,
+        snapshot_sync,
+        peer_book.clone(),
+        tx_pool.clone(),
+        tx_sender.clone(),
+        block_sender.clone(),
+        snapshot_sender,
+        sync_receiver,
+        compact_block_receiver,
+        shutdown,
+    );
+
+    let workers = vec![
+        thread::spawn(move || miner.mine()),
+        thread::spawn(move || syncer.sync()),
+    ];
+
+    let node = Node::new(
+        state,
+        wallet,
+        peer_book,
+        tx_pool,
+        pending_state,
+        snapshot_store,
+        event_bus,
+        tx_sender,
+        block_sender,
+        sync_sender,
+        compact_block_sender,
+    );
+    (node, workers)
+}
+
+/// A light node only needs the syncer of the headers in the background, which stops when the
+/// `shutdown` channel is closed.
+pub fn new_light_node<P: PeerClient>(
+    peer_client: P,
+    chain_spec: ChainSpec,
+    shutdown: Receiver<()>,
+) -> (LightNode<P>, Vec<JoinHandle<()>>) {
+    let peer_book = PeerBook::new(chain_spec, MINING_DIFFICULTY);
+    let headers = HeaderChain::new();
+    let (sync_sender, sync_receiver) = unbounded();
 
-    thread::spawn(move || miner.mine());
-    thread::spawn(move || syncer.sync());
+    let syncer = LightSyncer::new(
+        peer_client.clone(),
+        peer_book.clone(),
+        headers.clone(),
+        sync_receiver,
+        shutdown,
+    );
+    let workers = vec![thread::spawn(move || syncer.sync())];
 
-    Node::new(state, wallet, tx_sender, block_sender)
+    let node = LightNode::new(peer_client, peer_book, headers, sync_sender);
+    (node, workers)
 }
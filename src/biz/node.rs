@@ -1,11 +1,40 @@
 use std::{collections::HashMap, ops::Deref, sync::Arc};
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{bounded, Sender};
+use log::error;
+use tokio::sync::broadcast;
 use wallet::Wallet;
 
 use crate::{error::Error, schema::*, types::Hash};
 
-use super::{miner::TxMsg, State};
+use super::{
+    miner::{BlockMsg, TxMsg},
+    syncer::CompactBlockMsg,
+    BlockId, Event, EventBus, PeerBook, PendingState, SnapshotStore, State, TxDirection,
+    TxLocation, TxPool,
+};
+
+/// A tx found by its hash.
+#[derive(Debug, Clone)]
+pub enum TxLookup {
+    /// The tx is waiting in the tx pool.
+    Pending(SignedTx),
+    /// The tx is included in a block, which is the first confirmation.
+    Included {
+        tx: SignedTx,
+        receipt: TxReceipt,
+        confirmations: u64,
+    },
+}
+
+/// The confirmed state of an account, and its state after the pending txs.
+#[derive(Debug, Clone)]
+pub struct AccountDetail {
+    pub confirmed: AccountState,
+    pub pending: AccountState,
+    /// The pending txs sent or received by the account, sorted by the timestamp.
+    pub pending_txs: Vec<SignedTx>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Node<S: State> {
@@ -13,18 +42,33 @@ pub struct Node<S: State> {
 }
 
 impl<S: State> Node<S> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         state: S,
         wallet: Wallet,
+        peer_book: PeerBook,
+        tx_pool: TxPool,
+        pending_state: PendingState,
+        snapshot_store: SnapshotStore,
+        event_bus: EventBus,
         tx_sender: Sender<TxMsg>,
-        block_sender: Sender<Block>,
+        block_sender: Sender<BlockMsg>,
+        sync_sender: Sender<String>,
+        compact_block_sender: Sender<CompactBlockMsg>,
     ) -> Self {
         Self {
             inner: Arc::new(NodeInner {
                 state,
                 wallet,
+                peer_book,
+                tx_pool,
+                pending_state,
+                snapshot_store,
+                event_bus,
                 tx_sender,
                 block_sender,
+                sync_sender,
+                compact_block_sender,
             }),
         }
     }
@@ -42,10 +86,24 @@ impl<S: State> Deref for Node<S> {
 pub struct NodeInner<S: State> {
     // A state machine that holds the state of the blockchain.
     state: S,
+    // The cached statuses of the peers.
+    peer_book: PeerBook,
+    // The pending txs, which are only written by the miner.
+    tx_pool: TxPool,
+    // The state after the pending txs, which is only written by the miner.
+    pending_state: PendingState,
+    // The latest state snapshot and the state at the head, which are only set by the miner.
+    snapshot_store: SnapshotStore,
+    // The bus of the events emitted by the miner.
+    event_bus: EventBus,
     // A channel to send a signed transaction to the miner.
     tx_sender: Sender<TxMsg>,
     // A channel to send a block to the miner.
-    block_sender: Sender<Block>,
+    block_sender: Sender<BlockMsg>,
+    // A channel to ask the syncer to sync from a peer.
+    sync_sender: Sender<String>,
+    // A channel to ask the syncer to fetch the missing txs of a compact block.
+    compact_block_sender: Sender<CompactBlockMsg>,
 
     // For facilitating a smooth demonstration, the node holds a wallet that stores all
     // the keys of the users, so that it can sign transactions on behalf of the users.
@@ -60,29 +118,222 @@ impl<S: State> Node<S> {
         self.state.next_account_nonce(account)
     }
 
-    pub fn transfer(&self, from: &str, to: &str, value: u64, nonce: u64) -> Result<(), Error> {
+    /// Sign and submit a transfer, and return the hash of the tx.
+    pub fn transfer(&self, from: &str, to: &str, value: u64, nonce: u64) -> Result<Hash, Error> {
         let tx = Tx::new(from, to, value, nonce);
         let signed_tx = self.sign_tx(tx)?;
-        let _ = self.tx_sender.send(TxMsg {
-            tx: signed_tx,
-            need_broadcast: true,
-        });
+        self.submit_tx(signed_tx)
+    }
+
+    /// Submit a tx signed by the user, which is broadcasted once the miner accepts it.
+    /// Block until the miner checks the tx, and return the hash of the tx if it's accepted.
+    pub fn submit_tx(&self, tx: SignedTx) -> Result<Hash, Error> {
+        tx.validate()?;
+        let hash = tx.hash();
+        let (reply_sender, reply_receiver) = bounded(1);
+        self.tx_sender
+            .send(TxMsg {
+                tx,
+                need_broadcast: true,
+                reply: Some(reply_sender),
+            })
+            .map_err(|_| Error::MinerUnavailable)?;
+
+        // The reply is dropped without a result if the miner stops.
+        reply_receiver
+            .recv()
+            .map_err(|_| Error::MinerUnavailable)?
+            .map(|_| hash)
+    }
+
+    /// Find a tx in the chain or in the tx pool.
+    pub fn get_tx(&self, hash: &Hash) -> Option<TxLookup> {
+        // The miner adds a block before removing its txs from the pool, so a tx missing from
+        // both may have been mined in the meantime, and the chain is checked again.
+        self.get_included_tx(hash)
+            .or_else(|| self.tx_pool.get_txs(&[*hash]).pop().map(TxLookup::Pending))
+            .or_else(|| self.get_included_tx(hash))
+    }
+
+    fn get_included_tx(&self, hash: &Hash) -> Option<TxLookup> {
+        let receipt = self.state.get_tx_receipt(hash)?;
+        let height = self.state.block_height();
+        let block = self.state.get_block(receipt.block_number)?;
+        let tx = block.txs.get(receipt.index as usize)?.clone();
+
+        Some(TxLookup::Included {
+            tx,
+            confirmations: height - receipt.block_number,
+            receipt,
+        })
+    }
+
+    /// Get at most `limit` included txs sent or received by the account, see
+    /// `State::get_account_tx_locations`.
+    pub fn get_account_txs(
+        &self,
+        account: &str,
+        direction: Option<TxDirection>,
+        from: TxLocation,
+        to_number: Option<u64>,
+        limit: usize,
+    ) -> Vec<(TxLocation, TxLookup)> {
+        let height = self.state.block_height();
+        let locations = self
+            .state
+            .get_account_tx_locations(account, direction, from, to_number, limit);
 
-        Ok(())
+        let mut block: Option<Block> = None;
+        locations
+            .into_iter()
+            .filter_map(|location| {
+                // The txs of the same block are next to each other.
+                if block.as_ref().map(|block| block.number()) != Some(location.number) {
+                    block = self.state.get_block(location.number);
+                }
+                // The block may be missing, e.g. on a node started from a snapshot.
+                let block = block.as_ref()?;
+                let tx = block.txs.get(location.index as usize)?.clone();
+                let receipt = TxReceipt::new(&tx, block.hash(), location.number, location.index);
+                let lookup = TxLookup::Included {
+                    tx,
+                    receipt,
+                    confirmations: height - location.number,
+                };
+                Some((location, lookup))
+            })
+            .collect()
     }
 
-    pub fn get_blocks(&self, from_number: u64) -> Vec<Block> {
-        self.state.get_blocks(from_number)
+    pub fn get_blocks(
+        &self,
+        from_number: u64,
+        to_number: Option<u64>,
+        limit: usize,
+        reverse: bool,
+    ) -> Vec<Block> {
+        self.state
+            .get_blocks(from_number, to_number, limit, reverse)
     }
 
     pub fn get_block(&self, number: u64) -> Option<Block> {
         self.state.get_block(number)
     }
 
+    pub fn get_block_by_hash(&self, hash: &Hash) -> Option<Block> {
+        self.state.get_block_by_hash(hash)
+    }
+
+    /// The number of the first block kept, which is above 0 if the node started from a snapshot.
+    pub fn lowest_block_number(&self) -> u64 {
+        self.state.lowest_block_number()
+    }
+
+    pub fn get_headers(&self, from_number: u64, count: usize) -> Vec<BlockHeader> {
+        self.state.get_headers(from_number, count)
+    }
+
+    /// Get the bodies of the blocks, stopping at the first unknown block.
+    pub fn get_block_bodies(&self, hashes: &[Hash]) -> Vec<BlockBody> {
+        hashes
+            .iter()
+            .map_while(|hash| self.state.get_block_by_hash(hash))
+            .map(|block| BlockBody { txs: block.txs })
+            .collect()
+    }
+
+    /// Get the txs of a block by their positions, stopping at the first invalid position.
+    pub fn get_block_txs(&self, block_hash: &Hash, indexes: &[u32]) -> Vec<SignedTx> {
+        let Some(block) = self.state.get_block_by_hash(block_hash) else {
+            return vec![];
+        };
+
+        indexes
+            .iter()
+            .map_while(|index| block.txs.get(*index as usize).cloned())
+            .collect()
+    }
+
     pub fn get_balances(&self) -> HashMap<String, u64> {
         self.state.get_balances()
     }
 
+    /// Get all the balances after applying the block, and the hash of the block.
+    pub fn get_balances_at(&self, at: &BlockId) -> Result<(Hash, HashMap<String, u64>), Error> {
+        let block = self.resolve_block(at)?;
+        let balances = self
+            .state
+            .get_balances_at(block.number())
+            .ok_or(Error::HistoryUnavailable(block.number()))?;
+
+        Ok((block.hash(), balances))
+    }
+
+    /// Get the balance and the nonce of the account after applying the block, or the latest ones
+    /// if no block is given. The header of the block is returned as well, if any.
+    pub fn get_account(
+        &self,
+        account: &str,
+        at: Option<&BlockId>,
+    ) -> Result<(AccountState, Option<BlockHeader>), Error> {
+        let Some(at) = at else {
+            let header = self
+                .state
+                .last_block_hash()
+                .and_then(|hash| self.state.get_block_by_hash(&hash))
+                .and_then(|block| block.header);
+            let state = AccountState {
+                account: account.to_string(),
+                balance: self.state.get_balance(account),
+                nonce: self.state.next_account_nonce(account),
+            };
+            return Ok((state, header));
+        };
+
+        let block = self.resolve_block(at)?;
+        let state = self
+            .state
+            .get_account_at(account, block.number())
+            .ok_or(Error::HistoryUnavailable(block.number()))?;
+
+        Ok((state, block.header))
+    }
+
+    /// Get the confirmed and the pending states of the account.
+    /// They are read one after the other, so a block added in the meantime may be in both.
+    pub fn get_account_detail(&self, account: &str) -> AccountDetail {
+        let confirmed = AccountState {
+            account: account.to_string(),
+            balance: self.state.get_balance(account),
+            nonce: self.state.next_account_nonce(account),
+        };
+        let pending = AccountState {
+            account: account.to_string(),
+            balance: self.pending_state.balance(account),
+            nonce: self.pending_state.next_nonce(account),
+        };
+        let pending_txs = self
+            .tx_pool
+            .sorted_txs()
+            .into_iter()
+            .filter(|tx| tx.from == account || tx.to == account)
+            .collect();
+
+        AccountDetail {
+            confirmed,
+            pending,
+            pending_txs,
+        }
+    }
+
+    fn resolve_block(&self, id: &BlockId) -> Result<Block, Error> {
+        match id {
+            BlockId::Number(number) => self.state.get_block(*number),
+            BlockId::Hash(hash) => self.state.get_block_by_hash(hash),
+        }
+        .ok_or_else(|| Error::UnknownBlock(id.to_string()))
+    }
+
     pub fn block_height(&self) -> u64 {
         self.state.block_height()
     }
@@ -91,14 +342,83 @@ impl<S: State> Node<S> {
         self.state.last_block_hash()
     }
 
-    pub fn handle_broadcast_block(&self, block: Block) {
-        let _ = self.block_sender.send(block);
+    pub fn pending_tx_hashes(&self) -> Vec<Hash> {
+        self.tx_pool.hashes()
+    }
+
+    pub fn get_pending_txs(&self, hashes: &[Hash]) -> Vec<SignedTx> {
+        self.tx_pool.get_txs(hashes)
+    }
+
+    pub fn snapshot_manifest(&self) -> Option<SnapshotManifest> {
+        self.snapshot_store.manifest()
+    }
+
+    pub fn get_snapshot_chunk(&self, block_hash: &Hash, index: usize) -> Option<StateChunk> {
+        self.snapshot_store.chunk(block_hash, index)
+    }
+
+    /// Prove the state of the account at the head, or `None` if there is no block yet. The
+    /// proof is built from the state chunks kept by the miner, which match the head block.
+    pub fn account_proof(&self, account: &str) -> Option<AccountProof> {
+        self.snapshot_store.account_proof(account)
+    }
+
+    /// Subscribe to the chain events from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.event_bus.subscribe()
+    }
+
+    /// The status of this node, which is sent to other peers.
+    pub fn status(&self) -> StatusResp {
+        self.peer_book.local_status(&self.state)
+    }
+
+    /// Rebuild the block from the local tx pool. If any tx is missing, the syncer fetches it
+    /// from the peers, so that the p2p event loop is never blocked.
+    pub fn handle_broadcast_compact_block(&self, compact: CompactBlock, source: String) {
+        if let Err(err) = compact.check() {
+            error!("❌ Dropping compact block from {}: {:?}", source, err);
+            return;
+        }
+        self.peer_book.update_head(&source, compact.header());
+
+        let txs: Option<Vec<SignedTx>> = self
+            .tx_pool
+            .get_txs_by_short_ids(&compact.short_tx_ids)
+            .into_iter()
+            .collect();
+        if let Some(txs) = txs {
+            let body = BlockBody { txs };
+            if compact.header().matches_body(&body) {
+                let block = Block::from_parts(compact.header.unwrap(), body);
+                let _ = self.block_sender.send(BlockMsg {
+                    block,
+                    source: Some(source),
+                });
+                return;
+            }
+        }
+
+        let _ = self
+            .compact_block_sender
+            .send(CompactBlockMsg { compact, source });
+    }
+
+    /// A new peer is connected, exchange the status with it, and sync from it if it is ahead.
+    pub fn handle_new_peer(&self, peer_id: String) {
+        let _ = self.sync_sender.send(peer_id);
+    }
+
+    pub fn handle_peer_disconnected(&self, peer_id: &str) {
+        self.peer_book.remove(peer_id);
     }
 
     pub fn handle_broadcast_tx(&self, tx: SignedTx) {
         let _ = self.tx_sender.send(TxMsg {
             tx,
             need_broadcast: false,
+            reply: None,
         });
     }
 
@@ -0,0 +1,298 @@
+//! The status handshake between peers.
+//!
+//! When a peer is connected, the syncer asks for its status, and disconnects it if it is on
+//! another chain. The statuses are cached in the `PeerBook`, so that the best peers are known
+//! without asking every peer again. The cached heads are updated by the blocks the peers send.
+//!
+//! A status is only a claim: the head header is checked on the handshake, but the chain below it
+//! is only checked as its headers are downloaded, and the peers serving bad headers are removed.
+//!
+//! Light peers are cached as well, but they are never picked to sync from since they only have
+//! the headers.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    error::Error,
+    schema::{BlockHeader, StatusResp},
+    types::Hash,
+    utils,
+};
+
+use super::{ChainSpec, Checkpoints, State};
+
+/// The version of the p2p protocol, which is bumped on incompatible changes.
+pub const PROTOCOL_VERSION: u32 = 4;
+
+#[derive(Debug, Clone)]
+pub struct PeerBook {
+    /// The chain that this node is on.
+    chain_spec: ChainSpec,
+    /// The mining difficulty, which is used to verify the blocks from the peers.
+    mining_difficulty: usize,
+    /// The expected number of hashes to mine a block.
+    block_work: u64,
+    /// The statuses of the peers on the same chain, indexed by the peer ids.
+    statuses: Arc<RwLock<HashMap<String, StatusResp>>>,
+}
+
+impl PeerBook {
+    pub fn new(chain_spec: ChainSpec, mining_difficulty: usize) -> Self {
+        Self {
+            chain_spec,
+            mining_difficulty,
+            block_work: 256u64.saturating_pow(mining_difficulty as u32),
+            statuses: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn checkpoints(&self) -> &Checkpoints {
+        &self.chain_spec.checkpoints
+    }
+
+    pub fn mining_difficulty(&self) -> usize {
+        self.mining_difficulty
+    }
+
+    /// The status of this node, which is sent to other peers.
+    pub fn local_status<S: State>(&self, state: &S) -> StatusResp {
+        self.status_of(
+            state.lowest_block_number(),
+            state.block_height(),
+            state.last_block_hash(),
+            false,
+        )
+    }
+
+    /// The status of a light node, whose head is the last synced header.
+    pub fn light_status(&self, height: u64, head_hash: Option<Hash>) -> StatusResp {
+        self.status_of(0, height, head_hash, true)
+    }
+
+    /// Cache the status of the peer if it is on the same chain.
+    pub fn insert(&self, peer_id: &str, status: StatusResp) -> Result<(), Error> {
+        self.check_status(&status)?;
+        self.statuses
+            .write()
+            .unwrap()
+            .insert(peer_id.to_string(), status);
+
+        Ok(())
+    }
+
+    pub fn remove(&self, peer_id: &str) {
+        self.statuses.write().unwrap().remove(peer_id);
+    }
+
+    pub fn contains(&self, peer_id: &str) -> bool {
+        self.statuses.read().unwrap().contains_key(peer_id)
+    }
+
+    /// Get the ids of all the peers on the same chain.
+    pub fn peer_ids(&self) -> Vec<String> {
+        self.statuses.read().unwrap().keys().cloned().collect()
+    }
+
+    /// The peer has the block it sent us, so its head moves to the block if the block is mined on
+    /// top of its head. Any other block is ignored, so that a peer can't claim an arbitrary height
+    /// without the work; its status is exchanged again when syncing from it.
+    pub fn update_head(&self, peer_id: &str, header: &BlockHeader) {
+        let hash = header.hash();
+        if !utils::is_valid_hash(&hash, self.mining_difficulty) {
+            return;
+        }
+
+        let mut statuses = self.statuses.write().unwrap();
+        let Some(status) = statuses.get_mut(peer_id) else {
+            return;
+        };
+
+        if header.number == status.height && header.parent_hash == status.head_hash {
+            status.height += 1;
+            status.head_hash = hash.to_vec();
+            status.total_work = self.total_work(status.height);
+        }
+    }
+
+    /// Get the full peers whose height is greater than the `local_height`, the one with the most
+    /// total work comes first.
+    pub fn peers_ahead(&self, local_height: u64) -> Vec<(String, u64)> {
+        let statuses = self.statuses.read().unwrap();
+        let mut peers: Vec<_> = statuses
+            .iter()
+            .filter(|(_, status)| !status.light && status.height > local_height)
+            .collect();
+        peers.sort_by_key(|(_, status)| std::cmp::Reverse(status.total_work));
+
+        peers
+            .into_iter()
+            .map(|(peer, status)| (peer.clone(), status.height))
+            .collect()
+    }
+
+    /// Check if the full peer has the blocks from the `number` on, which it doesn't if it
+    /// started from a later snapshot.
+    pub fn has_blocks_from(&self, peer_id: &str, number: u64) -> bool {
+        self.statuses
+            .read()
+            .unwrap()
+            .get(peer_id)
+            .is_some_and(|status| !status.light && status.lowest_number <= number)
+    }
+
+    fn status_of(
+        &self,
+        lowest_number: u64,
+        height: u64,
+        head_hash: Option<Hash>,
+        light: bool,
+    ) -> StatusResp {
+        StatusResp {
+            protocol_version: PROTOCOL_VERSION,
+            chain_id: self.chain_spec.chain_id.clone(),
+            genesis_hash: self.chain_spec.genesis_hash.to_vec(),
+            head_hash: head_hash.unwrap_or_default().to_vec(),
+            height,
+            total_work: self.total_work(height),
+            light,
+            lowest_number,
+        }
+    }
+
+    fn check_status(&self, status: &StatusResp) -> Result<(), Error> {
+        if status.protocol_version != PROTOCOL_VERSION {
+            return Err(Error::InvalidPeerStatus(format!(
+                "protocol version: expected '{}', got '{}'",
+                PROTOCOL_VERSION, status.protocol_version
+            )));
+        }
+
+        if status.chain_id != self.chain_spec.chain_id {
+            return Err(Error::InvalidPeerStatus(format!(
+                "chain id: expected '{}', got '{}'",
+                self.chain_spec.chain_id, status.chain_id
+            )));
+        }
+
+        if status.genesis_hash != self.chain_spec.genesis_hash.to_vec() {
+            return Err(Error::InvalidPeerStatus(format!(
+                "genesis hash: expected '{}', got '0x{}'",
+                self.chain_spec.genesis_hash,
+                hex::encode(&status.genesis_hash)
+            )));
+        }
+
+        if status.head_hash.len() != Hash::default().len() {
+            return Err(Error::InvalidPeerStatus("malformed head hash".to_string()));
+        }
+
+        // The peers are ranked by the total work, which is only backed by the height.
+        let total_work = self.total_work(status.height);
+        if status.total_work != total_work {
+            return Err(Error::InvalidPeerStatus(format!(
+                "total work of height {}: expected '{}', got '{}'",
+                status.height, total_work, status.total_work
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn total_work(&self, height: u64) -> u64 {
+        height.saturating_mul(self.block_work)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data::MemoryState, schema::Block};
+
+    fn new_peer_book() -> PeerBook {
+        let chain_spec = ChainSpec {
+            chain_id: "test".to_string(),
+            genesis_hash: Hash::from([1u8; 32]),
+            checkpoints: Checkpoints::default(),
+        };
+        PeerBook::new(chain_spec, 1)
+    }
+
+    fn status_of(book: &PeerBook, height: u64) -> StatusResp {
+        let mut status = book.local_status(&MemoryState::new(HashMap::new()));
+        status.height = height;
+        status.total_work = book.total_work(height);
+        status
+    }
+
+    #[test]
+    fn peers_on_another_chain_are_rejected() {
+        let book = new_peer_book();
+        assert!(book.insert("same", status_of(&book, 0)).is_ok());
+
+        let mut status = status_of(&book, 0);
+        status.chain_id = "other".to_string();
+        assert!(book.insert("other-chain", status).is_err());
+
+        let mut status = status_of(&book, 0);
+        status.genesis_hash = vec![2u8; 32];
+        assert!(book.insert("other-genesis", status).is_err());
+
+        let mut status = status_of(&book, 0);
+        status.protocol_version += 1;
+        assert!(book.insert("other-version", status).is_err());
+
+        // A peer can't claim more work than its height is worth to be picked first.
+        let mut status = status_of(&book, 2);
+        status.total_work = u64::MAX;
+        assert!(book.insert("inflated", status).is_err());
+        assert!(book.peers_ahead(0).is_empty());
+
+        assert!(book.contains("same"));
+        assert!(!book.contains("other-chain"));
+    }
+
+    #[test]
+    fn peers_ahead_are_sorted_by_total_work() {
+        let book = new_peer_book();
+        book.insert("a", status_of(&book, 3)).unwrap();
+        book.insert("b", status_of(&book, 5)).unwrap();
+        book.insert("c", status_of(&book, 1)).unwrap();
+        book.insert("light", book.light_status(9, None)).unwrap();
+
+        let peers = book.peers_ahead(1);
+        assert_eq!(peers, vec![("b".to_string(), 5), ("a".to_string(), 3)]);
+
+        let mine = |number: u64, parent_hash: Hash| {
+            let mut block = Block::new(parent_hash, number, "0x01234567".to_string(), vec![]);
+            while !utils::is_valid_hash(&block.hash(), 1) {
+                block.update_nonce_and_time();
+            }
+            block
+        };
+
+        // A block that is not mined is ignored.
+        let mut block = Block::new(Hash::default(), 1, "0x01234567".to_string(), vec![]);
+        while utils::is_valid_hash(&block.hash(), 1) {
+            block.update_nonce_and_time();
+        }
+        book.update_head("c", block.header());
+        assert_eq!(book.peers_ahead(1).len(), 2);
+
+        // A mined block on top of the head of a peer moves its head forward.
+        let block = mine(1, Hash::default());
+        book.update_head("c", block.header());
+        assert_eq!(book.peers_ahead(1)[2], ("c".to_string(), 2));
+
+        // A mined block which doesn't extend the head can't make the peer claim its height.
+        book.update_head("c", mine(9, Hash::default()).header());
+        assert_eq!(book.peers_ahead(1)[2], ("c".to_string(), 2));
+        book.update_head("c", mine(2, block.hash()).header());
+        assert!(book.peers_ahead(1).contains(&("c".to_string(), 3)));
+
+        book.remove("c");
+        assert_eq!(book.peers_ahead(1).len(), 2);
+    }
+}
@@ -2,22 +2,72 @@ use std::fmt::Debug;
 
 use crate::{
     error::Error,
-    schema::{Block, SignedTx},
+    schema::{
+        AccountProof, Block, BlockBody, BlockHeader, SignedTx, SnapshotManifest, StateChunk,
+        StatusResp,
+    },
+    types::Hash,
 };
 
 pub trait PeerClient: Debug + Clone + Send + Sync + 'static {
     /// Return the peers (base58 encoded peer ids) that this node knows about.
     fn known_peers(&self) -> Vec<String>;
 
-    /// Get the block height from a peer.
-    fn get_block_height(&self, peer_id: &str) -> Result<u64, Error>;
+    /// Get the status of a peer.
+    fn get_status(&self, peer_id: &str) -> Result<StatusResp, Error>;
 
-    /// Get blocks from a peer, starting from the `from_number`.
-    fn get_blocks(&self, peer_id: &str, from_number: u64) -> Result<Vec<Block>, Error>;
+    /// Disconnect from a peer, e.g. it is on another chain.
+    fn disconnect(&self, peer_id: &str) -> Result<(), Error>;
+
+    /// Get at most `count` block headers from a peer, starting from the `from_number`.
+    /// The peer may return fewer headers than requested.
+    fn get_headers(
+        &self,
+        peer_id: &str,
+        from_number: u64,
+        count: u64,
+    ) -> Result<Vec<BlockHeader>, Error>;
+
+    /// Get the block bodies from a peer by the block hashes.
+    /// The bodies are in the same order as the hashes, but may be fewer than requested.
+    fn get_block_bodies(&self, peer_id: &str, hashes: &[Hash]) -> Result<Vec<BlockBody>, Error>;
+
+    /// Get the txs of a block from a peer by their positions in the block.
+    /// The txs are in the same order as the indexes, and are empty if the block is unknown.
+    fn get_block_txs(
+        &self,
+        peer_id: &str,
+        block_hash: Hash,
+        indexes: &[u32],
+    ) -> Result<Vec<SignedTx>, Error>;
+
+    /// Get the hashes of the pending txs from a peer.
+    fn get_pending_tx_hashes(&self, peer_id: &str) -> Result<Vec<Hash>, Error>;
+
+    /// Get the pending txs from a peer by the hashes.
+    fn get_pending_txs(&self, peer_id: &str, hashes: &[Hash]) -> Result<Vec<SignedTx>, Error>;
+
+    /// Get the manifest of the latest state snapshot from a peer, if it has one.
+    fn get_snapshot_manifest(&self, peer_id: &str) -> Result<Option<SnapshotManifest>, Error>;
+
+    /// Get a chunk of the state snapshot taken at the block from a peer.
+    fn get_snapshot_chunk(
+        &self,
+        peer_id: &str,
+        block_hash: Hash,
+        index: u32,
+    ) -> Result<Option<StateChunk>, Error>;
+
+    /// Get the proof of an account's state at the head of a peer, if it has any block.
+    fn get_account_proof(
+        &self,
+        peer_id: &str,
+        account: &str,
+    ) -> Result<Option<AccountProof>, Error>;
 
     /// Broadcast a transaction to the network.
     fn broadcast_tx(&self, tx: SignedTx);
 
-    /// Broadcast a block to the network.
+    /// Broadcast a block to the network, which is announced as a compact block.
     fn broadcast_block(&self, block: Block);
 }
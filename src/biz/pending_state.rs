@@ -0,0 +1,112 @@
+//! The state after applying the pending transactions in the tx pool to the current state.
+//!
+//! Like the tx pool, it is only written by the `Miner`, which checks the incoming transactions
+//! against it, while other threads can read it, e.g. to tell a wallet its next nonce.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::schema::SignedTx;
+
+#[derive(Debug, Clone, Default)]
+pub struct PendingState {
+    inner: Arc<RwLock<InnerState>>,
+}
+
+#[derive(Debug, Default)]
+struct InnerState {
+    balances: HashMap<String, u64>,
+    account2nonce: HashMap<String, u64>,
+}
+
+impl InnerState {
+    fn balance(&self, account: &str) -> u64 {
+        self.balances.get(account).cloned().unwrap_or_default()
+    }
+
+    fn apply_tx(&mut self, tx: &SignedTx) {
+        let from_balance = self.balance(&tx.from) - tx.cost();
+        self.balances.insert(tx.from.clone(), from_balance);
+        let to_balance = self.balance(&tx.to) + tx.value;
+        self.balances.insert(tx.to.clone(), to_balance);
+        self.account2nonce.insert(tx.from.clone(), tx.nonce + 1);
+    }
+}
+
+impl PendingState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the balance of the account after the pending txs.
+    pub fn balance(&self, account: &str) -> u64 {
+        self.inner.read().unwrap().balance(account)
+    }
+
+    /// Get the next nonce of the account after the pending txs.
+    pub fn next_nonce(&self, account: &str) -> u64 {
+        self.inner
+            .read()
+            .unwrap()
+            .account2nonce
+            .get(account)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Apply a tx that is added to the tx pool.
+    pub fn apply_tx(&self, tx: &SignedTx) {
+        self.inner.write().unwrap().apply_tx(tx);
+    }
+
+    /// Rebuild the state from the current state and the pending `txs`.
+    /// It is replaced at once, so that the readers never see a partially rebuilt state.
+    pub fn reset(
+        &self,
+        balances: HashMap<String, u64>,
+        account2nonce: HashMap<String, u64>,
+        txs: &[SignedTx],
+    ) {
+        let mut state = InnerState {
+            balances,
+            account2nonce,
+        };
+        for tx in txs {
+            state.apply_tx(tx);
+        }
+
+        *self.inner.write().unwrap() = state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::Tx;
+
+    fn new_tx(from: &str, to: &str, value: u64, nonce: u64) -> SignedTx {
+        SignedTx {
+            tx: Some(Tx::new(from, to, value, nonce)),
+            sig: vec![],
+        }
+    }
+
+    #[test]
+    fn pending_txs_are_applied_to_the_state() {
+        let state = PendingState::new();
+        let balances = HashMap::from([("alice".to_string(), 1000)]);
+        let account2nonce = HashMap::from([("alice".to_string(), 2)]);
+        state.reset(balances, account2nonce, &[new_tx("alice", "bob", 100, 2)]);
+        assert_eq!(state.balance("alice"), 879);
+        assert_eq!(state.balance("bob"), 100);
+        assert_eq!(state.next_nonce("alice"), 3);
+        assert_eq!(state.next_nonce("bob"), 0);
+
+        state.apply_tx(&new_tx("bob", "alice", 50, 0));
+        assert_eq!(state.balance("alice"), 929);
+        assert_eq!(state.balance("bob"), 29);
+        assert_eq!(state.next_nonce("bob"), 1);
+    }
+}
@@ -0,0 +1,597 @@
+//! State snapshots, which let a new node skip replaying the blocks from the genesis.
+//!
+//! The state (balances and nonces) is split into chunks of `STATE_CHUNK_SIZE` accounts sorted by
+//! the address, and the `state_root` in a block header is the hash of the concatenated chunk
+//! hashes. So a snapshot can be downloaded chunk by chunk from any peer: every chunk is checked
+//! against the manifest, and the manifest is checked against a header of the PoW chain.
+//!
+//! The miner keeps the chunks of the state at the head, and only hashes again the chunks that a
+//! block changes. It takes a snapshot every `SNAPSHOT_INTERVAL` blocks, which is served to other
+//! peers until the next one is taken.
+//!
+//! The same chunks prove the state of a single account to a light node, which only has the
+//! headers: the chunk that holds the account (or the neighbouring chunks if it is absent) is sent
+//! along with all the chunk hashes.
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    error::Error,
+    schema::{
+        AccountProof, AccountState, Block, BlockHeader, IndexedStateChunk, SnapshotManifest,
+        StateChunk,
+    },
+    types::Hash,
+    utils,
+};
+
+/// The number of accounts in a state chunk.
+pub const STATE_CHUNK_SIZE: usize = 1024;
+/// The number of blocks between two snapshots.
+pub const SNAPSHOT_INTERVAL: u64 = 64;
+
+/// Split the state into chunks. The accounts with neither balance nor nonce are left out,
+/// so that the chunks don't depend on how a `State` stores the empty accounts.
+pub fn state_chunks(
+    balances: &HashMap<String, u64>,
+    account2nonce: &HashMap<String, u64>,
+) -> Vec<StateChunk> {
+    let mut accounts: BTreeMap<&str, AccountState> = BTreeMap::new();
+    for (account, balance) in balances.iter().filter(|(_, balance)| **balance > 0) {
+        accounts.entry(account).or_default().balance = *balance;
+    }
+    for (account, nonce) in account2nonce.iter().filter(|(_, nonce)| **nonce > 0) {
+        accounts.entry(account).or_default().nonce = *nonce;
+    }
+
+    let accounts: Vec<AccountState> = accounts
+        .into_iter()
+        .map(|(account, state)| AccountState {
+            account: account.to_string(),
+            ..state
+        })
+        .collect();
+
+    accounts
+        .chunks(STATE_CHUNK_SIZE)
+        .map(|accounts| StateChunk {
+            accounts: accounts.to_vec(),
+        })
+        .collect()
+}
+
+/// The state root is the hash of the concatenated chunk hashes.
+pub fn state_root_of(chunk_hashes: &[Hash]) -> Hash {
+    let mut msg = Vec::with_capacity(chunk_hashes.len() * 32);
+    for hash in chunk_hashes {
+        msg.extend_from_slice(&hash[..]);
+    }
+    utils::hash_message(&msg)
+}
+
+/// Apply the block to the state in the same way as `State::add_block`, which is used to compute
+/// the state root before the block is added.
+pub fn apply_block(
+    balances: &mut HashMap<String, u64>,
+    account2nonce: &mut HashMap<String, u64>,
+    block: &Block,
+) -> Result<(), Error> {
+    for tx in &block.txs {
+        let from_balance = balances.entry(tx.from.clone()).or_default();
+        *from_balance = from_balance
+            .checked_sub(tx.cost())
+            .ok_or_else(|| Error::BalanceInsufficient(tx.from.clone(), *from_balance, tx.cost()))?;
+        *balances.entry(tx.to.clone()).or_default() += tx.value;
+        *account2nonce.entry(tx.from.clone()).or_default() += 1;
+    }
+    *balances.entry(block.author().to_string()).or_default() += block.block_reward();
+
+    Ok(())
+}
+
+/// Collect the balances and the nonces from the chunks.
+pub fn accounts_of(chunks: &[StateChunk]) -> (HashMap<String, u64>, HashMap<String, u64>) {
+    let mut balances = HashMap::new();
+    let mut account2nonce = HashMap::new();
+    for account in chunks.iter().flat_map(|chunk| &chunk.accounts) {
+        if account.balance > 0 {
+            balances.insert(account.account.clone(), account.balance);
+        }
+        if account.nonce > 0 {
+            account2nonce.insert(account.account.clone(), account.nonce);
+        }
+    }
+
+    (balances, account2nonce)
+}
+
+/// The index of the first chunk that may hold the account, as the accounts are sorted across the
+/// chunks.
+fn chunk_index_of<C: Borrow<StateChunk>>(chunks: &[C], account: &str) -> usize {
+    chunks.partition_point(|chunk| {
+        chunk
+            .borrow()
+            .accounts
+            .last()
+            .is_some_and(|last| last.account.as_str() < account)
+    })
+}
+
+/// Build the proof of an account's state from the chunks of the state after the block.
+pub fn account_proof<C: Borrow<StateChunk>>(
+    block_hash: Hash,
+    chunks: &[C],
+    account: &str,
+) -> AccountProof {
+    let index = chunk_index_of(chunks, account);
+    let chunks: Vec<&StateChunk> = chunks.iter().map(|chunk| chunk.borrow()).collect();
+
+    let indexes = match chunks.get(index) {
+        // The account is in the chunk, or within its range so that the chunk proves the absence.
+        Some(chunk) if chunk.accounts[0].account.as_str() <= account => vec![index],
+        // The account is before the first chunk.
+        Some(_) if index == 0 => vec![index],
+        // The account is between two chunks.
+        Some(_) => vec![index - 1, index],
+        // The account is after the last chunk, if any.
+        None => chunks.len().checked_sub(1).into_iter().collect(),
+    };
+
+    AccountProof {
+        block_hash: block_hash.to_vec(),
+        chunk_hashes: chunks.iter().map(|chunk| chunk.hash().to_vec()).collect(),
+        chunks: indexes
+            .into_iter()
+            .map(|index| IndexedStateChunk {
+                index: index as u32,
+                chunk: Some(chunks[index].clone()),
+            })
+            .collect(),
+    }
+}
+
+/// Verify the proof against the header of the block it was built on, and return the state of
+/// the account, which is empty if the proof shows that the account is absent.
+pub fn verify_account_proof(
+    proof: &AccountProof,
+    header: &BlockHeader,
+    account: &str,
+) -> Result<AccountState, Error> {
+    let invalid = |reason: &str| Error::InvalidAccountProof(reason.to_string());
+    if proof.block_hash != header.hash().to_vec() {
+        return Err(invalid("block hash mismatches the header"));
+    }
+    if proof.chunk_hashes.iter().any(|hash| hash.len() != 32) {
+        return Err(invalid("malformed chunk hash"));
+    }
+    let chunk_hashes: Vec<Hash> = proof.chunk_hashes.iter().cloned().map(Hash::from).collect();
+    if state_root_of(&chunk_hashes) != header.state_root() {
+        return Err(invalid("chunk hashes mismatch the state root"));
+    }
+
+    let mut chunks: BTreeMap<usize, &[AccountState]> = BTreeMap::new();
+    for IndexedStateChunk { index, chunk } in &proof.chunks {
+        let index = *index as usize;
+        let chunk = chunk.as_ref().ok_or_else(|| invalid("missing chunk"))?;
+        if chunk_hashes.get(index) != Some(&chunk.hash()) {
+            return Err(invalid("chunk mismatches its hash"));
+        }
+        if chunk.accounts.is_empty() {
+            return Err(invalid("empty chunk"));
+        }
+        chunks.insert(index, &chunk.accounts);
+    }
+
+    let found = chunks
+        .values()
+        .flat_map(|accounts| accounts.iter())
+        .find(|state| state.account == account);
+    if let Some(state) = found {
+        return Ok(state.clone());
+    }
+
+    // The chunks are sorted by the address, so the account is absent if it falls in a chunk,
+    // before the first chunk, after the last chunk, or between two neighbouring chunks.
+    fn first(accounts: &[AccountState]) -> &str {
+        &accounts[0].account
+    }
+    fn last(accounts: &[AccountState]) -> &str {
+        &accounts[accounts.len() - 1].account
+    }
+    let absent = chunk_hashes.is_empty()
+        || chunks.iter().any(|(index, accounts)| {
+            let after_prev = match index.checked_sub(1) {
+                None => true,
+                Some(prev) => chunks.get(&prev).is_some_and(|prev| last(prev) < account),
+            };
+            let before_next = *index + 1 == chunk_hashes.len() || account <= last(accounts);
+            (after_prev || first(accounts) <= account) && before_next
+        });
+    if !absent {
+        return Err(invalid("account is neither found nor proven absent"));
+    }
+
+    Ok(AccountState {
+        account: account.to_string(),
+        ..Default::default()
+    })
+}
+
+/// The state split into chunks as by `state_chunks`, along with the chunk hashes. The chunks are
+/// shared between the states of successive blocks, so that only the changed ones are copied.
+#[derive(Debug, Clone, Default)]
+pub struct StateChunks {
+    chunks: Vec<Arc<StateChunk>>,
+    chunk_hashes: Vec<Hash>,
+}
+
+impl StateChunks {
+    pub fn new(balances: &HashMap<String, u64>, account2nonce: &HashMap<String, u64>) -> Self {
+        let chunks: Vec<Arc<StateChunk>> = state_chunks(balances, account2nonce)
+            .into_iter()
+            .map(Arc::new)
+            .collect();
+        let chunk_hashes = chunks.iter().map(|chunk| chunk.hash()).collect();
+
+        Self {
+            chunks,
+            chunk_hashes,
+        }
+    }
+
+    pub fn chunks(&self) -> &[Arc<StateChunk>] {
+        &self.chunks
+    }
+
+    pub fn chunk_hashes(&self) -> &[Hash] {
+        &self.chunk_hashes
+    }
+
+    pub fn state_root(&self) -> Hash {
+        state_root_of(&self.chunk_hashes)
+    }
+
+    /// The position of the account among all the accounts, and whether it is there. Every chunk
+    /// but the last one is full, so the position tells the chunk and the index in it.
+    fn position(&self, account: &str) -> (usize, bool) {
+        let index = chunk_index_of(&self.chunks, account);
+        let Some(chunk) = self.chunks.get(index) else {
+            let len = self.chunks.last().map_or(0, |last| {
+                (self.chunks.len() - 1) * STATE_CHUNK_SIZE + last.accounts.len()
+            });
+            return (len, false);
+        };
+        match chunk
+            .accounts
+            .binary_search_by(|state| state.account.as_str().cmp(account))
+        {
+            Ok(offset) => (index * STATE_CHUNK_SIZE + offset, true),
+            Err(offset) => (index * STATE_CHUNK_SIZE + offset, false),
+        }
+    }
+
+    fn get(&self, position: usize) -> &AccountState {
+        &self.chunks[position / STATE_CHUNK_SIZE].accounts[position % STATE_CHUNK_SIZE]
+    }
+
+    /// The state after applying the block, in the same way as `apply_block`.
+    ///
+    /// Only the chunks holding the accounts touched by the block are hashed again. But an account
+    /// which is added or emptied shifts the accounts after it, so the chunks from there on are
+    /// split and hashed again.
+    pub fn apply_block(&self, block: &Block) -> Result<Self, Error> {
+        let mut balances = HashMap::new();
+        let mut account2nonce = HashMap::new();
+        let touched = block
+            .txs
+            .iter()
+            .flat_map(|tx| [tx.from.as_str(), tx.to.as_str()])
+            .chain([block.author()]);
+        for account in touched {
+            if let (position, true) = self.position(account) {
+                let state = self.get(position);
+                balances.insert(account.to_string(), state.balance);
+                account2nonce.insert(account.to_string(), state.nonce);
+            }
+        }
+        apply_block(&mut balances, &mut account2nonce, block)?;
+
+        // The touched accounts sorted by the address, with their positions before the block.
+        let changes: BTreeMap<String, (usize, bool, AccountState)> = balances
+            .into_iter()
+            .map(|(account, balance)| {
+                let nonce = account2nonce.get(&account).copied().unwrap_or_default();
+                let (position, found) = self.position(&account);
+                let state = AccountState {
+                    account: account.clone(),
+                    balance,
+                    nonce,
+                };
+                (account, (position, found, state))
+            })
+            .collect();
+        let is_empty = |state: &AccountState| state.balance == 0 && state.nonce == 0;
+
+        // The chunks before the first added or emptied account keep their accounts in place.
+        let shift_from = changes
+            .values()
+            .filter(|(_, found, state)| *found == is_empty(state))
+            .map(|(position, _, _)| *position)
+            .min();
+        let split = shift_from.map_or(self.chunks.len(), |position| position / STATE_CHUNK_SIZE);
+
+        let mut chunks = self.chunks.clone();
+        let mut chunk_hashes = self.chunk_hashes.clone();
+        let mut dirty = BTreeSet::new();
+        for (position, found, state) in changes.values() {
+            if *found && *position < split * STATE_CHUNK_SIZE {
+                let index = position / STATE_CHUNK_SIZE;
+                Arc::make_mut(&mut chunks[index]).accounts[position % STATE_CHUNK_SIZE] =
+                    state.clone();
+                dirty.insert(index);
+            }
+        }
+        for index in dirty {
+            chunk_hashes[index] = chunks[index].hash();
+        }
+
+        if shift_from.is_some() {
+            // Merge the changed accounts into the accounts from the split on.
+            let mut accounts = Vec::new();
+            let mut old = self.chunks[split..]
+                .iter()
+                .flat_map(|chunk| chunk.accounts.iter())
+                .peekable();
+            let tail = changes
+                .iter()
+                .filter(|(_, (position, _, _))| *position >= split * STATE_CHUNK_SIZE);
+            for (account, (_, _, state)) in tail {
+                while let Some(prev) = old.next_if(|prev| prev.account < *account) {
+                    accounts.push(prev.clone());
+                }
+                old.next_if(|prev| prev.account == *account);
+                if !is_empty(state) {
+                    accounts.push(state.clone());
+                }
+            }
+            accounts.extend(old.cloned());
+
+            chunks.truncate(split);
+            chunk_hashes.truncate(split);
+            for accounts in accounts.chunks(STATE_CHUNK_SIZE) {
+                let chunk = StateChunk {
+                    accounts: accounts.to_vec(),
+                };
+                chunk_hashes.push(chunk.hash());
+                chunks.push(Arc::new(chunk));
+            }
+        }
+
+        Ok(Self {
+            chunks,
+            chunk_hashes,
+        })
+    }
+}
+
+/// The state after applying a block.
+#[derive(Debug)]
+pub struct Snapshot {
+    pub number: u64,
+    pub block_hash: Hash,
+    pub state: StateChunks,
+}
+
+impl Snapshot {
+    pub fn new(block: &Block, state: StateChunks) -> Self {
+        Self {
+            number: block.number(),
+            block_hash: block.hash(),
+            state,
+        }
+    }
+
+    pub fn manifest(&self) -> SnapshotManifest {
+        SnapshotManifest {
+            number: self.number,
+            block_hash: self.block_hash.to_vec(),
+            chunk_hashes: self
+                .state
+                .chunk_hashes()
+                .iter()
+                .map(|hash| hash.to_vec())
+                .collect(),
+        }
+    }
+}
+
+/// The latest snapshot and the state at the head, which are only set by the miner and read by
+/// other threads.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotStore {
+    latest: Arc<RwLock<Option<Arc<Snapshot>>>>,
+    /// The state at the head, which the account proofs are built from.
+    head: Arc<RwLock<Option<Arc<Snapshot>>>>,
+}
+
+impl SnapshotStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, snapshot: Snapshot) {
+        *self.latest.write().unwrap() = Some(Arc::new(snapshot));
+    }
+
+    pub fn set_head(&self, head: Snapshot) {
+        *self.head.write().unwrap() = Some(Arc::new(head));
+    }
+
+    /// Prove the state of the account at the head, or `None` if there is no block yet.
+    pub fn account_proof(&self, account: &str) -> Option<AccountProof> {
+        let head = self.head.read().unwrap().clone()?;
+        Some(account_proof(head.block_hash, head.state.chunks(), account))
+    }
+
+    pub fn manifest(&self) -> Option<SnapshotManifest> {
+        self.latest
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|snapshot| snapshot.manifest())
+    }
+
+    /// Get a chunk of the latest snapshot, or `None` if the snapshot has been replaced.
+    pub fn chunk(&self, block_hash: &Hash, index: usize) -> Option<StateChunk> {
+        let snapshot = self.latest.read().unwrap().clone()?;
+        if snapshot.block_hash != *block_hash {
+            return None;
+        }
+        snapshot
+            .state
+            .chunks()
+            .get(index)
+            .map(|chunk| StateChunk::clone(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{SignedTx, Tx};
+
+    #[test]
+    fn state_chunks_roundtrip() {
+        let balances: HashMap<String, u64> = (0..STATE_CHUNK_SIZE + 10)
+            .map(|i| (format!("0x{:08x}", i), i as u64))
+            .collect();
+        let account2nonce = HashMap::from([("0x00000001".to_string(), 3)]);
+
+        let chunks = state_chunks(&balances, &account2nonce);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].accounts[0].account, "0x00000001");
+        assert_eq!(chunks[0].accounts[0].nonce, 3);
+
+        // The empty account '0x00000000' is left out, which doesn't change the state root.
+        let (restored_balances, restored_nonces) = accounts_of(&chunks);
+        assert_eq!(restored_balances.len(), balances.len() - 1);
+        assert_eq!(restored_nonces, account2nonce);
+        assert_eq!(
+            StateChunks::new(&restored_balances, &restored_nonces).state_root(),
+            StateChunks::new(&balances, &account2nonce).state_root()
+        );
+    }
+
+    #[test]
+    fn account_proofs_work() {
+        // The accounts are '0x00000002', '0x00000004', ... in two chunks.
+        let balances: HashMap<String, u64> = (1..=STATE_CHUNK_SIZE + 10)
+            .map(|i| (format!("0x{:08x}", i * 2), i as u64))
+            .collect();
+        let chunks = state_chunks(&balances, &HashMap::new());
+        let chunk_hashes: Vec<Hash> = chunks.iter().map(|chunk| chunk.hash()).collect();
+        let mut block = Block::new(Hash::default(), 0, "0x01234567".to_string(), vec![]);
+        block.set_state_root(state_root_of(&chunk_hashes));
+        let header = block.header();
+
+        let prove = |account: &str| account_proof(block.hash(), &chunks, account);
+        let verify = |proof: &AccountProof, account: &str| {
+            verify_account_proof(proof, header, account).map(|state| state.balance)
+        };
+
+        assert_eq!(verify(&prove("0x00000004"), "0x00000004").unwrap(), 2);
+        // Absent in a chunk, before the first chunk, after the last chunk, and between chunks.
+        for account in ["0x00000005", "0x00000001", "0xffffffff", "0x00000801"] {
+            assert_eq!(verify(&prove(account), account).unwrap(), 0);
+        }
+        assert_eq!(prove("0x00000801").chunks.len(), 2);
+
+        // The absence between chunks can't be proven with only one of them.
+        let mut proof = prove("0x00000801");
+        proof.chunks.remove(0);
+        assert!(verify(&proof, "0x00000801").is_err());
+
+        // A forged balance mismatches the chunk hash.
+        let mut proof = prove("0x00000004");
+        proof.chunks[0].chunk.as_mut().unwrap().accounts[1].balance = 100;
+        assert!(verify(&proof, "0x00000004").is_err());
+
+        // The proof is only valid against the header it was built on.
+        let other = Block::new(Hash::default(), 0, "0x01234567".to_string(), vec![]);
+        assert!(verify_account_proof(&prove("0x00000004"), other.header(), "0x00000004").is_err());
+    }
+
+    #[test]
+    fn state_chunks_apply_block_incrementally() {
+        // The accounts are '0x00000002', '0x00000004', ... in three chunks.
+        let mut balances: HashMap<String, u64> = (1..=STATE_CHUNK_SIZE * 2 + 10)
+            .map(|i| (format!("0x{:08x}", i * 2), 1000))
+            .collect();
+        let mut account2nonce = HashMap::new();
+        let mut chunks = StateChunks::new(&balances, &account2nonce);
+
+        let transfer = |from: &str, to: &str, nonce: u64| SignedTx {
+            tx: Some(Tx::new(from, to, 100, nonce)),
+            sig: vec![0u8; 65],
+        };
+        let blocks = [
+            // Only existing accounts in the first chunk are changed.
+            vec![transfer("0x00000002", "0x00000004", 0)],
+            // A new account in the first chunk shifts the later chunks.
+            vec![transfer("0x00000002", "0x00000003", 1)],
+            // New accounts after the last one, which fill up the last chunk.
+            (0..STATE_CHUNK_SIZE)
+                .map(|i| {
+                    let from = format!("0x{:08x}", (i + 10) * 2);
+                    transfer(&from, &format!("0xf{:07x}", i), 0)
+                })
+                .collect(),
+            vec![transfer("0x00000006", "0xfffffffe", 0)],
+        ];
+        for txs in blocks {
+            let block = Block::new(Hash::default(), 0, "0x00000008".to_string(), txs);
+            chunks = chunks.apply_block(&block).unwrap();
+            apply_block(&mut balances, &mut account2nonce, &block).unwrap();
+
+            let expected = StateChunks::new(&balances, &account2nonce);
+            assert_eq!(chunks.chunk_hashes(), expected.chunk_hashes());
+            assert_eq!(chunks.chunks(), expected.chunks());
+        }
+        assert_eq!(chunks.chunks().len(), 4);
+
+        // A block without txs rewards nothing, which doesn't add an empty author.
+        let block = Block::new(Hash::default(), 0, "0x00000001".to_string(), vec![]);
+        let unchanged = chunks.apply_block(&block).unwrap();
+        assert_eq!(unchanged.state_root(), chunks.state_root());
+
+        // The state is untouched by an invalid block.
+        let overspending = transfer("0x00000002", "0x00000004", 2);
+        let mut block = Block::new(Hash::default(), 0, "0x00000008".to_string(), vec![]);
+        block.txs = vec![overspending; 10];
+        assert!(chunks.apply_block(&block).is_err());
+    }
+
+    #[test]
+    fn apply_block_rejects_overspending() {
+        let tx = SignedTx {
+            tx: Some(Tx::new("0x00000000", "0x11111111", 100, 0)),
+            sig: vec![0u8; 65],
+        };
+        let block = Block::new(Hash::default(), 0, "0x01234567".to_string(), vec![tx]);
+
+        let mut balances = HashMap::from([("0x00000000".to_string(), 121)]);
+        let mut account2nonce = HashMap::new();
+        apply_block(&mut balances, &mut account2nonce, &block).unwrap();
+        assert_eq!(balances["0x00000000"], 0);
+        assert_eq!(balances["0x11111111"], 100);
+        assert_eq!(balances["0x01234567"], 21);
+        assert_eq!(account2nonce["0x00000000"], 1);
+
+        let mut balances = HashMap::from([("0x00000000".to_string(), 120)]);
+        assert!(apply_block(&mut balances, &mut HashMap::new(), &block).is_err());
+    }
+}
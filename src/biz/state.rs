@@ -1,9 +1,42 @@
 use std::{collections::HashMap, fmt::Debug};
 
-use crate::{error::Error, schema::Block, types::Hash};
+use serde::Deserialize;
+
+use crate::{
+    error::Error,
+    schema::{AccountState, Block, BlockHeader, TxReceipt},
+    types::Hash,
+};
+
+pub use primitives::{BlockId, TxDirection};
+
+/// The number of recent blocks whose states are kept in the pruned mode.
+pub const PRUNED_HISTORY_BLOCKS: u64 = 1024;
+
+/// How much history of the balances and nonces a `State` keeps.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryMode {
+    /// Only the state after the last block.
+    #[default]
+    Latest,
+    /// The states after all the blocks.
+    Archive,
+    /// The states after the last `PRUNED_HISTORY_BLOCKS` blocks.
+    Pruned,
+}
+
+/// The position of a tx in the chain, which is ordered by the block number and then the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TxLocation {
+    /// The number of the block that includes the tx.
+    pub number: u64,
+    /// The index of the tx in the block.
+    pub index: u32,
+}
 
 pub trait State: Debug + Clone + Send + Sync + 'static {
-    /// Current block height.
+    /// Current block height, which is the number of the last block plus one.
     fn block_height(&self) -> u64;
 
     /// Next account nonce to be used.
@@ -12,15 +45,45 @@ pub trait State: Debug + Clone + Send + Sync + 'static {
     /// Get the last block hash.
     fn last_block_hash(&self) -> Option<Hash>;
 
+    /// The number of the first block kept, which is the snapshot block if a snapshot was
+    /// imported, or 0.
+    fn lowest_block_number(&self) -> u64;
+
     /// Add a block to the state.
     fn add_block(&self, block: Block) -> Result<(), Error>;
 
-    /// Get blocks, starting from the `from_number`.
-    fn get_blocks(&self, from_number: u64) -> Vec<Block>;
+    /// Replace the state with a snapshot taken after the `block`, which becomes the last block.
+    /// Only a state without any block can import a snapshot, and the blocks before the snapshot
+    /// are not available.
+    fn import_snapshot(
+        &self,
+        block: Block,
+        balances: HashMap<String, u64>,
+        account2nonce: HashMap<String, u64>,
+    ) -> Result<(), Error>;
+
+    /// Get at most `limit` blocks, starting from the `from_number` and stopping at the
+    /// `to_number` (inclusive).
+    /// The blocks are in ascending order, or in descending order if `reverse` is set, in which
+    /// case the `to_number` defaults to 0.
+    fn get_blocks(
+        &self,
+        from_number: u64,
+        to_number: Option<u64>,
+        limit: usize,
+        reverse: bool,
+    ) -> Vec<Block>;
 
     /// Get a block by its number.
     fn get_block(&self, number: u64) -> Option<Block>;
 
+    /// Get a block by its hash.
+    fn get_block_by_hash(&self, hash: &Hash) -> Option<Block>;
+
+    /// Get at most `count` block headers, starting from the `from_number`, or none if the block
+    /// is not kept.
+    fn get_headers(&self, from_number: u64, count: usize) -> Vec<BlockHeader>;
+
     /// Get the balance of the account.
     fn get_balance(&self, account: &str) -> u64;
 
@@ -29,4 +92,39 @@ pub trait State: Debug + Clone + Send + Sync + 'static {
 
     /// Get all the nonces of the accounts.
     fn get_account2nonce(&self) -> HashMap<String, u64>;
+
+    /// Get the receipt of an included tx by its hash.
+    fn get_tx_receipt(&self, hash: &Hash) -> Option<TxReceipt>;
+
+    /// Get at most `limit` locations of the txs sent or received by the account, or only the
+    /// ones in the `direction` if given. They are in ascending order, starting from the `from`
+    /// location (inclusive) and stopping at the block `to_number` (inclusive).
+    fn get_account_tx_locations(
+        &self,
+        account: &str,
+        direction: Option<TxDirection>,
+        from: TxLocation,
+        to_number: Option<u64>,
+        limit: usize,
+    ) -> Vec<TxLocation>;
+
+    /// Get all the balances after applying the block `number`, or `None` if the state of the
+    /// block is not kept, see `HistoryMode`.
+    fn get_balances_at(&self, number: u64) -> Option<HashMap<String, u64>>;
+
+    /// Get the balance and the nonce of the account after applying the block `number`, or `None`
+    /// if the state of the block is not kept, see `HistoryMode`.
+    fn get_account_at(&self, account: &str, number: u64) -> Option<AccountState>;
+}
+
+/// Convert the arguments of `State::get_blocks` into an inclusive range of block numbers,
+/// or `None` if the range is empty.
+pub fn block_range(from_number: u64, to_number: Option<u64>, reverse: bool) -> Option<(u64, u64)> {
+    let (start, end) = if reverse {
+        (to_number.unwrap_or(0), from_number)
+    } else {
+        (from_number, to_number.unwrap_or(u64::MAX))
+    };
+
+    (start <= end).then_some((start, end))
 }
@@ -1,14 +1,66 @@
-//! Periodically sync blocks from the best peer.
+//! Sync blocks from other peers periodically, or immediately when asked to sync from a peer,
+//! e.g. a new peer is connected, or an orphan block is received from the peer.
+//! The pending txs are also synced from new peers and periodically, so that the txs missed by
+//! the gossip still reach the miner.
+//!
+//! The sync is headers-first:
+//!
+//! 1. Download a batch of headers from the best peer, and validate the header chain (number,
+//!    parent hash and PoW) before downloading anything else.
+//! 2. Download the bodies of the batch in parallel from all the peers that have them, and check
+//!    every body against the `txs_hash` of its header.
+//! 3. Send the assembled blocks to the miner in order, then continue with the next batch.
+//!
+//! The head that a peer reports is verified on the handshake, and the peers whose chains conflict
+//! with the trusted checkpoints of the `ChainSpec` are disconnected. Every synced header must
+//! pass through the checkpoints as well.
+//!
+//! A new node can start with a state snapshot instead of replaying the blocks from the genesis,
+//! if the snapshot sync is enabled. The headers from the last checkpoint up to the snapshot block
+//! are still downloaded and validated, so that the snapshot is checked against the `state_root`
+//! of a PoW header.
+//!
+//! The syncer also completes the compact blocks whose txs are not all in the local tx pool, by
+//! fetching the missing txs from the peers that have the block.
 
-use std::time::Duration;
+use std::{thread, time::Duration};
 
-use crossbeam_channel::{tick, Sender};
+use crossbeam_channel::{bounded, select, tick, Receiver, Sender, TryRecvError};
+use log::{error, info};
 
-use crate::schema::Block;
+use crate::{
+    error::Error,
+    schema::{
+        Block, BlockBody, BlockHeader, CompactBlock, SignedTx, SnapshotManifest, StateChunk,
+        StatusResp,
+    },
+    types::Hash,
+    utils,
+};
 
-use super::{PeerClient, State};
+use super::{
+    accounts_of,
+    miner::{BlockMsg, SnapshotMsg, TxMsg},
+    state_root_of, Checkpoints, PeerBook, PeerClient, State, TxPool,
+};
 
 const SYNC_INTERVAL: u64 = 30;
+/// The number of headers requested from a peer at a time.
+const HEADERS_BATCH_SIZE: u64 = 256;
+/// The number of block bodies requested from a peer at a time.
+const BODIES_BATCH_SIZE: usize = 32;
+/// The number of attempts to download a batch of bodies, each from a different peer if possible.
+const BODIES_MAX_ATTEMPTS: usize = 3;
+/// The number of pending txs requested from a peer at a time.
+const TXS_BATCH_SIZE: usize = 256;
+
+/// A compact block whose txs are not all in the local tx pool.
+#[derive(Debug)]
+pub struct CompactBlockMsg {
+    pub compact: CompactBlock,
+    /// The peer that sent the block, which is asked for the missing txs first.
+    pub source: String,
+}
 
 #[derive(Debug)]
 pub struct Syncer<S: State, P: PeerClient> {
@@ -16,16 +68,53 @@ pub struct Syncer<S: State, P: PeerClient> {
     state: S,
     /// The client to interact with other peers.
     peer_client: P,
+    /// Whether to start with a state snapshot if the local chain is empty.
+    snapshot_sync: bool,
+    /// The cached statuses of the peers.
+    peer_book: PeerBook,
+    /// The pending txs, which are used to find out the missing txs.
+    tx_pool: TxPool,
+    /// The channel to send txs to the miner.
+    tx_sender: Sender<TxMsg>,
     /// The channel to send blocks to the miner.
-    block_sender: Sender<Block>,
+    block_sender: Sender<BlockMsg>,
+    /// The channel to send the downloaded snapshot to the miner.
+    snapshot_sender: Sender<SnapshotMsg>,
+    /// The receiver of the peers to sync from immediately.
+    sync_receiver: Receiver<String>,
+    /// The receiver of the compact blocks to complete.
+    compact_block_receiver: Receiver<CompactBlockMsg>,
+    /// The shutdown signal, which is triggered by closing the channel.
+    shutdown: Receiver<()>,
 }
 
 impl<S: State, P: PeerClient> Syncer<S, P> {
-    pub fn new(state: S, peer_client: P, block_sender: Sender<Block>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        state: S,
+        peer_client: P,
+        snapshot_sync: bool,
+        peer_book: PeerBook,
+        tx_pool: TxPool,
+        tx_sender: Sender<TxMsg>,
+        block_sender: Sender<BlockMsg>,
+        snapshot_sender: Sender<SnapshotMsg>,
+        sync_receiver: Receiver<String>,
+        compact_block_receiver: Receiver<CompactBlockMsg>,
+        shutdown: Receiver<()>,
+    ) -> Self {
         Self {
             state,
             peer_client,
+            snapshot_sync,
+            peer_book,
+            tx_pool,
+            tx_sender,
             block_sender,
+            snapshot_sender,
+            sync_receiver,
+            compact_block_receiver,
+            shutdown,
         }
     }
 
@@ -33,39 +122,1295 @@ impl<S: State, P: PeerClient> Syncer<S, P> {
         let ticker = tick(Duration::from_secs(SYNC_INTERVAL));
 
         loop {
-            ticker.recv().unwrap();
+            select! {
+                recv(self.shutdown) -> _ => {
+                    info!("📣 Syncer stopped");
+                    return;
+                }
+                recv(self.sync_receiver) -> msg => {
+                    if let Ok(peer) = msg {
+                        let is_new = !self.peer_book.contains(&peer);
+                        if self.handshake(&peer) {
+                            self.sync_once(Some(&peer));
+                            if is_new {
+                                self.sync_txs(&peer);
+                            }
+                        }
+                    }
+                }
+                recv(self.compact_block_receiver) -> msg => {
+                    if let Ok(msg) = msg {
+                        self.complete_compact_block(msg);
+                    }
+                }
+                recv(ticker) -> _ => {
+                    // The peers that were connected before the node started to listen to the
+                    // connection events are not handshaked yet.
+                    for peer in self.peer_client.known_peers() {
+                        if !self.peer_book.contains(&peer) {
+                            self.handshake(&peer);
+                        }
+                    }
+                    self.sync_once(None);
+                    for peer in self.peer_book.peer_ids() {
+                        self.sync_txs(&peer);
+                    }
+                }
+            }
+        }
+    }
 
-            let local_height = self.state.block_height();
-            let best_peer = self.get_best_peer(local_height);
-            if best_peer.is_none() {
-                continue;
+    fn handshake(&self, peer: &str) -> bool {
+        handshake(&self.peer_client, &self.peer_book, peer)
+    }
+
+    /// Sync from all the peers, or only from the `target` peer if given.
+    fn sync_once(&self, target: Option<&str>) {
+        let mut local_height = self.state.block_height();
+        let mut peers = self.peer_book.peers_ahead(local_height);
+        if let Some(target) = target {
+            peers.retain(|(peer, _)| peer == target);
+        }
+        if peers.is_empty() {
+            return;
+        }
+
+        if self.snapshot_sync && local_height == 0 && self.sync_snapshot(&peers) {
+            local_height = self.state.block_height();
+        }
+
+        let target_height = peers[0].1;
+        let mut next_number = local_height;
+        let mut parent_hash = self.state.last_block_hash().unwrap_or_default();
+        info!(
+            "📣 Syncing blocks from {} to {} with {} peers",
+            local_height,
+            target_height,
+            peers.len()
+        );
+
+        while next_number < target_height {
+            if let Err(TryRecvError::Disconnected) = self.shutdown.try_recv() {
+                return;
             }
-            let best_peer = best_peer.unwrap();
 
-            let _ = self
-                .peer_client
-                .get_blocks(&best_peer, local_height)
-                .map(|blocks| {
-                    for block in blocks {
-                        let _ = self.block_sender.send(block);
-                    }
+            let count = HEADERS_BATCH_SIZE.min(target_height - next_number);
+            let Some(headers) = download_headers(
+                &self.peer_client,
+                &self.peer_book,
+                &peers,
+                parent_hash,
+                next_number,
+                count,
+            ) else {
+                error!("❌ Failed to download headers from {}", next_number);
+                return;
+            };
+
+            // Only the peers that have all the blocks of this batch are asked for the bodies.
+            let last_number = next_number + headers.len() as u64 - 1;
+            let body_peers: Vec<&str> = peers
+                .iter()
+                .filter(|(peer, height)| {
+                    *height > last_number && self.peer_book.has_blocks_from(peer, next_number)
+                })
+                .map(|(peer, _)| peer.as_str())
+                .collect();
+
+            let Some(blocks) = self.download_blocks(&body_peers, headers) else {
+                error!("❌ Failed to download block bodies from {}", next_number);
+                return;
+            };
+
+            for block in blocks {
+                parent_hash = block.hash();
+                next_number += 1;
+                let _ = self.block_sender.send(BlockMsg {
+                    block,
+                    source: None,
                 });
+            }
         }
+
+        info!("📣 Synced blocks up to {}", next_number);
     }
 
-    fn get_best_peer(&self, local_height: u64) -> Option<String> {
-        let (mut best_peer, mut best_height) = (None, local_height);
-        let peers = self.peer_client.known_peers();
+    /// Download the latest snapshot of the peers, and wait for the miner to import it.
+    /// Returns `true` if a snapshot is imported.
+    fn sync_snapshot(&self, peers: &[(String, u64)]) -> bool {
+        for (peer, _) in peers {
+            let manifest = match self.peer_client.get_snapshot_manifest(peer) {
+                Ok(Some(manifest)) => manifest,
+                Ok(None) => continue,
+                Err(err) => {
+                    error!(
+                        "❌ Failed to get snapshot manifest from {}: {:?}",
+                        peer, err
+                    );
+                    continue;
+                }
+            };
 
-        for peer in peers {
-            let _ = self.peer_client.get_block_height(&peer).map(|height| {
-                if best_height < height {
-                    best_height = height;
-                    best_peer = Some(peer);
+            let (block, chunks) = match self.download_snapshot(peers, peer, manifest) {
+                Ok(snapshot) => snapshot,
+                Err(err) => {
+                    error!("❌ Failed to download snapshot from {}: {:?}", peer, err);
+                    continue;
                 }
+            };
+
+            let (balances, account2nonce) = accounts_of(&chunks);
+            let (reply, result) = bounded(1);
+            let _ = self.snapshot_sender.send(SnapshotMsg {
+                block,
+                balances,
+                account2nonce,
+                reply,
             });
+            // The miner may fail to import it, e.g. a block was added in the meantime.
+            return matches!(result.recv(), Ok(Ok(())));
         }
 
-        best_peer
+        false
+    }
+
+    /// Download the snapshot described by the manifest from the peer, and verify it against the
+    /// header chain downloaded from all the `peers`.
+    fn download_snapshot(
+        &self,
+        peers: &[(String, u64)],
+        peer: &str,
+        manifest: SnapshotManifest,
+    ) -> Result<(Block, Vec<StateChunk>), Error> {
+        let invalid = |reason: &str| Error::InvalidSnapshot(reason.to_string());
+        if manifest.block_hash.len() != 32
+            || manifest.chunk_hashes.iter().any(|hash| hash.len() != 32)
+        {
+            return Err(invalid("malformed manifest"));
+        }
+        let block_hash = Hash::from(manifest.block_hash);
+        let chunk_hashes: Vec<Hash> = manifest.chunk_hashes.into_iter().map(Hash::from).collect();
+
+        info!(
+            "📣 Syncing the state snapshot at block {} with {} chunks from {}",
+            manifest.number,
+            chunk_hashes.len(),
+            peer
+        );
+
+        // Only the headers of a valid chain can vouch for the state root. The headers up to the
+        // last checkpoint are trusted, so the chain is only downloaded from there.
+        let checkpoints = self.peer_book.checkpoints();
+        let (mut next_number, mut parent_hash, mut header) =
+            match checkpoints.last_below(manifest.number + 1) {
+                Some((number, hash)) => {
+                    let header = get_header(&self.peer_client, peer, number)?
+                        .ok_or_else(|| invalid("missing checkpoint header"))?;
+                    checkpoints.check(&header)?;
+                    (number + 1, hash, Some(header))
+                }
+                None => (0, Hash::default(), None),
+            };
+        while next_number <= manifest.number {
+            let count = HEADERS_BATCH_SIZE.min(manifest.number + 1 - next_number);
+            let mut headers = download_headers(
+                &self.peer_client,
+                &self.peer_book,
+                peers,
+                parent_hash,
+                next_number,
+                count,
+            )
+            .ok_or_else(|| invalid("failed to download headers"))?;
+            next_number += headers.len() as u64;
+            header = headers.pop();
+            parent_hash = header.as_ref().map(|h| h.hash()).unwrap_or_default();
+        }
+        let header = header.ok_or_else(|| invalid("missing header"))?;
+        if header.hash() != block_hash {
+            return Err(invalid("block hash mismatches the header"));
+        }
+        if state_root_of(&chunk_hashes) != header.state_root() {
+            return Err(invalid("chunk hashes mismatch the state root"));
+        }
+
+        let mut chunks = Vec::with_capacity(chunk_hashes.len());
+        for (index, chunk_hash) in chunk_hashes.iter().enumerate() {
+            let chunk = self
+                .peer_client
+                .get_snapshot_chunk(peer, block_hash, index as u32)?
+                .ok_or_else(|| invalid("missing chunk"))?;
+            if chunk.hash() != *chunk_hash {
+                return Err(invalid("chunk mismatches its hash"));
+            }
+            chunks.push(chunk);
+        }
+
+        let body = self
+            .peer_client
+            .get_block_bodies(peer, &[block_hash])?
+            .into_iter()
+            .next()
+            .filter(|body| header.matches_body(body))
+            .ok_or_else(|| invalid("missing block body"))?;
+
+        Ok((Block::from_parts(header, body), chunks))
+    }
+
+    /// Fetch the pending txs of the peer that are missing locally, and send them to the miner,
+    /// where they are checked as usual.
+    /// The txs that depend on blocks not yet added may be rejected, they are fetched again in
+    /// the next round.
+    fn sync_txs(&self, peer: &str) {
+        let hashes = match self.peer_client.get_pending_tx_hashes(peer) {
+            Ok(hashes) => hashes,
+            Err(err) => {
+                error!(
+                    "❌ Failed to get pending tx hashes from {}: {:?}",
+                    peer, err
+                );
+                return;
+            }
+        };
+
+        let missing: Vec<Hash> = hashes
+            .into_iter()
+            .filter(|hash| !self.tx_pool.contains(hash))
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        let mut txs = Vec::with_capacity(missing.len());
+        for hashes in missing.chunks(TXS_BATCH_SIZE) {
+            match self.peer_client.get_pending_txs(peer, hashes) {
+                Ok(batch) => txs.extend(batch),
+                Err(err) => {
+                    error!("❌ Failed to get pending txs from {}: {:?}", peer, err);
+                    break;
+                }
+            }
+        }
+
+        // The txs from the same account must be added in the order of their nonces.
+        txs.sort_by_key(|tx| tx.nonce);
+        info!("📣 Synced {} pending txs from {}", txs.len(), peer);
+        for tx in txs {
+            let _ = self.tx_sender.send(TxMsg {
+                tx,
+                need_broadcast: false,
+                reply: None,
+            });
+        }
+    }
+
+    /// Fetch the missing txs of a compact block, trying the source peer first, then the other
+    /// peers that have the block.
+    fn complete_compact_block(&self, msg: CompactBlockMsg) {
+        let CompactBlockMsg { compact, source } = msg;
+        if self.state.get_block_by_hash(&compact.hash()).is_some() {
+            return;
+        }
+
+        let mut peers = vec![source.clone()];
+        peers.extend(
+            self.peer_book
+                .peers_ahead(compact.number())
+                .into_iter()
+                .map(|(peer, _)| peer)
+                .filter(|peer| *peer != source),
+        );
+
+        let txs = self.tx_pool.get_txs_by_short_ids(&compact.short_tx_ids);
+        for peer in peers {
+            if let Some(body) = self.fetch_compact_block_body(&peer, &compact, txs.clone()) {
+                let block = Block::from_parts(compact.header.unwrap(), body);
+                let _ = self.block_sender.send(BlockMsg {
+                    block,
+                    source: Some(source),
+                });
+                return;
+            }
+        }
+
+        error!("❌ Failed to complete compact block '{}'", compact.hash());
+    }
+
+    /// Fill in the missing `txs` of a compact block from the peer. If the txs still don't match
+    /// the header, e.g. a short ID is shared by another tx, download the whole body instead.
+    fn fetch_compact_block_body(
+        &self,
+        peer: &str,
+        compact: &CompactBlock,
+        mut txs: Vec<Option<SignedTx>>,
+    ) -> Option<BlockBody> {
+        let header = compact.header();
+        let missing: Vec<u32> = txs
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| tx.is_none())
+            .map(|(index, _)| index as u32)
+            .collect();
+
+        if !missing.is_empty() {
+            match self
+                .peer_client
+                .get_block_txs(peer, header.hash(), &missing)
+            {
+                Ok(fetched) if fetched.len() == missing.len() => {
+                    for (index, tx) in missing.into_iter().zip(fetched) {
+                        txs[index as usize] = Some(tx);
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => error!("❌ Failed to get block txs from {}: {:?}", peer, err),
+            }
+        }
+
+        if let Some(txs) = txs.into_iter().collect::<Option<Vec<_>>>() {
+            let body = BlockBody { txs };
+            if header.matches_body(&body) {
+                return Some(body);
+            }
+        }
+
+        match self.peer_client.get_block_bodies(peer, &[header.hash()]) {
+            Ok(bodies) => bodies
+                .into_iter()
+                .next()
+                .filter(|body| header.matches_body(body)),
+            Err(err) => {
+                error!("❌ Failed to get block body from {}: {:?}", peer, err);
+                None
+            }
+        }
+    }
+
+    /// Download the bodies of the headers in parallel, and assemble the blocks.
+    fn download_blocks(&self, peers: &[&str], headers: Vec<BlockHeader>) -> Option<Vec<Block>> {
+        if peers.is_empty() {
+            return None;
+        }
+
+        let hashes: Vec<Hash> = headers.iter().map(|header| header.hash()).collect();
+        let bodies: Option<Vec<Vec<BlockBody>>> = thread::scope(|s| {
+            let handles: Vec<_> = hashes
+                .chunks(BODIES_BATCH_SIZE)
+                .zip(headers.chunks(BODIES_BATCH_SIZE))
+                .enumerate()
+                .map(|(i, (hashes, headers))| {
+                    s.spawn(move || self.download_bodies(peers, i, hashes, headers))
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().ok().flatten())
+                .collect()
+        });
+
+        let blocks = headers
+            .into_iter()
+            .zip(bodies?.into_iter().flatten())
+            .map(|(header, body)| Block::from_parts(header, body))
+            .collect();
+
+        Some(blocks)
+    }
+
+    /// Download a batch of bodies, starting from the `index`-th peer, and retrying with the
+    /// next peers on failure.
+    fn download_bodies(
+        &self,
+        peers: &[&str],
+        index: usize,
+        hashes: &[Hash],
+        headers: &[BlockHeader],
+    ) -> Option<Vec<BlockBody>> {
+        for attempt in 0..BODIES_MAX_ATTEMPTS {
+            let peer = peers[(index + attempt) % peers.len()];
+            match self.peer_client.get_block_bodies(peer, hashes) {
+                Ok(bodies) => match check_bodies(headers, &bodies) {
+                    Ok(()) => return Some(bodies),
+                    Err(err) => error!("❌ Bad block bodies from {}: {:?}", peer, err),
+                },
+                Err(err) => error!("❌ Failed to get block bodies from {}: {:?}", peer, err),
+            }
+        }
+
+        None
+    }
+}
+
+/// Exchange the status with the peer, and disconnect it if it is on another chain, or its
+/// status can't be verified.
+/// Returns `true` if the peer is on the same chain.
+pub(super) fn handshake<P: PeerClient>(peer_client: &P, peer_book: &PeerBook, peer: &str) -> bool {
+    let status = match peer_client.get_status(peer) {
+        Ok(status) => status,
+        Err(err) => {
+            error!("❌ Failed to get status from {}: {:?}", peer, err);
+            return false;
+        }
+    };
+
+    let result = peer_book
+        .insert(peer, status.clone())
+        .and_then(|()| verify_peer_chain(peer_client, peer_book, peer, &status));
+    match result {
+        Ok(()) => true,
+        Err(err) => {
+            reject_peer(peer_client, peer_book, peer, &err);
+            false
+        }
+    }
+}
+
+/// Forget the peer and disconnect it, as it is on another chain or sent invalid data.
+fn reject_peer<P: PeerClient>(peer_client: &P, peer_book: &PeerBook, peer: &str, err: &Error) {
+    error!("❌ Disconnecting peer {}: {:?}", peer, err);
+    peer_book.remove(peer);
+    let _ = peer_client.disconnect(peer);
+}
+
+/// Verify the head that the peer reports instead of trusting it, and reject the peer if its
+/// chain conflicts with the last checkpoint below its head. Only the head is checked here, the
+/// rest of the chain is checked as its headers are downloaded.
+fn verify_peer_chain<P: PeerClient>(
+    peer_client: &P,
+    peer_book: &PeerBook,
+    peer: &str,
+    status: &StatusResp,
+) -> Result<(), Error> {
+    let Some(head_number) = status.height.checked_sub(1) else {
+        return Ok(());
+    };
+
+    let head = get_header(peer_client, peer, head_number)?
+        .ok_or_else(|| Error::InvalidPeerStatus("missing head header".to_string()))?;
+    let head_hash = head.hash();
+    if head_hash.to_vec() != status.head_hash {
+        return Err(Error::InvalidPeerStatus(
+            "head hash mismatches the head header".to_string(),
+        ));
+    }
+    if !utils::is_valid_hash(&head_hash, peer_book.mining_difficulty()) {
+        return Err(Error::InvalidBlockHash(
+            head_hash,
+            peer_book.mining_difficulty(),
+        ));
+    }
+
+    // A peer started from a snapshot may not have the checkpoint block, which is fine as
+    // the checkpoints are checked again when syncing from it.
+    let checkpoints = peer_book.checkpoints();
+    if let Some((number, _)) = checkpoints.last_below(status.height) {
+        if number >= status.lowest_number {
+            if let Some(header) = get_header(peer_client, peer, number)? {
+                checkpoints.check(&header)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the header of the given number from the peer, if it has the block.
+pub(super) fn get_header<P: PeerClient>(
+    peer_client: &P,
+    peer: &str,
+    number: u64,
+) -> Result<Option<BlockHeader>, Error> {
+    let headers = peer_client.get_headers(peer, number, 1)?;
+    Ok(headers.into_iter().next().filter(|h| h.number == number))
+}
+
+/// Download a valid chain of headers, trying the peers one by one. The peers that serve invalid
+/// headers are rejected.
+pub(super) fn download_headers<P: PeerClient>(
+    peer_client: &P,
+    peer_book: &PeerBook,
+    peers: &[(String, u64)],
+    parent_hash: Hash,
+    from_number: u64,
+    count: u64,
+) -> Option<Vec<BlockHeader>> {
+    for (peer, height) in peers {
+        if *height <= from_number {
+            break;
+        }
+        if !peer_book.has_blocks_from(peer, from_number) {
+            continue;
+        }
+
+        let headers = match peer_client.get_headers(peer, from_number, count) {
+            Ok(headers) if !headers.is_empty() => headers,
+            Ok(_) => continue,
+            Err(err @ Error::InvalidBlockHeader(..)) => {
+                reject_peer(peer_client, peer_book, peer, &err);
+                continue;
+            }
+            Err(err) => {
+                error!("❌ Failed to get headers from {}: {:?}", peer, err);
+                continue;
+            }
+        };
+
+        let checked = check_headers(
+            &headers,
+            parent_hash,
+            from_number,
+            peer_book.mining_difficulty(),
+            peer_book.checkpoints(),
+        );
+        match checked {
+            Ok(()) => return Some(headers),
+            Err(err) => reject_peer(peer_client, peer_book, peer, &err),
+        }
+    }
+
+    None
+}
+
+/// Check that the headers form a valid chain on top of the `parent_hash`, which passes through
+/// the checkpoints.
+fn check_headers(
+    headers: &[BlockHeader],
+    mut parent_hash: Hash,
+    mut expected_number: u64,
+    mining_difficulty: usize,
+    checkpoints: &Checkpoints,
+) -> Result<(), Error> {
+    for header in headers {
+        header.check_hashes()?;
+        if header.number != expected_number {
+            return Err(Error::InvalidBlockNumber(expected_number, header.number));
+        }
+
+        if header.parent_hash() != parent_hash {
+            return Err(Error::InvalidBlockParent(parent_hash, header.parent_hash()));
+        }
+
+        let hash = header.hash();
+        if !utils::is_valid_hash(&hash, mining_difficulty) {
+            return Err(Error::InvalidBlockHash(hash, mining_difficulty));
+        }
+        checkpoints.check(header)?;
+
+        parent_hash = hash;
+        expected_number += 1;
+    }
+
+    Ok(())
+}
+
+/// Check that every header has a body which matches its `txs_hash`, and that there are no more
+/// bodies than headers, as the bodies of the batches are joined by their positions.
+fn check_bodies(headers: &[BlockHeader], bodies: &[BlockBody]) -> Result<(), Error> {
+    if let Some(header) = headers.get(bodies.len()) {
+        return Err(Error::MissingBlockBody(header.hash()));
+    }
+    if bodies.len() > headers.len() {
+        return Err(Error::InvalidResponse(format!(
+            "expected {} block bodies, got {}",
+            headers.len(),
+            bodies.len()
+        )));
+    }
+
+    for (header, body) in headers.iter().zip(bodies) {
+        if !header.matches_body(body) {
+            return Err(Error::InvalidBlockTxsHash(header.hash()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use crossbeam_channel::{bounded, unbounded};
+
+    use super::*;
+    use crate::{
+        biz::{ChainSpec, Checkpoints, Snapshot, SnapshotStore, StateChunks},
+        data::MemoryState,
+        schema::{AccountProof, SignedTx, StatusResp, Tx},
+    };
+
+    #[derive(Debug, Clone)]
+    struct MockPeer {
+        state: MemoryState,
+        /// The chain that the peer is on.
+        book: PeerBook,
+        tx_pool: TxPool,
+        snapshots: SnapshotStore,
+        /// The peer claims to be higher than it is.
+        fake_height: u64,
+        /// The peer claims more total work than its height is worth.
+        fake_work: bool,
+        /// The peer responds with more block bodies than requested.
+        extra_bodies: bool,
+    }
+
+    impl MockPeer {
+        fn new(state: MemoryState, chain_id: &str) -> Self {
+            Self {
+                state,
+                book: new_peer_book(chain_id),
+                tx_pool: TxPool::new(),
+                snapshots: SnapshotStore::new(),
+                fake_height: 0,
+                fake_work: false,
+                extra_bodies: false,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    struct MockPeers {
+        peers: HashMap<String, MockPeer>,
+        disconnected: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockPeers {
+        fn state(&self, peer_id: &str) -> &MemoryState {
+            &self.peers[peer_id].state
+        }
+    }
+
+    impl PeerClient for MockPeers {
+        fn known_peers(&self) -> Vec<String> {
+            self.peers.keys().cloned().collect()
+        }
+
+        fn get_status(&self, peer_id: &str) -> Result<StatusResp, Error> {
+            let peer = &self.peers[peer_id];
+            let mut status = peer.book.local_status(&peer.state);
+            status.height += peer.fake_height;
+            if peer.fake_work {
+                status.total_work = u64::MAX;
+            }
+            Ok(status)
+        }
+
+        fn disconnect(&self, peer_id: &str) -> Result<(), Error> {
+            self.disconnected.lock().unwrap().push(peer_id.to_string());
+            Ok(())
+        }
+
+        fn get_headers(
+            &self,
+            peer_id: &str,
+            from_number: u64,
+            count: u64,
+        ) -> Result<Vec<BlockHeader>, Error> {
+            Ok(self.state(peer_id).get_headers(from_number, count as usize))
+        }
+
+        fn get_block_bodies(
+            &self,
+            peer_id: &str,
+            hashes: &[Hash],
+        ) -> Result<Vec<BlockBody>, Error> {
+            let peer = &self.peers[peer_id];
+            let mut bodies: Vec<BlockBody> = hashes
+                .iter()
+                .map_while(|hash| peer.state.get_block_by_hash(hash))
+                .map(|block| BlockBody { txs: block.txs })
+                .collect();
+            if peer.extra_bodies {
+                bodies.push(BlockBody::default());
+            }
+            Ok(bodies)
+        }
+
+        fn get_block_txs(
+            &self,
+            peer_id: &str,
+            block_hash: Hash,
+            indexes: &[u32],
+        ) -> Result<Vec<SignedTx>, Error> {
+            let Some(block) = self.state(peer_id).get_block_by_hash(&block_hash) else {
+                return Ok(vec![]);
+            };
+            Ok(indexes
+                .iter()
+                .map(|index| block.txs[*index as usize].clone())
+                .collect())
+        }
+
+        fn get_pending_tx_hashes(&self, peer_id: &str) -> Result<Vec<Hash>, Error> {
+            Ok(self.peers[peer_id].tx_pool.hashes())
+        }
+
+        fn get_pending_txs(&self, peer_id: &str, hashes: &[Hash]) -> Result<Vec<SignedTx>, Error> {
+            Ok(self.peers[peer_id].tx_pool.get_txs(hashes))
+        }
+
+        fn get_snapshot_manifest(&self, peer_id: &str) -> Result<Option<SnapshotManifest>, Error> {
+            Ok(self.peers[peer_id].snapshots.manifest())
+        }
+
+        fn get_snapshot_chunk(
+            &self,
+            peer_id: &str,
+            block_hash: Hash,
+            index: u32,
+        ) -> Result<Option<StateChunk>, Error> {
+            Ok(self.peers[peer_id]
+                .snapshots
+                .chunk(&block_hash, index as usize))
+        }
+
+        fn get_account_proof(&self, _: &str, _: &str) -> Result<Option<AccountProof>, Error> {
+            Ok(None)
+        }
+
+        fn broadcast_tx(&self, _tx: SignedTx) {}
+
+        fn broadcast_block(&self, _block: Block) {}
+    }
+
+    /// A chain of empty blocks on top of the genesis `balances`.
+    fn new_chain(len: u64, balances: &HashMap<String, u64>) -> Vec<Block> {
+        let root = StateChunks::new(balances, &HashMap::new()).state_root();
+        let mut parent_hash = Hash::default();
+        (0..len)
+            .map(|number| {
+                let mut block = Block::new(parent_hash, number, "0x01234567".to_string(), vec![]);
+                block.set_state_root(root);
+                parent_hash = block.hash();
+                block
+            })
+            .collect()
+    }
+
+    fn headers_of(blocks: &[Block]) -> Vec<BlockHeader> {
+        blocks.iter().map(|b| b.header.clone().unwrap()).collect()
+    }
+
+    #[test]
+    fn check_headers_works() {
+        let headers = headers_of(&new_chain(3, &HashMap::new()));
+        let none = Checkpoints::default();
+        assert!(check_headers(&headers, Hash::default(), 0, 0, &none).is_ok());
+        assert!(check_headers(&headers[1..], headers[0].hash(), 1, 0, &none).is_ok());
+
+        // Wrong start number.
+        assert!(check_headers(&headers[1..], headers[0].hash(), 2, 0, &none).is_err());
+        // Broken linkage.
+        assert!(check_headers(&headers[1..], Hash::default(), 1, 0, &none).is_err());
+        // Not enough work.
+        assert!(check_headers(&headers, Hash::default(), 0, 32, &none).is_err());
+        // Conflicting with a checkpoint.
+        let checkpoints = Checkpoints::from_iter([(1, Hash::default())]);
+        assert!(check_headers(&headers, Hash::default(), 0, 0, &checkpoints).is_err());
+        // Malformed hashes.
+        let mut malformed = headers.clone();
+        malformed[2].state_root.truncate(31);
+        assert!(check_headers(&malformed, Hash::default(), 0, 0, &none).is_err());
+    }
+
+    #[test]
+    fn check_bodies_works() {
+        let blocks = new_chain(2, &HashMap::new());
+        let headers = headers_of(&blocks);
+        let bodies = vec![BlockBody::default(), BlockBody::default()];
+        assert!(check_bodies(&headers, &bodies).is_ok());
+        assert!(check_bodies(&headers, &bodies[..1]).is_err());
+        assert!(check_bodies(&headers[..1], &bodies).is_err());
+
+        let mut bad_header = headers[1].clone();
+        bad_header.txs_hash = vec![1u8; 32];
+        assert!(check_bodies(&[bad_header], &bodies[..1]).is_err());
+    }
+
+    fn new_peer_book(chain_id: &str) -> PeerBook {
+        let chain_spec = ChainSpec {
+            chain_id: chain_id.to_string(),
+            genesis_hash: Hash::default(),
+            checkpoints: Checkpoints::default(),
+        };
+        PeerBook::new(chain_spec, 0)
+    }
+
+    /// A syncer with the other ends of its channels.
+    struct TestSyncer {
+        syncer: Syncer<MemoryState, MockPeers>,
+        block_receiver: Receiver<BlockMsg>,
+        tx_receiver: Receiver<TxMsg>,
+        snapshot_receiver: Receiver<SnapshotMsg>,
+        // The syncer stops once the shutdown sender is dropped, so it must be bound in the tests.
+        _shutdown: Sender<()>,
+    }
+
+    fn new_syncer(
+        local: MemoryState,
+        peers: MockPeers,
+        tx_pool: TxPool,
+        snapshot_sync: bool,
+    ) -> TestSyncer {
+        let (tx_sender, tx_receiver) = unbounded();
+        let (block_sender, block_receiver) = unbounded();
+        let (snapshot_sender, snapshot_receiver) = unbounded();
+        let (_, sync_receiver) = unbounded();
+        let (_, compact_block_receiver) = unbounded();
+        let (shutdown_sender, shutdown) = bounded(0);
+        let syncer = Syncer::new(
+            local,
+            peers,
+            snapshot_sync,
+            new_peer_book("test"),
+            tx_pool,
+            tx_sender,
+            block_sender,
+            snapshot_sender,
+            sync_receiver,
+            compact_block_receiver,
+            shutdown,
+        );
+
+        TestSyncer {
+            syncer,
+            block_receiver,
+            tx_receiver,
+            snapshot_receiver,
+            _shutdown: shutdown_sender,
+        }
+    }
+
+    #[test]
+    fn sync_from_multiple_peers() {
+        let chain = new_chain(HEADERS_BATCH_SIZE + 10, &HashMap::new());
+        let mut peers = HashMap::new();
+        for (name, len, chain_id) in [
+            ("full", chain.len(), "test"),
+            ("partial", 100, "test"),
+            ("behind", 0, "test"),
+            ("other-chain", chain.len(), "other"),
+        ] {
+            let state = MemoryState::new(HashMap::new());
+            for block in &chain[..len] {
+                state.add_block(block.clone()).unwrap();
+            }
+            let peer = MockPeer::new(state, chain_id);
+            peers.insert(name.to_string(), peer);
+        }
+        let peers = MockPeers {
+            peers,
+            disconnected: Arc::new(Mutex::new(vec![])),
+        };
+
+        let local = MemoryState::new(HashMap::new());
+        local.add_block(chain[0].clone()).unwrap();
+
+        let TestSyncer {
+            syncer,
+            block_receiver,
+            _shutdown,
+            ..
+        } = new_syncer(local, peers.clone(), TxPool::new(), false);
+
+        // The peer on another chain is disconnected.
+        for peer in peers.known_peers() {
+            assert_eq!(syncer.handshake(&peer), peer != "other-chain");
+        }
+        assert_eq!(*peers.disconnected.lock().unwrap(), vec!["other-chain"]);
+        assert!(!syncer.peer_book.contains("other-chain"));
+
+        let synced = || -> Vec<Block> { block_receiver.try_iter().map(|msg| msg.block).collect() };
+
+        // Only the target peer is synced from, if given.
+        syncer.sync_once(Some("behind"));
+        assert!(synced().is_empty());
+        syncer.sync_once(Some("partial"));
+        assert_eq!(synced(), chain[1..100]);
+
+        // The syncer doesn't track the blocks that are not yet added by the miner.
+        syncer.sync_once(None);
+        assert_eq!(synced(), chain[1..]);
+    }
+
+    #[test]
+    fn sync_missing_pending_txs() {
+        let new_tx = |nonce| SignedTx {
+            tx: Some(Tx::new("0x00000000", "0x11111111", 100, nonce)),
+            sig: vec![0u8; 65],
+        };
+
+        let remote_pool = TxPool::new();
+        for nonce in [2, 0, 1] {
+            remote_pool.insert(new_tx(nonce));
+        }
+        let peer = MockPeer {
+            tx_pool: remote_pool,
+            ..MockPeer::new(MemoryState::new(HashMap::new()), "test")
+        };
+        let peers = MockPeers {
+            peers: HashMap::from([("peer".to_string(), peer)]),
+            disconnected: Arc::new(Mutex::new(vec![])),
+        };
+
+        // The tx with nonce 0 is already pending locally.
+        let local_pool = TxPool::new();
+        local_pool.insert(new_tx(0));
+        let local = MemoryState::new(HashMap::new());
+        let TestSyncer {
+            syncer,
+            tx_receiver,
+            _shutdown,
+            ..
+        } = new_syncer(local, peers, local_pool, false);
+
+        syncer.sync_txs("peer");
+        let nonces: Vec<u64> = tx_receiver.try_iter().map(|msg| msg.tx.nonce).collect();
+        assert_eq!(nonces, vec![1, 2]);
+    }
+
+    #[test]
+    fn complete_compact_block_with_missing_txs() {
+        let txs: Vec<SignedTx> = (0..3)
+            .map(|nonce| SignedTx {
+                tx: Some(Tx::new("0x00000000", "0x11111111", 100, nonce)),
+                sig: vec![0u8; 65],
+            })
+            .collect();
+        let genesis = Block::new(Hash::default(), 0, "0x01234567".to_string(), vec![]);
+        let block = Block::new(genesis.hash(), 1, "0x01234567".to_string(), txs.clone());
+
+        let balances = HashMap::from([("0x00000000".to_string(), 1000)]);
+        let state = MemoryState::new(balances);
+        state.add_block(genesis.clone()).unwrap();
+        state.add_block(block.clone()).unwrap();
+        let peer = MockPeer::new(state, "test");
+        let peers = MockPeers {
+            peers: HashMap::from([("peer".to_string(), peer)]),
+            disconnected: Arc::new(Mutex::new(vec![])),
+        };
+
+        // Only the second tx is in the local pool.
+        let local_pool = TxPool::new();
+        local_pool.insert(txs[1].clone());
+        let local = MemoryState::new(HashMap::new());
+        local.add_block(genesis).unwrap();
+        let TestSyncer {
+            syncer,
+            block_receiver,
+            _shutdown,
+            ..
+        } = new_syncer(local, peers, local_pool, false);
+
+        syncer.complete_compact_block(CompactBlockMsg {
+            compact: block.to_compact(),
+            source: "peer".to_string(),
+        });
+        let msg = block_receiver.try_recv().unwrap();
+        assert_eq!(msg.block, block);
+        assert_eq!(msg.source.as_deref(), Some("peer"));
+    }
+
+    const SNAPSHOT_NUMBER: u64 = HEADERS_BATCH_SIZE + 5;
+
+    #[test]
+    fn sync_from_snapshot() {
+        let balances = HashMap::from([("0x00000000".to_string(), 1000)]);
+        let chain = new_chain(SNAPSHOT_NUMBER + 10, &balances);
+
+        let state = MemoryState::new(balances.clone());
+        for block in &chain {
+            state.add_block(block.clone()).unwrap();
+        }
+        let peer = MockPeer::new(state, "test");
+        let snapshot_block = &chain[SNAPSHOT_NUMBER as usize];
+        peer.snapshots.set(Snapshot::new(
+            snapshot_block,
+            StateChunks::new(&balances, &HashMap::new()),
+        ));
+        let peers = MockPeers {
+            peers: HashMap::from([("peer".to_string(), peer)]),
+            disconnected: Arc::new(Mutex::new(vec![])),
+        };
+
+        let local = MemoryState::new(balances.clone());
+        let TestSyncer {
+            syncer,
+            block_receiver,
+            snapshot_receiver,
+            _shutdown,
+            ..
+        } = new_syncer(local.clone(), peers, TxPool::new(), true);
+        assert!(syncer.handshake("peer"));
+
+        // Import the snapshot as the miner does.
+        let miner = thread::spawn(move || {
+            let msg = snapshot_receiver.recv().unwrap();
+            let result = local.import_snapshot(msg.block, msg.balances, msg.account2nonce);
+            msg.reply.send(result).unwrap();
+            local
+        });
+        syncer.sync_once(None);
+        let local = miner.join().unwrap();
+
+        assert_eq!(local.block_height(), SNAPSHOT_NUMBER + 1);
+        assert_eq!(local.last_block_hash(), Some(snapshot_block.hash()));
+        assert_eq!(local.get_balances(), balances);
+
+        // Only the blocks after the snapshot are synced.
+        let synced: Vec<Block> = block_receiver.try_iter().map(|msg| msg.block).collect();
+        assert_eq!(synced, chain[SNAPSHOT_NUMBER as usize + 1..]);
+
+        // The blocks before the snapshot are not kept, which the status tells the peers.
+        assert_eq!(local.lowest_block_number(), SNAPSHOT_NUMBER);
+        assert!(local.get_headers(0, 10).is_empty());
+        assert_eq!(local.get_headers(SNAPSHOT_NUMBER, 10).len(), 1);
+        let status = new_peer_book("test").local_status(&local);
+        assert_eq!(status.lowest_number, SNAPSHOT_NUMBER);
+    }
+
+    #[test]
+    fn peers_without_the_blocks_are_skipped() {
+        let balances = HashMap::from([("0x00000000".to_string(), 1000)]);
+        let chain = new_chain(SNAPSHOT_NUMBER + 10, &balances);
+
+        // The peer started from a snapshot is ahead, but only has the blocks from the snapshot.
+        let full = MemoryState::new(balances.clone());
+        for block in &chain[..SNAPSHOT_NUMBER as usize + 5] {
+            full.add_block(block.clone()).unwrap();
+        }
+        let snapshot = MemoryState::new(HashMap::new());
+        let snapshot_block = &chain[SNAPSHOT_NUMBER as usize];
+        snapshot
+            .import_snapshot(snapshot_block.clone(), balances.clone(), HashMap::new())
+            .unwrap();
+        for block in &chain[SNAPSHOT_NUMBER as usize + 1..] {
+            snapshot.add_block(block.clone()).unwrap();
+        }
+        let peers = MockPeers {
+            peers: HashMap::from([
+                ("full".to_string(), MockPeer::new(full, "test")),
+                ("snapshot".to_string(), MockPeer::new(snapshot, "test")),
+            ]),
+            disconnected: Arc::new(Mutex::new(vec![])),
+        };
+        let disconnected = peers.disconnected.clone();
+
+        let local = MemoryState::new(balances);
+        let TestSyncer {
+            syncer,
+            block_receiver,
+            _shutdown,
+            ..
+        } = new_syncer(local, peers, TxPool::new(), false);
+        assert!(syncer.handshake("full"));
+        assert!(syncer.handshake("snapshot"));
+        assert!(!syncer.peer_book.has_blocks_from("snapshot", 0));
+
+        syncer.sync_once(None);
+        let synced: Vec<Block> = block_receiver.try_iter().map(|msg| msg.block).collect();
+        assert_eq!(synced, chain);
+        assert!(disconnected.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn peers_with_bad_headers_are_rejected() {
+        let chain = new_chain(10, &HashMap::new());
+        // The liar is ahead, but its chain is broken at block 2, which is only found out when
+        // downloading the headers.
+        let mut broken = new_chain(15, &HashMap::new());
+        broken[2].header.as_mut().unwrap().parent_hash = vec![1u8; 32];
+        let mut peers = HashMap::new();
+        for (name, blocks) in [("honest", &chain), ("liar", &broken)] {
+            let state = MemoryState::new(HashMap::new());
+            for block in blocks.iter() {
+                state.add_block(block.clone()).unwrap();
+            }
+            peers.insert(name.to_string(), MockPeer::new(state, "test"));
+        }
+        let peers = MockPeers {
+            peers,
+            disconnected: Arc::new(Mutex::new(vec![])),
+        };
+
+        let TestSyncer {
+            syncer,
+            block_receiver,
+            _shutdown,
+            ..
+        } = new_syncer(
+            MemoryState::new(HashMap::new()),
+            peers.clone(),
+            TxPool::new(),
+            false,
+        );
+        assert!(syncer.handshake("honest"));
+        assert!(syncer.handshake("liar"));
+        assert_eq!(syncer.peer_book.peers_ahead(0)[0].0, "liar");
+
+        syncer.sync_once(None);
+        let synced: Vec<Block> = block_receiver.try_iter().map(|msg| msg.block).collect();
+        assert_eq!(synced, chain);
+        assert_eq!(*peers.disconnected.lock().unwrap(), vec!["liar"]);
+        assert_eq!(syncer.peer_book.peer_ids(), vec!["honest"]);
+    }
+
+    #[test]
+    fn extra_bodies_are_rejected() {
+        // The blocks have txs, so that a body shifted to another block doesn't match it.
+        let mut chain: Vec<Block> = vec![];
+        for number in 0..BODIES_BATCH_SIZE as u64 * 3 {
+            let parent_hash = chain.last().map(|b| b.hash()).unwrap_or_default();
+            let tx = SignedTx {
+                tx: Some(Tx {
+                    nonce: number,
+                    ..Default::default()
+                }),
+                sig: vec![],
+            };
+            let block = Block::new(parent_hash, number, "0x01234567".to_string(), vec![tx]);
+            chain.push(block);
+        }
+        let mut peers = HashMap::new();
+        for (name, extra_bodies) in [("honest", false), ("padder", true)] {
+            let state = MemoryState::new(HashMap::new());
+            for block in &chain {
+                state.add_block(block.clone()).unwrap();
+            }
+            let peer = MockPeer {
+                extra_bodies,
+                ..MockPeer::new(state, "test")
+            };
+            peers.insert(name.to_string(), peer);
+        }
+        let peers = MockPeers {
+            peers,
+            disconnected: Arc::new(Mutex::new(vec![])),
+        };
+
+        let TestSyncer {
+            syncer,
+            block_receiver,
+            _shutdown,
+            ..
+        } = new_syncer(
+            MemoryState::new(HashMap::new()),
+            peers.clone(),
+            TxPool::new(),
+            false,
+        );
+        assert!(syncer.handshake("honest"));
+        assert!(syncer.handshake("padder"));
+
+        // Every batch asked from the padder is retried with the honest peer.
+        let headers = headers_of(&chain);
+        let blocks = syncer.download_blocks(&["padder", "honest"], headers);
+        assert_eq!(blocks.unwrap(), chain);
+
+        syncer.sync_once(None);
+        let synced: Vec<Block> = block_receiver.try_iter().map(|msg| msg.block).collect();
+        assert_eq!(synced, chain);
+    }
+
+    #[test]
+    fn inflated_work_does_not_pick_the_target() {
+        let chain = new_chain(10, &HashMap::new());
+        let mut peers = HashMap::new();
+        for (name, len, fake_work) in [("honest", 10, false), ("inflated", 5, true)] {
+            let state = MemoryState::new(HashMap::new());
+            for block in &chain[..len] {
+                state.add_block(block.clone()).unwrap();
+            }
+            let peer = MockPeer {
+                fake_work,
+                ..MockPeer::new(state, "test")
+            };
+            peers.insert(name.to_string(), peer);
+        }
+        let peers = MockPeers {
+            peers,
+            disconnected: Arc::new(Mutex::new(vec![])),
+        };
+
+        let TestSyncer {
+            syncer,
+            block_receiver,
+            _shutdown,
+            ..
+        } = new_syncer(
+            MemoryState::new(HashMap::new()),
+            peers.clone(),
+            TxPool::new(),
+            false,
+        );
+        assert!(syncer.handshake("honest"));
+        assert!(!syncer.handshake("inflated"));
+        assert_eq!(*peers.disconnected.lock().unwrap(), vec!["inflated"]);
+        assert_eq!(
+            syncer.peer_book.peers_ahead(0),
+            vec![("honest".to_string(), 10)]
+        );
+
+        // The target is the head of the honest peer, not the lower head of the inflated one.
+        syncer.sync_once(None);
+        let synced: Vec<Block> = block_receiver.try_iter().map(|msg| msg.block).collect();
+        assert_eq!(synced, chain);
+    }
+
+    #[test]
+    fn peers_are_verified_on_handshake() {
+        let chain = new_chain(10, &HashMap::new());
+        let fork = new_chain(10, &HashMap::new());
+        let mut peers = HashMap::new();
+        for (name, blocks, fake_height) in [
+            ("honest", &chain, 0),
+            ("liar", &chain, 5),
+            ("fork", &fork, 0),
+        ] {
+            let state = MemoryState::new(HashMap::new());
+            for block in blocks.iter() {
+                state.add_block(block.clone()).unwrap();
+            }
+            let peer = MockPeer {
+                fake_height,
+                ..MockPeer::new(state, "test")
+            };
+            peers.insert(name.to_string(), peer);
+        }
+        let peers = MockPeers {
+            peers,
+            disconnected: Arc::new(Mutex::new(vec![])),
+        };
+
+        let TestSyncer {
+            mut syncer,
+            _shutdown,
+            ..
+        } = new_syncer(
+            MemoryState::new(HashMap::new()),
+            peers.clone(),
+            TxPool::new(),
+            false,
+        );
+        syncer.peer_book = PeerBook::new(
+            ChainSpec {
+                chain_id: "test".to_string(),
+                genesis_hash: Hash::default(),
+                checkpoints: Checkpoints::from_iter([(5, chain[5].hash())]),
+            },
+            0,
+        );
+
+        assert!(syncer.handshake("honest"));
+        assert!(!syncer.handshake("liar"));
+        assert!(!syncer.handshake("fork"));
+        let mut disconnected = peers.disconnected.lock().unwrap().clone();
+        disconnected.sort();
+        assert_eq!(disconnected, vec!["fork", "liar"]);
+        assert_eq!(syncer.peer_book.peer_ids(), vec!["honest"]);
     }
 }
@@ -0,0 +1,77 @@
+//! The pool of pending transactions that are not yet included in a block.
+//!
+//! Following the read/write separation of the biz layer, only the `Miner` adds or removes
+//! transactions, while other threads can read the pool, e.g. to serve other peers.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{schema::SignedTx, types::Hash};
+
+#[derive(Debug, Clone, Default)]
+pub struct TxPool {
+    txs: Arc<RwLock<HashMap<Hash, SignedTx>>>,
+}
+
+impl TxPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, tx: SignedTx) {
+        self.txs.write().unwrap().insert(tx.hash(), tx);
+    }
+
+    pub fn remove(&self, hash: &Hash) {
+        self.txs.write().unwrap().remove(hash);
+    }
+
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.txs.read().unwrap().contains_key(hash)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.read().unwrap().is_empty()
+    }
+
+    /// Get the hashes of all the pending transactions.
+    pub fn hashes(&self) -> Vec<Hash> {
+        self.txs.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Get the pending transactions by the hashes, the unknown ones are skipped.
+    pub fn get_txs(&self, hashes: &[Hash]) -> Vec<SignedTx> {
+        let txs = self.txs.read().unwrap();
+        hashes
+            .iter()
+            .filter_map(|hash| txs.get(hash).cloned())
+            .collect()
+    }
+
+    /// Look up the txs of a compact block by their short IDs.
+    /// The unknown txs, and the ones whose short IDs are shared by several txs, are `None`.
+    pub fn get_txs_by_short_ids(&self, short_ids: &[u64]) -> Vec<Option<SignedTx>> {
+        let txs = self.txs.read().unwrap();
+        let mut by_short_id: HashMap<u64, Option<&SignedTx>> = HashMap::with_capacity(txs.len());
+        for tx in txs.values() {
+            by_short_id
+                .entry(tx.short_id())
+                .and_modify(|found| *found = None)
+                .or_insert(Some(tx));
+        }
+
+        short_ids
+            .iter()
+            .map(|id| by_short_id.get(id).copied().flatten().cloned())
+            .collect()
+    }
+
+    /// Get all the pending transactions, sorted by the timestamp.
+    pub fn sorted_txs(&self) -> Vec<SignedTx> {
+        let mut txs: Vec<SignedTx> = self.txs.read().unwrap().values().cloned().collect();
+        txs.sort_by_key(|tx| tx.timestamp);
+        txs
+    }
+}
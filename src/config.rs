@@ -4,7 +4,7 @@ use serde::Deserialize;
 use tinyp2p::P2pConfig;
 use wallet::WalletConfig;
 
-use crate::error::Error;
+use crate::{biz::HistoryMode, error::Error};
 
 #[derive(Clone, Debug, Default, Deserialize)]
 pub struct Config {
@@ -16,12 +16,32 @@ pub struct Config {
     pub http_addr: String,
     /// The miner account to receive mining rewards.
     pub author: String,
+    /// Whether to run a full node, or a light node that only syncs the headers.
+    #[serde(default)]
+    pub mode: NodeMode,
+    /// How much history of the balances and nonces a full node keeps.
+    #[serde(default)]
+    pub history: HistoryMode,
+    /// Whether a new node starts with a state snapshot of the peers instead of replaying
+    /// all the blocks.
+    #[serde(default)]
+    pub snapshot_sync: bool,
     /// P2p configuration.
     pub p2p: P2pConfig,
     /// Wallet configuration.
     pub wallet: WalletConfig,
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeMode {
+    /// Sync and validate the whole blocks, and mine new blocks.
+    #[default]
+    Full,
+    /// Only sync and validate the headers, and ask the full peers to prove the balances.
+    Light,
+}
+
 impl Config {
     /// Load the configuration from the given path.
     pub fn load(path: &str) -> Result<Self, Error> {
@@ -48,6 +68,9 @@ mod tests {
             genesis_file,
             http_addr,
             author: miner,
+            mode,
+            history,
+            snapshot_sync,
             p2p,
             wallet,
         } = Config::load(path_str).unwrap();
@@ -56,6 +79,9 @@ mod tests {
         assert_eq!(genesis_file, "./genesis.json");
         assert_eq!(http_addr, "127.0.0.1:8000");
         assert_eq!(miner, "0x8d1cbb757610619d74fdca9ee008a007a633a71e");
+        assert_eq!(mode, NodeMode::Full);
+        assert_eq!(history, HistoryMode::Latest);
+        assert!(!snapshot_sync);
         assert_eq!(wallet.keystore_dir, "./db/keystore/");
 
         let P2pConfig {
@@ -65,6 +91,8 @@ mod tests {
             discovery_interval,
             pubsub_topics,
             req_resp,
+            limits,
+            nat,
         }: P2pConfig = p2p;
 
         assert_eq!(addr, "/ip4/0.0.0.0/tcp/9000");
@@ -91,5 +119,18 @@ mod tests {
         assert_eq!(req_resp.request_timeout, Some(10));
         assert_eq!(req_resp.max_request_size, Some(1048576));
         assert_eq!(req_resp.max_response_size, Some(1048576));
+
+        let limits = limits.unwrap();
+        assert_eq!(limits.max_inbound_connections, Some(50));
+        assert_eq!(limits.max_outbound_connections, Some(50));
+        assert_eq!(limits.max_connections_per_peer, Some(2));
+        assert_eq!(limits.requests_per_second, Some(20));
+        assert_eq!(limits.request_burst, Some(40));
+
+        let nat = nat.unwrap();
+        assert!(nat.relay_node.is_none());
+        assert_eq!(nat.enable_relay_server, Some(false));
+        assert!(nat.external_addresses.is_empty());
+        assert_eq!(nat.autonat_only_global_ips, Some(false));
     }
 }
@@ -1,9 +1,14 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     sync::{Arc, RwLock},
 };
 
-use crate::{biz::State, error::Error, schema::Block, types::Hash};
+use crate::{
+    biz::{block_range, State, TxDirection, TxLocation},
+    error::Error,
+    schema::{AccountState, Block, BlockHeader, Tx, TxReceipt},
+    types::Hash,
+};
 
 #[derive(Debug, Clone)]
 pub struct MemoryState {
@@ -13,15 +18,40 @@ pub struct MemoryState {
 #[derive(Debug, Clone)]
 struct InnerState {
     blocks: BTreeMap<u64, Block>,
+    block_hashes: HashMap<Hash, u64>,
+    receipts: HashMap<Hash, TxReceipt>,
+    account_txs: HashMap<String, BTreeSet<TxLocation>>,
     balances: HashMap<String, u64>,
     account2nonce: HashMap<String, u64>,
 }
 
+impl InnerState {
+    fn index_block(&mut self, block: &Block) {
+        self.block_hashes.insert(block.hash(), block.number());
+        for (index, tx) in block.txs.iter().enumerate() {
+            let location = TxLocation {
+                number: block.number(),
+                index: index as u32,
+            };
+            let receipt = TxReceipt::new(tx, block.hash(), block.number(), index as u32);
+            self.receipts.insert(tx.hash(), receipt);
+            for account in [&tx.from, &tx.to] {
+                self.account_txs
+                    .entry(account.clone())
+                    .or_default()
+                    .insert(location);
+            }
+        }
+    }
+}
+
 impl MemoryState {
-    #[allow(dead_code)]
     pub fn new(balances: HashMap<String, u64>) -> Self {
         let inner = InnerState {
             blocks: BTreeMap::new(),
+            block_hashes: HashMap::new(),
+            receipts: HashMap::new(),
+            account_txs: HashMap::new(),
             balances,
             account2nonce: HashMap::new(),
         };
@@ -34,7 +64,13 @@ impl MemoryState {
 
 impl State for MemoryState {
     fn block_height(&self) -> u64 {
-        self.inner.read().unwrap().blocks.len() as u64
+        self.inner
+            .read()
+            .unwrap()
+            .blocks
+            .keys()
+            .next_back()
+            .map_or(0, |number| number + 1)
     }
 
     fn next_account_nonce(&self, account: &str) -> u64 {
@@ -57,6 +93,17 @@ impl State for MemoryState {
             .map(|b| b.hash())
     }
 
+    fn lowest_block_number(&self) -> u64 {
+        self.inner
+            .read()
+            .unwrap()
+            .blocks
+            .keys()
+            .next()
+            .copied()
+            .unwrap_or_default()
+    }
+
     fn add_block(&self, block: Block) -> Result<(), Error> {
         let mut inner = self.inner.write().unwrap();
 
@@ -73,25 +120,81 @@ impl State for MemoryState {
             block.author().into(),
             block.block_reward(),
         );
+        inner.index_block(&block);
         inner.blocks.insert(block.number(), block);
 
         Ok(())
     }
 
-    fn get_blocks(&self, from_number: u64) -> Vec<Block> {
+    fn import_snapshot(
+        &self,
+        block: Block,
+        balances: HashMap<String, u64>,
+        account2nonce: HashMap<String, u64>,
+    ) -> Result<(), Error> {
+        let mut inner = self.inner.write().unwrap();
+        if !inner.blocks.is_empty() {
+            return Err(Error::InvalidSnapshot("the state has blocks".to_string()));
+        }
+
+        inner.balances = balances;
+        inner.account2nonce = account2nonce;
+        inner.index_block(&block);
+        inner.blocks.insert(block.number(), block);
+
+        Ok(())
+    }
+
+    fn get_blocks(
+        &self,
+        from_number: u64,
+        to_number: Option<u64>,
+        limit: usize,
+        reverse: bool,
+    ) -> Vec<Block> {
+        let Some((start, end)) = block_range(from_number, to_number, reverse) else {
+            return vec![];
+        };
+
+        let inner = self.inner.read().unwrap();
+        let range = inner
+            .blocks
+            .range(start..=end)
+            .map(|(_, block)| block.clone());
+        if reverse {
+            range.rev().take(limit).collect()
+        } else {
+            range.take(limit).collect()
+        }
+    }
+
+    fn get_block(&self, number: u64) -> Option<Block> {
+        self.inner.read().unwrap().blocks.get(&number).cloned()
+    }
+
+    fn get_block_by_hash(&self, hash: &Hash) -> Option<Block> {
+        let inner = self.inner.read().unwrap();
+        inner
+            .block_hashes
+            .get(hash)
+            .and_then(|number| inner.blocks.get(number))
+            .cloned()
+    }
+
+    fn get_headers(&self, from_number: u64, count: usize) -> Vec<BlockHeader> {
         self.inner
             .read()
             .unwrap()
             .blocks
             .range(from_number..)
-            .map(|(_, block)| block.clone())
+            .take(count)
+            .map(|(_, block)| block.header.clone().unwrap())
+            .enumerate()
+            .take_while(|(i, header)| header.number == from_number + *i as u64)
+            .map(|(_, header)| header)
             .collect()
     }
 
-    fn get_block(&self, number: u64) -> Option<Block> {
-        self.inner.read().unwrap().blocks.get(&number).cloned()
-    }
-
     fn get_balance(&self, account: &str) -> u64 {
         self.inner
             .read()
@@ -109,6 +212,63 @@ impl State for MemoryState {
     fn get_account2nonce(&self) -> HashMap<String, u64> {
         self.inner.read().unwrap().account2nonce.clone()
     }
+
+    fn get_tx_receipt(&self, hash: &Hash) -> Option<TxReceipt> {
+        self.inner.read().unwrap().receipts.get(hash).cloned()
+    }
+
+    fn get_account_tx_locations(
+        &self,
+        account: &str,
+        direction: Option<TxDirection>,
+        from: TxLocation,
+        to_number: Option<u64>,
+        limit: usize,
+    ) -> Vec<TxLocation> {
+        let inner = self.inner.read().unwrap();
+        let Some(locations) = inner.account_txs.get(account) else {
+            return vec![];
+        };
+
+        locations
+            .range(from..)
+            .take_while(|location| to_number.is_none_or(|to| location.number <= to))
+            .filter(|location| {
+                direction.is_none_or(|direction| {
+                    inner
+                        .blocks
+                        .get(&location.number)
+                        .and_then(|block| block.txs.get(location.index as usize))
+                        .is_some_and(|tx| matches_direction(direction, tx, account))
+                })
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    // Only the latest state is kept in memory.
+    fn get_balances_at(&self, number: u64) -> Option<HashMap<String, u64>> {
+        let inner = self.inner.read().unwrap();
+        (inner.blocks.keys().next_back() == Some(&number)).then(|| inner.balances.clone())
+    }
+
+    fn get_account_at(&self, account: &str, number: u64) -> Option<AccountState> {
+        let inner = self.inner.read().unwrap();
+        (inner.blocks.keys().next_back() == Some(&number)).then(|| AccountState {
+            account: account.to_string(),
+            balance: inner.balances.get(account).cloned().unwrap_or(0),
+            nonce: inner.account2nonce.get(account).cloned().unwrap_or(0),
+        })
+    }
+}
+
+/// Whether the account sends or receives the tx in the `direction`.
+fn matches_direction(direction: TxDirection, tx: &Tx, account: &str) -> bool {
+    match direction {
+        TxDirection::Sent => tx.from == account,
+        TxDirection::Received => tx.to == account,
+    }
 }
 
 fn fetch_add(map: &mut HashMap<String, u64>, key: String, value: u64) {
@@ -120,3 +280,51 @@ fn fetch_sub(map: &mut HashMap<String, u64>, key: String, value: u64) {
     let entry = map.entry(key).or_insert(0);
     *entry -= value;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::tests::AUTHOR;
+
+    fn new_state(height: u64) -> MemoryState {
+        let state = MemoryState::new(HashMap::new());
+        let mut parent_hash = Hash::default();
+        for number in 0..height {
+            let block = Block::new(parent_hash, number, AUTHOR.to_string(), vec![]);
+            parent_hash = block.hash();
+            state.add_block(block).unwrap();
+        }
+        state
+    }
+
+    fn numbers(blocks: Vec<Block>) -> Vec<u64> {
+        blocks.iter().map(|b| b.number()).collect()
+    }
+
+    #[test]
+    fn get_blocks_with_pagination() {
+        let state = new_state(10);
+
+        assert_eq!(numbers(state.get_blocks(0, None, 3, false)), vec![0, 1, 2]);
+        assert_eq!(numbers(state.get_blocks(8, None, 3, false)), vec![8, 9]);
+        assert_eq!(
+            numbers(state.get_blocks(2, Some(4), 10, false)),
+            vec![2, 3, 4]
+        );
+        assert!(state.get_blocks(5, Some(4), 10, false).is_empty());
+
+        assert_eq!(
+            numbers(state.get_blocks(u64::MAX, None, 3, true)),
+            vec![9, 8, 7]
+        );
+        assert_eq!(
+            numbers(state.get_blocks(4, Some(2), 10, true)),
+            vec![4, 3, 2]
+        );
+        assert!(state.get_blocks(1, Some(2), 10, true).is_empty());
+
+        let block = state.get_block(5).unwrap();
+        assert_eq!(state.get_block_by_hash(&block.hash()), Some(block));
+        assert_eq!(state.get_headers(9, 10).len(), 1);
+    }
+}
@@ -1,5 +1,11 @@
+// The in-memory state is only used by the tests of the biz layer.
+#[cfg(test)]
 mod memory_state;
 mod sled_state;
 
+#[cfg(test)]
 pub use memory_state::*;
 pub use sled_state::*;
+
+#[cfg(test)]
+mod tests;
@@ -1,4 +1,15 @@
-use std::collections::HashMap;
+//! The state stored in sled.
+//!
+//! Besides the latest balances and nonces, the archive and pruned modes keep the history: for
+//! every block, the values of the accounts before the block are recorded, keyed by the block
+//! number and the account. The state after a past block is rebuilt by undoing the later blocks
+//! from the latest state, where the value before the first later change of an account wins.
+//!
+//! The blocks are indexed by their hashes, the receipts of the txs by the tx hashes, and the txs
+//! by the accounts sending or receiving them. The indexes are written in the same transaction as
+//! the block, and rebuilt when the database was created with an older `INDEX_VERSION`.
+
+use std::collections::{HashMap, HashSet};
 
 use sled::{
     self,
@@ -7,32 +18,202 @@ use sled::{
     },
 };
 
-use crate::{biz::State, error::Error, schema::Block, types::Hash};
+use crate::{
+    biz::{block_range, HistoryMode, State, TxDirection, TxLocation, PRUNED_HISTORY_BLOCKS},
+    error::Error,
+    schema::{AccountState, Block, BlockHeader, TxReceipt},
+    types::Hash,
+};
 
 #[derive(Debug, Clone)]
 pub struct SledState {
+    db: sled::Db,
     blocks: sled::Tree,
+    /// Index from block hash to block number.
+    block_hashes: sled::Tree,
+    /// Index from tx hash to its receipt.
+    receipts: sled::Tree,
+    /// Index from the account and the location of a tx it sends or receives to the direction.
+    account_txs: sled::Tree,
     balances: sled::Tree,
     account2nonce: sled::Tree,
+    /// How much history is kept.
+    history: HistoryMode,
+    /// The balances before each block, keyed by the block number and the account.
+    balance_history: sled::Tree,
+    /// The nonces before each block, keyed by the block number and the account.
+    nonce_history: sled::Tree,
 }
 
+/// The key of the earliest block whose state can be rebuilt from the history.
+const HISTORY_FROM_KEY: &[u8] = b"history_from";
+/// The key of the hash of the last block, so that it's not decoded from the block.
+const LAST_BLOCK_HASH_KEY: &[u8] = b"last_block_hash";
+/// The key of the version of the indexes.
+const INDEX_VERSION_KEY: &[u8] = b"index_version";
+/// The version of the indexes, which is bumped when they change so that they are rebuilt.
+const INDEX_VERSION: u64 = 2;
+
+/// The account sends the tx.
+const TX_SENT: u8 = 0b01;
+/// The account receives the tx.
+const TX_RECEIVED: u8 = 0b10;
+
 impl SledState {
     /// Create a new `SledState` instance.
-    pub fn new(data_dir: &str, balances: HashMap<String, u64>) -> Result<Self, Error> {
-        let db = sled::open(data_dir)?;
+    pub fn new(
+        data_dir: &str,
+        balances: HashMap<String, u64>,
+        history: HistoryMode,
+    ) -> Result<Self, Error> {
+        Self::with_db(sled::open(data_dir)?, balances, history)
+    }
+
+    /// Create a `SledState` in a temporary database, which is removed when it's dropped.
+    #[cfg(test)]
+    pub(super) fn temporary(balances: HashMap<String, u64>, history: HistoryMode) -> Self {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        Self::with_db(db, balances, history).unwrap()
+    }
+
+    fn with_db(
+        db: sled::Db,
+        balances: HashMap<String, u64>,
+        history: HistoryMode,
+    ) -> Result<Self, Error> {
         let state = Self {
             blocks: db.open_tree("blocks")?,
+            block_hashes: db.open_tree("block_hashes")?,
+            receipts: db.open_tree("receipts")?,
+            account_txs: db.open_tree("account_txs")?,
             balances: db.open_tree("balances")?,
             account2nonce: db.open_tree("account2nonce")?,
+            history,
+            balance_history: db.open_tree("balance_history")?,
+            nonce_history: db.open_tree("nonce_history")?,
+            db,
         };
 
         if state.balances.is_empty() {
             state.init_balances(balances);
         }
+        let index_version = state.db.get(INDEX_VERSION_KEY)?.map(|v| u64_decode(&v));
+        if index_version != Some(INDEX_VERSION) || state.block_hashes.len() != state.blocks.len() {
+            state.reindex()?;
+        }
+        state.init_history()?;
 
         Ok(state)
     }
 
+    /// Flush all dirty buffers to disk, which should be called before the node exits.
+    pub fn flush(&self) -> Result<(), Error> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Rebuild the indexes, e.g. for a database created before they existed.
+    fn reindex(&self) -> Result<(), Error> {
+        for tree in [&self.block_hashes, &self.receipts, &self.account_txs] {
+            tree.clear()?;
+        }
+        for result in self.blocks.iter() {
+            let (_, block) = result?;
+            let block = Block::try_from(block.to_vec())?;
+            (
+                &self.block_hashes,
+                &self.receipts,
+                &self.account_txs,
+                &*self.db,
+            )
+                .transaction(|(block_hashes, receipts, account_txs, meta)| {
+                    index_block(&block, block_hashes, receipts, account_txs, meta)
+                })
+                .map_err(|e| match e {
+                    TransactionError::Abort(e) => e,
+                    TransactionError::Storage(e) => e.into(),
+                })?;
+        }
+        self.db
+            .insert(INDEX_VERSION_KEY, u64_encode(INDEX_VERSION))?;
+
+        Ok(())
+    }
+
+    /// Drop the history if it is not kept any more, or start it from the last block if it was
+    /// not kept before.
+    fn init_history(&self) -> Result<(), Error> {
+        if self.history == HistoryMode::Latest {
+            self.balance_history.clear()?;
+            self.nonce_history.clear()?;
+            self.db.remove(HISTORY_FROM_KEY)?;
+        } else if !self.db.contains_key(HISTORY_FROM_KEY)? {
+            let from = self.block_height().saturating_sub(1);
+            self.db.insert(HISTORY_FROM_KEY, u64_encode(from))?;
+        }
+
+        Ok(())
+    }
+
+    /// The earliest block whose state is kept, or `None` if there is no block.
+    fn history_from(&self) -> Option<u64> {
+        let last = self.block_height().checked_sub(1)?;
+        let from = self
+            .db
+            .get(HISTORY_FROM_KEY)
+            .unwrap_or_default()
+            .map_or(last, |bytes| u64_decode(&bytes));
+
+        Some(from.min(last))
+    }
+
+    fn is_state_kept(&self, number: u64) -> bool {
+        self.history_from().is_some_and(|from| from <= number) && number < self.block_height()
+    }
+
+    /// Drop the history of the blocks before the recent `PRUNED_HISTORY_BLOCKS` blocks.
+    /// It is done after the block is added, as sled can't remove a range in a transaction.
+    fn prune_history(&self, last_number: u64) -> Result<(), Error> {
+        let Some(from) = last_number.checked_sub(PRUNED_HISTORY_BLOCKS) else {
+            return Ok(());
+        };
+
+        // Move the start first, so that no query relies on the history being dropped.
+        self.db.insert(HISTORY_FROM_KEY, u64_encode(from))?;
+        for tree in [&self.balance_history, &self.nonce_history] {
+            for key in tree.range(..u64_encode(from + 1)).keys() {
+                tree.remove(key?)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Undo the blocks after the `number` from the latest `values` with the `history`.
+    fn undo_history(history: &sled::Tree, number: u64, values: &mut HashMap<String, u64>) {
+        let mut undone = HashSet::new();
+        for result in history.range(u64_encode(number + 1)..) {
+            let (key, value) = result.unwrap();
+            let (_, account) = history_key_decode(&key);
+            if !undone.insert(account.clone()) {
+                continue;
+            }
+            match u64_decode(&value) {
+                0 => values.remove(&account),
+                value => values.insert(account, value),
+            };
+        }
+    }
+
+    /// Undo the blocks after the `number` from the latest `value` of the account.
+    fn undo_account_history(history: &sled::Tree, number: u64, account: &str, value: u64) -> u64 {
+        history
+            .range(u64_encode(number + 1)..)
+            .map(|result| result.unwrap())
+            .find(|(key, _)| history_key_decode(key).1 == account)
+            .map_or(value, |(_, value)| u64_decode(&value))
+    }
+
     fn init_balances(&self, balances: HashMap<String, u64>) {
         for (account, balance) in balances {
             self.balances
@@ -44,7 +225,10 @@ impl SledState {
 
 impl State for SledState {
     fn block_height(&self) -> u64 {
-        self.blocks.len() as u64
+        self.blocks
+            .last()
+            .unwrap_or_default()
+            .map_or(0, |(number, _)| u64_decode(&number) + 1)
     }
 
     fn next_account_nonce(&self, account: &str) -> u64 {
@@ -54,47 +238,167 @@ impl State for SledState {
             .map_or(0, |bytes| u64_decode(&bytes))
     }
 
-    fn last_block_hash(&self) -> Option<Hash> {
+    fn lowest_block_number(&self) -> u64 {
         self.blocks
-            .last()
+            .first()
             .unwrap_or_default()
-            .map(|(_, b)| Block::try_from(b.to_vec()).unwrap().hash())
+            .map_or(0, |(number, _)| u64_decode(&number))
+    }
+
+    fn last_block_hash(&self) -> Option<Hash> {
+        self.db
+            .get(LAST_BLOCK_HASH_KEY)
+            .unwrap_or_default()
+            .map(|hash| Hash::from(hash.to_vec()))
     }
 
     fn add_block(&self, block: Block) -> Result<(), Error> {
-        (&self.blocks, &self.balances, &self.account2nonce)
-            .transaction(|(blocks, balances, account2nonce)| {
-                // Apply txs
-                for tx in &block.txs {
-                    fetch_sub(balances, &tx.from, tx.cost())?;
-                    fetch_add(balances, &tx.to, tx.value)?;
-                    fetch_add(account2nonce, &tx.from, 1)?;
-                }
-
-                // Apply block
-                fetch_add(balances, block.author(), block.block_reward())?;
-                blocks.insert(u64_encode(block.number()), Vec::from(&block))?;
-
-                Ok(())
-            })
+        let number = block.number();
+        let keep_history = self.history != HistoryMode::Latest;
+        (
+            &self.blocks,
+            &self.block_hashes,
+            &self.balances,
+            &self.account2nonce,
+            &self.balance_history,
+            &self.nonce_history,
+            &self.receipts,
+            &self.account_txs,
+            &*self.db,
+        )
+            .transaction(
+                |(
+                    blocks,
+                    block_hashes,
+                    balances,
+                    account2nonce,
+                    balance_history,
+                    nonce_history,
+                    receipts,
+                    account_txs,
+                    meta,
+                )| {
+                    let record = |history: &TransactionalTree, tree, account: &str| {
+                        if keep_history {
+                            record_history(history, tree, number, account)?;
+                        }
+                        Ok::<_, sled::transaction::ConflictableTransactionError<Error>>(())
+                    };
+
+                    // Apply txs
+                    for tx in &block.txs {
+                        record(balance_history, balances, &tx.from)?;
+                        record(balance_history, balances, &tx.to)?;
+                        record(nonce_history, account2nonce, &tx.from)?;
+                        fetch_sub(balances, &tx.from, tx.cost())?;
+                        fetch_add(balances, &tx.to, tx.value)?;
+                        fetch_add(account2nonce, &tx.from, 1)?;
+                    }
+
+                    // Apply block
+                    record(balance_history, balances, block.author())?;
+                    fetch_add(balances, block.author(), block.block_reward())?;
+                    blocks.insert(u64_encode(number), Vec::from(&block))?;
+                    index_block(&block, block_hashes, receipts, account_txs, meta)?;
+
+                    Ok(())
+                },
+            )
             .map_err(|e| match e {
                 TransactionError::Abort(e) => e,
                 _ => Error::AddBlockFailure,
             })?;
 
+        if self.history == HistoryMode::Pruned {
+            self.prune_history(number)?;
+        }
+
         Ok(())
     }
 
-    fn get_blocks(&self, from_number: u64) -> Vec<Block> {
-        let start = u64_encode(from_number);
+    fn import_snapshot(
+        &self,
+        block: Block,
+        balances: HashMap<String, u64>,
+        account2nonce: HashMap<String, u64>,
+    ) -> Result<(), Error> {
+        if !self.blocks.is_empty() {
+            return Err(Error::InvalidSnapshot("the state has blocks".to_string()));
+        }
 
-        self.blocks
-            .range(start..)
-            .map(|result| {
-                let (_, block) = result.unwrap();
-                Block::try_from(block.to_vec()).unwrap()
-            })
-            .collect()
+        // The genesis balances are replaced by the snapshot.
+        let genesis_accounts = self.balances.iter().keys().collect::<Result<Vec<_>, _>>()?;
+        (
+            &self.blocks,
+            &self.block_hashes,
+            &self.balances,
+            &self.account2nonce,
+            &self.receipts,
+            &self.account_txs,
+            &*self.db,
+        )
+            .transaction(
+                |(
+                    blocks,
+                    block_hashes,
+                    balances_tree,
+                    account2nonce_tree,
+                    receipts,
+                    account_txs,
+                    meta,
+                )| {
+                    for account in &genesis_accounts {
+                        balances_tree.remove(account)?;
+                    }
+                    for (account, balance) in &balances {
+                        balances_tree.insert(account.as_bytes(), u64_encode(*balance))?;
+                    }
+                    for (account, nonce) in &account2nonce {
+                        account2nonce_tree.insert(account.as_bytes(), u64_encode(*nonce))?;
+                    }
+
+                    blocks.insert(u64_encode(block.number()), Vec::from(&block))?;
+                    index_block(&block, block_hashes, receipts, account_txs, meta)?;
+
+                    Ok(())
+                },
+            )
+            .map_err(|e| match e {
+                TransactionError::Abort(e) => e,
+                _ => Error::AddBlockFailure,
+            })?;
+
+        // The states before the snapshot are unknown.
+        if self.history != HistoryMode::Latest {
+            self.db
+                .insert(HISTORY_FROM_KEY, u64_encode(block.number()))?;
+        }
+
+        Ok(())
+    }
+
+    fn get_blocks(
+        &self,
+        from_number: u64,
+        to_number: Option<u64>,
+        limit: usize,
+        reverse: bool,
+    ) -> Vec<Block> {
+        let Some((start, end)) = block_range(from_number, to_number, reverse) else {
+            return vec![];
+        };
+
+        let range = self.blocks.range(u64_encode(start)..=u64_encode(end));
+        let decode = |result: sled::Result<(sled::IVec, sled::IVec)>| {
+            let (_, block) = result.unwrap();
+            Block::try_from(block.to_vec()).unwrap()
+        };
+
+        if reverse {
+            range.rev().take(limit).map(decode).collect()
+        } else {
+            range.take(limit).map(decode).collect()
+        }
     }
 
     fn get_block(&self, number: u64) -> Option<Block> {
@@ -104,6 +408,64 @@ impl State for SledState {
             .map(|block| Block::try_from(block.to_vec()).unwrap())
     }
 
+    fn get_block_by_hash(&self, hash: &Hash) -> Option<Block> {
+        self.block_hashes
+            .get(hash.as_slice())
+            .unwrap_or_default()
+            .and_then(|number| self.get_block(u64_decode(&number)))
+    }
+
+    fn get_tx_receipt(&self, hash: &Hash) -> Option<TxReceipt> {
+        self.receipts
+            .get(hash.as_slice())
+            .unwrap_or_default()
+            .map(|receipt| TxReceipt::try_from(receipt.to_vec()).unwrap())
+    }
+
+    fn get_account_tx_locations(
+        &self,
+        account: &str,
+        direction: Option<TxDirection>,
+        from: TxLocation,
+        to_number: Option<u64>,
+        limit: usize,
+    ) -> Vec<TxLocation> {
+        let prefix = account_txs_prefix(account);
+        let start = account_txs_key(account, from);
+        let mask = match direction {
+            None => TX_SENT | TX_RECEIVED,
+            Some(TxDirection::Sent) => TX_SENT,
+            Some(TxDirection::Received) => TX_RECEIVED,
+        };
+
+        self.account_txs
+            .range(start..)
+            .map(|result| result.unwrap())
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .filter(|(_, directions)| directions[0] & mask != 0)
+            .map(|(key, _)| tx_location_decode(&key[prefix.len()..]))
+            .take_while(|location| to_number.is_none_or(|to| location.number <= to))
+            .take(limit)
+            .collect()
+    }
+
+    fn get_headers(&self, from_number: u64, count: usize) -> Vec<BlockHeader> {
+        let start = u64_encode(from_number);
+
+        // The blocks before a snapshot are not kept, so the range may start after `from_number`.
+        self.blocks
+            .range(start..)
+            .take(count)
+            .map(|result| {
+                let (_, block) = result.unwrap();
+                Block::try_from(block.to_vec()).unwrap().header.unwrap()
+            })
+            .enumerate()
+            .take_while(|(i, header)| header.number == from_number + *i as u64)
+            .map(|(_, header)| header)
+            .collect()
+    }
+
     fn get_balance(&self, account: &str) -> u64 {
         self.balances
             .get(account)
@@ -136,6 +498,127 @@ impl State for SledState {
             })
             .collect()
     }
+
+    // The latest state is read before the history, so that a block added in the meantime is
+    // undone as well, whose recorded values are the ones read.
+    fn get_balances_at(&self, number: u64) -> Option<HashMap<String, u64>> {
+        if !self.is_state_kept(number) {
+            return None;
+        }
+
+        let mut balances = self.get_balances();
+        Self::undo_history(&self.balance_history, number, &mut balances);
+        Some(balances)
+    }
+
+    fn get_account_at(&self, account: &str, number: u64) -> Option<AccountState> {
+        if !self.is_state_kept(number) {
+            return None;
+        }
+
+        let balance = self.get_balance(account);
+        let nonce = self.next_account_nonce(account);
+        Some(AccountState {
+            account: account.to_string(),
+            balance: Self::undo_account_history(&self.balance_history, number, account, balance),
+            nonce: Self::undo_account_history(&self.nonce_history, number, account, nonce),
+        })
+    }
+}
+
+/// Index the block by its hash, and its txs by their hashes and accounts.
+fn index_block(
+    block: &Block,
+    block_hashes: &TransactionalTree,
+    receipts: &TransactionalTree,
+    account_txs: &TransactionalTree,
+    meta: &TransactionalTree,
+) -> ConflictableTransactionResult<(), Error> {
+    let hash = block.hash();
+    let number = block.number();
+    block_hashes.insert(hash.to_vec(), u64_encode(number))?;
+    meta.insert(LAST_BLOCK_HASH_KEY, hash.to_vec())?;
+
+    for (index, tx) in block.txs.iter().enumerate() {
+        let location = TxLocation {
+            number,
+            index: index as u32,
+        };
+        let receipt = TxReceipt::new(tx, hash, number, location.index);
+        receipts.insert(tx.hash().to_vec(), Vec::from(&receipt))?;
+
+        // A tx sent to the sender itself is indexed once, in both directions.
+        if tx.from == tx.to {
+            account_txs.insert(
+                account_txs_key(&tx.from, location),
+                vec![TX_SENT | TX_RECEIVED],
+            )?;
+        } else {
+            account_txs.insert(account_txs_key(&tx.from, location), vec![TX_SENT])?;
+            account_txs.insert(account_txs_key(&tx.to, location), vec![TX_RECEIVED])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Record the value of the account before the block, unless it is already changed in the block.
+fn record_history(
+    history: &TransactionalTree,
+    tree: &TransactionalTree,
+    number: u64,
+    account: &str,
+) -> ConflictableTransactionResult<(), Error> {
+    let key = history_key_encode(number, account);
+    if history.get(&key)?.is_none() {
+        let old = get_u64(tree, account.as_bytes()).unwrap_or_default();
+        history.insert(key, u64_encode(old))?;
+    }
+
+    Ok(())
+}
+
+fn history_key_encode(number: u64, account: &str) -> Vec<u8> {
+    let mut key = u64_encode(number);
+    key.extend_from_slice(account.as_bytes());
+    key
+}
+
+fn history_key_decode(key: &[u8]) -> (u64, String) {
+    let (number, account) = key.split_at(8);
+    (
+        u64_decode(number),
+        String::from_utf8(account.to_vec()).unwrap(),
+    )
+}
+
+/// The account is followed by a zero byte, so that no account is a prefix of another's keys.
+fn account_txs_prefix(account: &str) -> Vec<u8> {
+    let mut prefix = account.as_bytes().to_vec();
+    prefix.push(0);
+    prefix
+}
+
+fn account_txs_key(account: &str, location: TxLocation) -> Vec<u8> {
+    let mut key = account_txs_prefix(account);
+    key.extend(tx_location_encode(location));
+    key
+}
+
+fn tx_location_encode(location: TxLocation) -> Vec<u8> {
+    let mut bytes = u64_encode(location.number);
+    bytes.extend_from_slice(&location.index.to_be_bytes());
+    bytes
+}
+
+fn tx_location_decode(bytes: &[u8]) -> TxLocation {
+    let (number, index) = bytes.split_at(8);
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(index);
+    TxLocation {
+        number: u64_decode(number),
+        index: u32::from_be_bytes(buf),
+    }
 }
 
 fn fetch_add(
@@ -181,3 +664,150 @@ fn u64_decode(bytes: &[u8]) -> u64 {
 fn u64_encode(n: u64) -> Vec<u8> {
     n.to_be_bytes().to_vec()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data::tests::{ALICE, AUTHOR, BOB},
+        schema::{SignedTx, Tx},
+    };
+
+    /// A state where Alice sends 100 to Bob in each of the `len` blocks, which costs her 121.
+    fn new_state(history: HistoryMode, len: u64) -> SledState {
+        let balances = HashMap::from([(ALICE.to_string(), 1_000_000)]);
+        let state = SledState::temporary(balances, history);
+
+        let mut parent_hash = Hash::default();
+        for number in 0..len {
+            let tx = SignedTx {
+                tx: Some(Tx::new(ALICE, BOB, 100, number)),
+                sig: vec![0u8; 65],
+            };
+            let block = Block::new(parent_hash, number, AUTHOR.to_string(), vec![tx]);
+            parent_hash = block.hash();
+            state.add_block(block).unwrap();
+        }
+        state
+    }
+
+    fn expected_balances(number: u64) -> HashMap<String, u64> {
+        let count = number + 1;
+        HashMap::from([
+            (ALICE.to_string(), 1_000_000 - 121 * count),
+            (BOB.to_string(), 100 * count),
+            (AUTHOR.to_string(), 21 * count),
+        ])
+    }
+
+    #[test]
+    fn archive_keeps_all_the_history() {
+        let state = new_state(HistoryMode::Archive, 3);
+
+        for number in 0..3 {
+            assert_eq!(
+                state.get_balances_at(number),
+                Some(expected_balances(number))
+            );
+            let alice = state.get_account_at(ALICE, number).unwrap();
+            assert_eq!(alice.balance, expected_balances(number)[ALICE]);
+            assert_eq!(alice.nonce, number + 1);
+        }
+        assert_eq!(state.get_account_at(BOB, 0).unwrap().nonce, 0);
+        assert_eq!(state.get_account_at("0x0000cccc", 0).unwrap().balance, 0);
+        assert!(state.get_balances_at(3).is_none());
+    }
+
+    #[test]
+    fn blocks_and_txs_are_indexed() {
+        let state = new_state(HistoryMode::Latest, 3);
+        let last = state.get_block(2).unwrap();
+        assert_eq!(state.last_block_hash(), Some(last.hash()));
+        assert_eq!(state.get_block_by_hash(&last.hash()), Some(last.clone()));
+
+        let location = TxLocation {
+            number: 2,
+            index: 0,
+        };
+        let receipt = state.get_tx_receipt(&last.txs[0].hash()).unwrap();
+        assert_eq!(receipt.block_hash, last.hash().to_vec());
+        assert_eq!((receipt.block_number, receipt.index), (2, 0));
+        assert_eq!(receipt.fee, 21);
+        let from = TxLocation {
+            number: 1,
+            index: 0,
+        };
+        let locations = state.get_account_tx_locations(BOB, None, from, None, 10);
+        assert_eq!(locations, vec![from, location]);
+        assert_eq!(
+            state.get_account_tx_locations(ALICE, None, from, None, 1),
+            vec![from]
+        );
+        let first = TxLocation {
+            number: 0,
+            index: 0,
+        };
+        let sent = Some(TxDirection::Sent);
+        assert!(state
+            .get_account_tx_locations(BOB, sent, first, None, 10)
+            .is_empty());
+        assert_eq!(
+            state.get_account_tx_locations(ALICE, sent, first, Some(1), 10),
+            vec![first, from]
+        );
+        // An account whose address is a prefix of another one's has no txs.
+        assert!(state
+            .get_account_tx_locations(&ALICE[..8], None, from, None, 10)
+            .is_empty());
+
+        // The indexes of an older database are rebuilt.
+        state.account_txs.clear().unwrap();
+        state.db.remove(INDEX_VERSION_KEY).unwrap();
+        let state =
+            SledState::with_db(state.db.clone(), HashMap::new(), HistoryMode::Latest).unwrap();
+        assert_eq!(
+            state
+                .get_account_tx_locations(BOB, None, from, None, 10)
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn imported_snapshot_is_the_lowest_block() {
+        let chain = new_state(HistoryMode::Latest, 5);
+        let snapshot_block = chain.get_block(3).unwrap();
+        assert_eq!(chain.lowest_block_number(), 0);
+
+        let state = SledState::temporary(HashMap::new(), HistoryMode::Latest);
+        state
+            .import_snapshot(snapshot_block, expected_balances(3), HashMap::new())
+            .unwrap();
+        state.add_block(chain.get_block(4).unwrap()).unwrap();
+
+        assert_eq!(state.lowest_block_number(), 3);
+        assert!(state.get_headers(0, 10).is_empty());
+        let numbers: Vec<u64> = state.get_headers(3, 10).iter().map(|h| h.number).collect();
+        assert_eq!(numbers, vec![3, 4]);
+    }
+
+    #[test]
+    fn pruned_and_latest_keep_recent_history() {
+        let len = PRUNED_HISTORY_BLOCKS + 3;
+        let state = new_state(HistoryMode::Pruned, len);
+        let from = len - 1 - PRUNED_HISTORY_BLOCKS;
+        assert!(state.get_balances_at(from - 1).is_none());
+        assert_eq!(state.get_balances_at(from), Some(expected_balances(from)));
+        assert_eq!(
+            state.get_account_at(BOB, len - 2).unwrap().balance,
+            expected_balances(len - 2)[BOB]
+        );
+        // Only the recent blocks are recorded.
+        assert!(state.balance_history.len() <= 3 * PRUNED_HISTORY_BLOCKS as usize);
+
+        let state = new_state(HistoryMode::Latest, 3);
+        assert!(state.get_balances_at(1).is_none());
+        assert_eq!(state.get_balances_at(2), Some(expected_balances(2)));
+        assert!(state.balance_history.is_empty());
+    }
+}
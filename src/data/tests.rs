@@ -0,0 +1,101 @@
+//! The fixtures shared by the tests of the states, and the tests of what both states index.
+
+use std::collections::HashMap;
+
+use super::{MemoryState, SledState};
+use crate::{
+    biz::{HistoryMode, State, TxDirection, TxLocation},
+    schema::{Block, SignedTx, Tx},
+    types::Hash,
+};
+
+pub(super) const ALICE: &str = "0x0000aaaa";
+pub(super) const BOB: &str = "0x0000bbbb";
+pub(super) const AUTHOR: &str = "0x01234567";
+
+/// Add `height` blocks to the empty `state`, where in each block, Alice sends 100 to Bob and Bob
+/// sends 10 back to her.
+fn add_blocks_with_txs<S: State>(state: &S, height: u64) {
+    let mut parent_hash = Hash::default();
+    for number in 0..height {
+        let txs = vec![
+            SignedTx {
+                tx: Some(Tx::new(ALICE, BOB, 100, number)),
+                sig: vec![0u8; 65],
+            },
+            SignedTx {
+                tx: Some(Tx::new(BOB, ALICE, 10, number)),
+                sig: vec![0u8; 65],
+            },
+        ];
+        let block = Block::new(parent_hash, number, AUTHOR.to_string(), txs);
+        parent_hash = block.hash();
+        state.add_block(block).unwrap();
+    }
+}
+
+fn balances() -> HashMap<String, u64> {
+    HashMap::from([(ALICE.to_string(), 1_000_000), (BOB.to_string(), 1_000_000)])
+}
+
+fn check_tx_receipts<S: State>(state: S) {
+    add_blocks_with_txs(&state, 3);
+
+    let block = state.get_block(1).unwrap();
+    for (index, tx) in block.txs.iter().enumerate() {
+        let receipt = state.get_tx_receipt(&tx.hash()).unwrap();
+        assert_eq!(receipt.block_hash, block.hash().to_vec());
+        assert_eq!((receipt.block_number, receipt.index), (1, index as u32));
+        assert_eq!(receipt.fee, 21);
+    }
+    assert!(state.get_tx_receipt(&Hash::default()).is_none());
+}
+
+fn check_account_tx_locations<S: State>(state: S) {
+    add_blocks_with_txs(&state, 3);
+
+    let at = |number, index| TxLocation { number, index };
+    let get = |account, direction, from, to_number, limit| {
+        state.get_account_tx_locations(account, direction, from, to_number, limit)
+    };
+    let sent = Some(TxDirection::Sent);
+    let received = Some(TxDirection::Received);
+
+    let all = vec![at(0, 0), at(0, 1), at(1, 0), at(1, 1), at(2, 0), at(2, 1)];
+    assert_eq!(get(ALICE, None, at(0, 0), None, 10), all);
+    assert_eq!(
+        get(ALICE, sent, at(0, 0), None, 10),
+        vec![at(0, 0), at(1, 0), at(2, 0)]
+    );
+    assert_eq!(
+        get(ALICE, received, at(0, 0), None, 10),
+        vec![at(0, 1), at(1, 1), at(2, 1)]
+    );
+
+    // The cursor is inclusive, and may point into the middle of a block.
+    assert_eq!(get(ALICE, None, at(1, 1), None, 10), all[3..]);
+    assert_eq!(
+        get(BOB, received, at(0, 1), None, 10),
+        vec![at(1, 0), at(2, 0)]
+    );
+
+    // The `to_number` is inclusive, and the limit counts the txs in the direction only.
+    assert_eq!(get(ALICE, None, at(0, 0), Some(1), 10), all[..4]);
+    assert_eq!(get(BOB, sent, at(0, 0), Some(1), 1), vec![at(0, 1)]);
+    assert!(get(ALICE, None, at(2, 0), Some(1), 10).is_empty());
+
+    // The rewards of the author are not txs.
+    assert!(get(AUTHOR, None, at(0, 0), None, 10).is_empty());
+}
+
+#[test]
+fn tx_receipts_are_indexed() {
+    check_tx_receipts(MemoryState::new(balances()));
+    check_tx_receipts(SledState::temporary(balances(), HistoryMode::Latest));
+}
+
+#[test]
+fn account_tx_locations_are_filtered_and_bounded() {
+    check_account_tx_locations(MemoryState::new(balances()));
+    check_account_tx_locations(SledState::temporary(balances(), HistoryMode::Latest));
+}
@@ -16,16 +16,48 @@ pub enum Error {
     BadRequest(#[from] serde_json::Error),
     #[error("Failed to decode hash from hex")]
     InvalidHex(#[from] hex::FromHexError),
+    #[error("Invalid tx: {0}")]
+    InvalidTx(String),
     #[error("Invalid tx nonce from '{0}': expected '{1}', got '{2}'")]
     InvalidTxNonce(String, u64, u64),
     #[error("Balance of '{0}' is insufficient: balance '{1}', cost '{2}'")]
     BalanceInsufficient(String, u64, u64),
+    #[error("The miner is not running")]
+    MinerUnavailable,
     #[error("Invalid block number: expected '{0}', not '{1}'")]
     InvalidBlockNumber(u64, u64),
     #[error("Invalid block parent: expected '{0}', not '{1}'")]
     InvalidBlockParent(Hash, Hash),
     #[error("Block hash '{0}' donot meet the mining difficulty '{1}'")]
     InvalidBlockHash(Hash, usize),
+    #[error("Txs of block '{0}' donot match its txs hash")]
+    InvalidBlockTxsHash(Hash),
+    #[error("Invalid state root of block '{0}': expected '{1}', not '{2}'")]
+    InvalidBlockStateRoot(Hash, Hash, Hash),
+    #[error("Invalid header of block {0}: {1}")]
+    InvalidBlockHeader(u64, String),
+    #[error("Block '{0}' is not found")]
+    UnknownBlock(String),
+    #[error("Tx '{0}' is not found")]
+    UnknownTx(Hash),
+    #[error("State of block {0} is not kept, see the history mode")]
+    HistoryUnavailable(u64),
+    #[error("Body of block '{0}' is missing")]
+    MissingBlockBody(Hash),
+    #[error("Block {0} conflicts with the checkpoint: expected '{1}', not '{2}'")]
+    CheckpointMismatch(u64, Hash, Hash),
+    #[error("Invalid peer status: {0}")]
+    InvalidPeerStatus(String),
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("Invalid compact block: {0}")]
+    InvalidCompactBlock(String),
+    #[error("Invalid state snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error("Invalid account proof: {0}")]
+    InvalidAccountProof(String),
+    #[error("No peer can prove the state of account '{0}'")]
+    AccountProofUnavailable(String),
     #[error(transparent)]
     InvalidReqResp(#[from] prost::DecodeError),
     #[error("Failed to access db")]
@@ -1,8 +1,8 @@
 use clap::{Parser, Subcommand};
-use config::Config;
-use log::info;
+use config::{Config, NodeMode};
+use log::{error, info};
 
-use tokio::task;
+use tokio::{signal, sync::oneshot, task};
 use wallet::{self, Wallet};
 
 mod biz;
@@ -67,26 +67,128 @@ fn new_secret_key() {
 }
 
 async fn run(config_file: &str) {
-    // Load config.
+    let config = Config::load(config_file).unwrap();
+    match config.mode {
+        NodeMode::Full => run_full(config).await,
+        NodeMode::Light => run_light(config).await,
+    }
+}
+
+async fn run_full(config: Config) {
     let Config {
         data_dir,
         genesis_file,
         http_addr,
         author,
+        history,
+        snapshot_sync,
         p2p: p2p_config,
         wallet,
-    } = Config::load(config_file).unwrap();
+        ..
+    } = config;
     let http_addr = http_addr.parse().unwrap();
     let genesis = Genesis::load(&genesis_file).unwrap();
     info!("📣 Genesis: {:?}", genesis);
+    let chain_spec = genesis.chain_spec();
 
     let wallet = Wallet::new(&wallet.keystore_dir);
-    let sled_state = SledState::new(&data_dir, genesis.into_balances()).unwrap();
+    let sled_state = SledState::new(&data_dir, genesis.into_balances(), history).unwrap();
     let (p2p_client, mut p2p_server) = p2p::new(p2p_config).unwrap();
-    let node = biz::new_node(author, sled_state, p2p_client, wallet);
+
+    // Closing this channel tells the background workers to stop.
+    let (shutdown_sender, shutdown_receiver) = crossbeam_channel::bounded::<()>(0);
+    let (node, workers) = biz::new_node(
+        author,
+        sled_state.clone(),
+        p2p_client,
+        wallet,
+        chain_spec,
+        snapshot_sync,
+        shutdown_receiver,
+    );
     let event_handler = p2p::EventHandlerImpl::new(node.clone());
     p2p_server.set_event_handler(event_handler);
 
-    task::spawn(p2p_server.run());
-    http::run(http_addr, node).await;
+    let (p2p_shutdown_sender, p2p_shutdown_receiver) = oneshot::channel::<()>();
+    let p2p_task = task::spawn(p2p_server.run_with_shutdown(async {
+        let _ = p2p_shutdown_receiver.await;
+    }));
+    http::run(http_addr, node, shutdown_signal()).await;
+
+    // The HTTP server has stopped accepting requests, now stop the background workers
+    // and the p2p server. The p2p server is stopped first to unblock the pending requests
+    // of the workers, and the state is flushed after the workers finish writing.
+    drop(shutdown_sender);
+    let _ = p2p_shutdown_sender.send(());
+    let _ = p2p_task.await;
+    join_workers(workers).await;
+
+    match sled_state.flush() {
+        Ok(()) => info!("📣 Node stopped"),
+        Err(err) => error!("❌ Failed to flush the state: {:?}", err),
+    }
+}
+
+// A light node has neither a state nor a wallet, it only syncs the headers.
+async fn run_light(config: Config) {
+    let Config {
+        genesis_file,
+        http_addr,
+        p2p: p2p_config,
+        ..
+    } = config;
+    let http_addr = http_addr.parse().unwrap();
+    let genesis = Genesis::load(&genesis_file).unwrap();
+    info!("📣 Genesis: {:?}", genesis);
+
+    let (p2p_client, mut p2p_server) = p2p::new(p2p_config).unwrap();
+    let (shutdown_sender, shutdown_receiver) = crossbeam_channel::bounded::<()>(0);
+    let (node, workers) = biz::new_light_node(p2p_client, genesis.chain_spec(), shutdown_receiver);
+    p2p_server.set_event_handler(p2p::LightEventHandler::new(node.clone()));
+
+    let (p2p_shutdown_sender, p2p_shutdown_receiver) = oneshot::channel::<()>();
+    let p2p_task = task::spawn(p2p_server.run_with_shutdown(async {
+        let _ = p2p_shutdown_receiver.await;
+    }));
+    http::run_light(http_addr, node, shutdown_signal()).await;
+
+    drop(shutdown_sender);
+    let _ = p2p_shutdown_sender.send(());
+    let _ = p2p_task.await;
+    join_workers(workers).await;
+    info!("📣 Light node stopped");
+}
+
+async fn join_workers(workers: Vec<std::thread::JoinHandle<()>>) {
+    let _ = task::spawn_blocking(move || {
+        for worker in workers {
+            let _ = worker.join();
+        }
+    })
+    .await;
+}
+
+// Completes when the process receives SIGINT (Ctrl-C) or SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Failed to install the Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("📣 Shutdown signal received, stopping the node");
 }
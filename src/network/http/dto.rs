@@ -1,70 +1,16 @@
-//! DTO (Data Transfer Object) for HTTP requests and responses.
+//! Conversions between the DTOs and the types of the node.
 //!
-//! We use JSON as the DTO format for better readability.
+//! The DTOs themselves are shared with the clients, see `client::dto`.
 
-use serde::{Deserialize, Serialize};
+pub use client::dto::*;
 
 use crate::{
+    biz::{AccountDetail, ProvenAccount, TxLookup},
+    error::Error,
     schema,
     types::{Hash, Signature},
 };
 
-#[derive(Debug, Serialize)]
-pub struct Tx {
-    pub from: String,
-    pub to: String,
-    pub value: u64,
-    pub nonce: u64,
-    pub gas: u64,
-    pub gas_price: u64,
-    pub timestamp: u64,
-}
-
-#[derive(Debug, Serialize)]
-pub struct SignedTx {
-    pub tx: Tx,
-    pub sig: Signature,
-}
-
-#[derive(Debug, Serialize)]
-pub struct BlockHeader {
-    pub parent_hash: Hash,
-    pub number: u64,
-    pub nonce: u64,
-    pub timestamp: u64,
-    pub author: String,
-}
-
-#[derive(Debug, Serialize)]
-pub struct Block {
-    pub header: BlockHeader,
-    pub txs: Vec<SignedTx>,
-}
-
-#[derive(Debug, Serialize)]
-pub struct BlockResp {
-    pub hash: Hash,
-    pub block: Block,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct GetBlocksReq {
-    pub from_number: u64,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct NonceReq {
-    pub account: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct TxReq {
-    pub from: String,
-    pub to: String,
-    pub value: u64,
-    pub nonce: u64,
-}
-
 impl From<schema::Tx> for Tx {
     fn from(tx: schema::Tx) -> Self {
         Self {
@@ -79,6 +25,38 @@ impl From<schema::Tx> for Tx {
     }
 }
 
+impl From<Tx> for schema::Tx {
+    fn from(tx: Tx) -> Self {
+        Self {
+            from: tx.from,
+            to: tx.to,
+            value: tx.value,
+            nonce: tx.nonce,
+            gas: tx.gas,
+            gas_price: tx.gas_price,
+            timestamp: tx.timestamp,
+        }
+    }
+}
+
+impl TryFrom<SubmitTxReq> for schema::SignedTx {
+    type Error = Error;
+
+    fn try_from(req: SubmitTxReq) -> Result<Self, Self::Error> {
+        match req {
+            SubmitTxReq::Raw { raw } => {
+                let bytes = hex::decode(raw.strip_prefix("0x").unwrap_or(&raw))?;
+                schema::SignedTx::try_from(bytes)
+                    .map_err(|err| Error::InvalidTx(format!("failed to decode: {err}")))
+            }
+            SubmitTxReq::Json(tx) => Ok(Self {
+                tx: Some(tx.tx.into()),
+                sig: tx.sig.into(),
+            }),
+        }
+    }
+}
+
 impl From<schema::SignedTx> for SignedTx {
     fn from(tx: schema::SignedTx) -> Self {
         Self {
@@ -96,6 +74,8 @@ impl From<schema::BlockHeader> for BlockHeader {
             nonce: header.nonce,
             timestamp: header.timestamp,
             author: header.author,
+            txs_hash: Hash::from(header.txs_hash),
+            state_root: Hash::from(header.state_root),
         }
     }
 }
@@ -111,3 +91,143 @@ impl From<schema::Block> for BlockResp {
         Self { hash, block }
     }
 }
+
+impl From<schema::BlockHeader> for HeaderResp {
+    fn from(header: schema::BlockHeader) -> Self {
+        Self {
+            hash: header.hash(),
+            header: header.into(),
+        }
+    }
+}
+
+impl From<TxLookup> for TxResp {
+    fn from(lookup: TxLookup) -> Self {
+        match lookup {
+            TxLookup::Pending(tx) => Self {
+                hash: tx.hash(),
+                status: TxStatus::Pending,
+                tx: tx.into(),
+                block_number: None,
+                block_hash: None,
+                index: None,
+                confirmations: 0,
+                fee: None,
+            },
+            TxLookup::Included {
+                tx,
+                receipt,
+                confirmations,
+            } => Self {
+                hash: tx.hash(),
+                status: TxStatus::Included,
+                tx: tx.into(),
+                block_number: Some(receipt.block_number),
+                block_hash: Some(Hash::from(receipt.block_hash)),
+                index: Some(receipt.index),
+                confirmations,
+                fee: Some(receipt.fee),
+            },
+        }
+    }
+}
+
+/// Encode a number as a quantity of the Ethereum JSON-RPC, e.g. `0x1a`.
+pub fn quantity(n: u64) -> String {
+    format!("{n:#x}")
+}
+
+impl From<TxLookup> for EthTx {
+    fn from(lookup: TxLookup) -> Self {
+        let (tx, receipt) = match lookup {
+            TxLookup::Pending(tx) => (tx, None),
+            TxLookup::Included { tx, receipt, .. } => (tx, Some(receipt)),
+        };
+
+        Self {
+            hash: tx.hash(),
+            from: tx.from.clone(),
+            to: tx.to.clone(),
+            value: quantity(tx.value),
+            nonce: quantity(tx.nonce),
+            gas: quantity(tx.gas),
+            gas_price: quantity(tx.gas_price),
+            block_hash: receipt
+                .as_ref()
+                .map(|receipt| Hash::from(receipt.block_hash.clone())),
+            block_number: receipt
+                .as_ref()
+                .map(|receipt| quantity(receipt.block_number)),
+            transaction_index: receipt.map(|receipt| quantity(receipt.index as u64)),
+        }
+    }
+}
+
+/// Convert a block, with the full txs or only their hashes.
+pub fn eth_block(block: schema::Block, full_txs: bool) -> EthBlock {
+    let hash = block.hash();
+    let transactions = if full_txs {
+        let txs = block.txs.iter().enumerate().map(|(index, tx)| {
+            let receipt = schema::TxReceipt::new(tx, hash, block.number(), index as u32);
+            // The confirmations are not part of the format.
+            EthTx::from(TxLookup::Included {
+                tx: tx.clone(),
+                receipt,
+                confirmations: 0,
+            })
+        });
+        EthBlockTxs::Full(txs.collect())
+    } else {
+        EthBlockTxs::Hashes(block.txs.iter().map(|tx| tx.hash()).collect())
+    };
+    let header = block.header.unwrap();
+
+    EthBlock {
+        number: quantity(header.number),
+        hash,
+        parent_hash: Hash::from(header.parent_hash),
+        nonce: quantity(header.nonce),
+        timestamp: quantity(header.timestamp),
+        miner: header.author,
+        transactions_root: Hash::from(header.txs_hash),
+        state_root: Hash::from(header.state_root),
+        transactions,
+    }
+}
+
+/// Convert the state of an account after the block of the `header`.
+pub fn account_resp(
+    state: schema::AccountState,
+    header: Option<schema::BlockHeader>,
+) -> AccountResp {
+    AccountResp {
+        account: state.account,
+        balance: state.balance,
+        nonce: state.nonce,
+        block_number: header.as_ref().map(|header| header.number),
+        block_hash: header.as_ref().map(|header| header.hash()),
+    }
+}
+
+impl From<AccountDetail> for AccountDetailResp {
+    fn from(detail: AccountDetail) -> Self {
+        Self {
+            account: detail.confirmed.account,
+            balance: detail.confirmed.balance,
+            nonce: detail.confirmed.nonce,
+            pending_balance: detail.pending.balance,
+            pending_nonce: detail.pending.nonce,
+            pending_txs: detail
+                .pending_txs
+                .into_iter()
+                .map(|tx| TxLookup::Pending(tx).into())
+                .collect(),
+        }
+    }
+}
+
+impl From<ProvenAccount> for AccountResp {
+    fn from(proven: ProvenAccount) -> Self {
+        account_resp(proven.state, Some(proven.header))
+    }
+}
@@ -0,0 +1,343 @@
+//! The errors of the HTTP API, which are responded as `ErrorResp`s with a stable `code`.
+//!
+//! Both the errors of the node and the rejections of the extractors are converted into them, so
+//! that the clients never see the plain-text rejections of axum.
+
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use log::error;
+use serde_json::{json, Value};
+use wallet::WalletError;
+
+use super::dto::{ErrorCode, ErrorResp};
+use crate::error::Error;
+
+#[derive(Debug)]
+pub(super) struct HttpError {
+    status: StatusCode,
+    code: ErrorCode,
+    message: String,
+    details: Option<Value>,
+}
+
+impl HttpError {
+    fn new(status: StatusCode, code: ErrorCode, message: String) -> Self {
+        Self {
+            status,
+            code,
+            message,
+            details: None,
+        }
+    }
+
+    fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// There is no such endpoint.
+    pub(super) fn not_found() -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::NotFound,
+            "Not Found".to_string(),
+        )
+    }
+}
+
+impl From<Error> for HttpError {
+    fn from(err: Error) -> Self {
+        let message = err.to_string();
+        match err {
+            Error::BadRequest(..) | Error::InvalidHex(..) => {
+                Self::new(StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest, message)
+            }
+            Error::UnknownBlock(block) => {
+                Self::new(StatusCode::NOT_FOUND, ErrorCode::UnknownBlock, message)
+                    .with_details(json!({ "block": block }))
+            }
+            Error::UnknownTx(hash) => {
+                Self::new(StatusCode::NOT_FOUND, ErrorCode::UnknownTx, message)
+                    .with_details(json!({ "hash": hash }))
+            }
+            Error::WalletFailure(WalletError::AccountNotFound(account)) => {
+                Self::new(StatusCode::NOT_FOUND, ErrorCode::UnknownAccount, message)
+                    .with_details(json!({ "account": account }))
+            }
+            Error::HistoryUnavailable(number) => Self::new(
+                StatusCode::NOT_FOUND,
+                ErrorCode::HistoryUnavailable,
+                message,
+            )
+            .with_details(json!({ "number": number })),
+            Error::InvalidTxNonce(account, expected, got) => {
+                Self::new(StatusCode::CONFLICT, ErrorCode::InvalidTxNonce, message)
+                    .with_details(json!({ "account": account, "expected": expected, "got": got }))
+            }
+            Error::InvalidTx(..) => Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::InvalidTx,
+                message,
+            ),
+            Error::BalanceInsufficient(account, balance, cost) => Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::InsufficientBalance,
+                message,
+            )
+            .with_details(json!({ "account": account, "balance": balance, "cost": cost })),
+            Error::MinerUnavailable => Self::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::MinerUnavailable,
+                message,
+            ),
+            Error::AccountProofUnavailable(account) => Self::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::ProofUnavailable,
+                message,
+            )
+            .with_details(json!({ "account": account })),
+            // The failures of the node or its peers, which are not caused by the request.
+            Error::ConfigNotExist(..)
+            | Error::InvalidConfig(..)
+            | Error::GenesisNotExist(..)
+            | Error::InvalidGenesis
+            | Error::InvalidHttpAddr(..)
+            | Error::InvalidBlockNumber(..)
+            | Error::InvalidBlockParent(..)
+            | Error::InvalidBlockHash(..)
+            | Error::InvalidBlockTxsHash(..)
+            | Error::InvalidBlockStateRoot(..)
+            | Error::InvalidBlockHeader(..)
+            | Error::MissingBlockBody(..)
+            | Error::CheckpointMismatch(..)
+            | Error::InvalidPeerStatus(..)
+            | Error::InvalidResponse(..)
+            | Error::InvalidCompactBlock(..)
+            | Error::InvalidSnapshot(..)
+            | Error::InvalidAccountProof(..)
+            | Error::InvalidReqResp(..)
+            | Error::DbFailure(..)
+            | Error::AddBlockFailure
+            | Error::WalletFailure(..)
+            | Error::P2pFailure(..) => {
+                error!("❌ Failed to handle the request: {:?}", err);
+                Self::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorCode::Internal,
+                    message,
+                )
+            }
+        }
+    }
+}
+
+impl From<QueryRejection> for HttpError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(
+            rejection.status(),
+            ErrorCode::InvalidQuery,
+            rejection.body_text(),
+        )
+    }
+}
+
+impl From<PathRejection> for HttpError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(
+            rejection.status(),
+            ErrorCode::InvalidPath,
+            rejection.body_text(),
+        )
+    }
+}
+
+impl From<JsonRejection> for HttpError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(
+            rejection.status(),
+            ErrorCode::InvalidBody,
+            rejection.body_text(),
+        )
+    }
+}
+
+impl IntoResponse for HttpError {
+    fn into_response(self) -> Response {
+        let body = Json(ErrorResp {
+            code: self.code,
+            message: self.message,
+            details: self.details,
+        });
+
+        (self.status, body).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{Body, HttpBody},
+        extract::{FromRequest, FromRequestParts},
+        http::{header, Request},
+    };
+    use client::dto::{GetBlocksReq, TxReq};
+
+    use super::{super::extract, *};
+    use crate::types::Hash;
+
+    fn status_and_code(err: Error) -> (u16, ErrorCode) {
+        let err = HttpError::from(err);
+        (err.status.as_u16(), err.code)
+    }
+
+    /// The status and the body of the response of the error.
+    async fn respond(err: HttpError) -> (u16, ErrorResp) {
+        let resp = err.into_response();
+        let status = resp.status().as_u16();
+        let mut body = resp.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[test]
+    fn errors_of_the_reqs_are_mapped() {
+        let bad_json = serde_json::from_str::<u64>("x").unwrap_err();
+        let cases = [
+            (Error::BadRequest(bad_json), 400, ErrorCode::InvalidRequest),
+            (
+                Error::InvalidHex(hex::FromHexError::OddLength),
+                400,
+                ErrorCode::InvalidRequest,
+            ),
+            (
+                Error::UnknownBlock("1".to_string()),
+                404,
+                ErrorCode::UnknownBlock,
+            ),
+            (Error::UnknownTx(Hash::default()), 404, ErrorCode::UnknownTx),
+            (
+                Error::WalletFailure(WalletError::AccountNotFound("0x01".to_string())),
+                404,
+                ErrorCode::UnknownAccount,
+            ),
+            (
+                Error::HistoryUnavailable(1),
+                404,
+                ErrorCode::HistoryUnavailable,
+            ),
+            (
+                Error::InvalidTxNonce("0x01".to_string(), 1, 0),
+                409,
+                ErrorCode::InvalidTxNonce,
+            ),
+            (
+                Error::InvalidTx("bad sig".to_string()),
+                422,
+                ErrorCode::InvalidTx,
+            ),
+            (
+                Error::BalanceInsufficient("0x01".to_string(), 1, 2),
+                422,
+                ErrorCode::InsufficientBalance,
+            ),
+            (Error::MinerUnavailable, 503, ErrorCode::MinerUnavailable),
+            (
+                Error::AccountProofUnavailable("0x01".to_string()),
+                503,
+                ErrorCode::ProofUnavailable,
+            ),
+        ];
+        for (err, status, code) in cases {
+            let name = format!("{err:?}");
+            assert_eq!(status_and_code(err), (status, code), "{name}");
+        }
+    }
+
+    #[test]
+    fn failures_of_the_node_are_internal() {
+        let hash = Hash::default();
+        let errs = [
+            Error::ConfigNotExist("config.toml".to_string()),
+            Error::InvalidConfig(toml::from_str::<u64>("x").unwrap_err()),
+            Error::GenesisNotExist("genesis.toml".to_string()),
+            Error::InvalidGenesis,
+            Error::InvalidHttpAddr("x".parse::<std::net::SocketAddr>().unwrap_err()),
+            Error::InvalidBlockNumber(1, 2),
+            Error::InvalidBlockParent(hash, hash),
+            Error::InvalidBlockHash(hash, 2),
+            Error::InvalidBlockTxsHash(hash),
+            Error::InvalidBlockStateRoot(hash, hash, hash),
+            Error::InvalidBlockHeader(1, "short hash".to_string()),
+            Error::MissingBlockBody(hash),
+            Error::CheckpointMismatch(1, hash, hash),
+            Error::InvalidPeerStatus("unknown chain".to_string()),
+            Error::InvalidResponse("unexpected body".to_string()),
+            Error::InvalidCompactBlock("missing header".to_string()),
+            Error::InvalidSnapshot("bad root".to_string()),
+            Error::InvalidAccountProof("bad root".to_string()),
+            Error::InvalidReqResp(prost::DecodeError::new("x")),
+            Error::DbFailure(sled::Error::Unsupported("x".to_string())),
+            Error::AddBlockFailure,
+            Error::WalletFailure(WalletError::InvalidSignature),
+            Error::P2pFailure(tinyp2p::P2pError::InvalidPeerId),
+        ];
+        for err in errs {
+            let name = format!("{err:?}");
+            assert_eq!(status_and_code(err), (500, ErrorCode::Internal), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn errors_are_responded_as_json() {
+        let err = Error::InvalidTxNonce("0x01".to_string(), 1, 0);
+        let (status, body) = respond(err.into()).await;
+        assert_eq!(status, 409);
+        assert_eq!(body.code, ErrorCode::InvalidTxNonce);
+        assert_eq!(
+            body.details,
+            Some(json!({"account": "0x01", "expected": 1, "got": 0}))
+        );
+
+        let (status, body) = respond(HttpError::not_found()).await;
+        assert_eq!((status, body.code), (404, ErrorCode::NotFound));
+        assert_eq!(body.details, None);
+    }
+
+    #[tokio::test]
+    async fn rejections_are_responded_as_json() {
+        let req = Request::get("/blocks?limit=x").body(Body::empty()).unwrap();
+        let (mut parts, _) = req.into_parts();
+        let rejection = extract::Query::<GetBlocksReq>::from_request_parts(&mut parts, &())
+            .await
+            .err()
+            .unwrap();
+        let (status, body) = respond(rejection).await;
+        assert_eq!((status, body.code), (400, ErrorCode::InvalidQuery));
+
+        let req = Request::post("/transfer")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{\"from\": 1}"))
+            .unwrap();
+        let rejection = extract::Json::<TxReq>::from_request(req, &())
+            .await
+            .err()
+            .unwrap();
+        let (status, body) = respond(rejection).await;
+        assert_eq!((status, body.code), (422, ErrorCode::InvalidBody));
+
+        let req = Request::post("/transfer").body(Body::from("{}")).unwrap();
+        let rejection = extract::Json::<TxReq>::from_request(req, &())
+            .await
+            .err()
+            .unwrap();
+        let (status, body) = respond(rejection).await;
+        assert_eq!((status, body.code), (415, ErrorCode::InvalidBody));
+    }
+}
@@ -0,0 +1,28 @@
+//! The extractors of axum, whose rejections are responded as the errors of the API.
+
+use axum::{
+    extract::{FromRequest, FromRequestParts},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use super::error::HttpError;
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(HttpError))]
+pub(super) struct Query<T>(pub T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(HttpError))]
+pub(super) struct Path<T>(pub T);
+
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(HttpError))]
+pub(super) struct Json<T>(pub T);
+
+// It's also used for the responses, like `axum::Json`.
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
@@ -1,34 +1,68 @@
 //! HTTP server that handles requests from the outside world.
+//!
+//! A light node serves its own routes, as it has only the headers and asks the full peers to
+//! prove the balances.
 
-use std::net::SocketAddr;
+use std::{future::Future, net::SocketAddr};
 
 use axum::{
-    extract::{Extension, Path, Query},
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    extract::Extension,
+    response::IntoResponse,
     routing::{get, post},
-    Json, Router, Server,
+    Router, Server,
 };
 use log::info;
-use serde_json::json;
+use tokio::task;
+use utoipa::OpenApi;
 
+use self::{
+    error::HttpError,
+    extract::{Json, Path, Query},
+};
 use crate::{
-    biz::{Node, State},
+    biz::{LightNode, Node, PeerClient, State, TxLocation},
     error::Error,
+    schema,
+    types::Hash,
 };
 
 mod dto;
+mod error;
+mod extract;
+mod openapi;
+mod rpc;
+mod ws;
+
+/// The maximum number of blocks returned by `/blocks` in one page.
+const MAX_BLOCKS_PER_PAGE: usize = 100;
+/// The maximum number of txs returned by `/accounts/:address/txs` in one page.
+const MAX_TXS_PER_PAGE: usize = 100;
 
 pub use dto::*;
 
-pub async fn run<S: State>(addr: SocketAddr, node: Node<S>) {
-    let router = new_router(node);
+/// Run the HTTP server until the `signal` completes.
+/// The in-flight requests are completed before returning.
+pub async fn run<S: State>(addr: SocketAddr, node: Node<S>, signal: impl Future<Output = ()>) {
+    serve(addr, new_router(node), signal).await;
+}
+
+/// Run the HTTP server of a light node until the `signal` completes.
+pub async fn run_light<P: PeerClient>(
+    addr: SocketAddr,
+    node: LightNode<P>,
+    signal: impl Future<Output = ()>,
+) {
+    serve(addr, new_light_router(node), signal).await;
+}
 
+async fn serve(addr: SocketAddr, router: Router, signal: impl Future<Output = ()>) {
     info!("📣 HTTP server listening on {addr}");
     Server::bind(&addr)
         .serve(router.into_make_service())
+        .with_graceful_shutdown(signal)
         .await
         .expect("Failed to run http server");
+    info!("📣 HTTP server stopped");
 }
 
 pub fn new_router<S: State>(node: Node<S>) -> Router {
@@ -36,102 +70,567 @@ pub fn new_router<S: State>(node: Node<S>) -> Router {
         .route("/blocks", get(get_blocks::<S>))
         .route("/blocks/:number", get(get_block::<S>))
         .route("/balances", get(get_balances::<S>))
+        .route("/account/balance", get(get_account::<S>))
         .route("/account/nonce", get(next_account_nonce::<S>))
         .route("/transfer", post(transfer::<S>))
+        .route("/txs", post(submit_tx::<S>))
+        .route("/txs/:hash", get(get_tx::<S>))
+        .route("/accounts/:address", get(get_account_detail::<S>))
+        .route("/accounts/:address/txs", get(get_account_txs::<S>))
+        .route("/ws", get(ws::subscribe::<S>))
+        .route("/rpc", post(rpc::handle::<S>))
+        .route("/openapi.json", get(get_openapi))
+        .fallback(not_found)
+        .layer(Extension(node))
+}
+
+pub fn new_light_router<P: PeerClient>(node: LightNode<P>) -> Router {
+    Router::new()
+        .route("/headers/:number", get(get_header::<P>))
+        .route("/account/balance", get(get_proven_account::<P>))
+        .route("/openapi.json", get(get_light_openapi))
         .fallback(not_found)
         .layer(Extension(node))
 }
 
+#[utoipa::path(
+    get,
+    path = "/blocks",
+    params(GetBlocksReq),
+    responses(
+        (status = 200, description = "A page of blocks", body = GetBlocksResp),
+        (status = 400, description = "Invalid params", body = ErrorResp),
+    )
+)]
 async fn get_blocks<S: State>(
     Extension(node): Extension<Node<S>>,
     Query(params): Query<GetBlocksReq>,
 ) -> impl IntoResponse {
     info!("📣 >> get_blocks by: {:?}", params);
-    let blocks: Vec<BlockResp> = node
-        .get_blocks(params.from_number)
-        .into_iter()
-        .map(BlockResp::from)
-        .collect();
-    info!("📣 << get_blocks response: {:?}", blocks);
+    let GetBlocksReq {
+        from_number,
+        to_number,
+        limit,
+        reverse,
+    } = params;
+    let from_number = from_number.unwrap_or(if reverse { u64::MAX } else { 0 });
+    let limit = limit
+        .unwrap_or(MAX_BLOCKS_PER_PAGE)
+        .clamp(1, MAX_BLOCKS_PER_PAGE);
+
+    // Fetch one more block to find out the cursor of the next page.
+    let mut blocks = node.get_blocks(from_number, to_number, limit + 1, reverse);
+    let next = if blocks.len() > limit {
+        blocks.pop().map(|block| block.number())
+    } else {
+        None
+    };
+
+    let resp = GetBlocksResp {
+        blocks: blocks.into_iter().map(BlockResp::from).collect(),
+        next,
+    };
+    info!("📣 << get_blocks response: {:?}", resp);
 
-    Json(blocks)
+    Json(resp)
 }
 
+#[utoipa::path(
+    get,
+    path = "/blocks/{number}",
+    params(("number" = u64, Path, description = "The block number")),
+    responses(
+        (status = 200, description = "The block", body = BlockResp),
+        (status = 400, description = "Invalid params", body = ErrorResp),
+        (status = 404, description = "The block is not found", body = ErrorResp),
+    )
+)]
 async fn get_block<S: State>(
     Extension(node): Extension<Node<S>>,
     Path(number): Path<u64>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, HttpError> {
     info!("📣 >> get_block by: {:?}", number);
-    let block = node.get_block(number).map(BlockResp::from);
+    let block = node
+        .get_block(number)
+        .map(BlockResp::from)
+        .ok_or_else(|| Error::UnknownBlock(number.to_string()));
     info!("📣 << get_block response: {:?}", block);
 
-    Json(block)
+    Ok(Json(block?))
 }
 
-async fn get_balances<S: State>(Extension(node): Extension<Node<S>>) -> impl IntoResponse {
-    info!("📣 >> get_balances");
-    let resp = json!({
-        "last_block_hash": node.last_block_hash(),
-        "balances": node.get_balances(),
-    });
+#[utoipa::path(
+    get,
+    path = "/balances",
+    params(BalancesReq),
+    responses(
+        (status = 200, description = "The balances of all the accounts", body = BalancesResp),
+        (status = 400, description = "Invalid params", body = ErrorResp),
+        (status = 404, description = "The block or its state is not found", body = ErrorResp),
+    )
+)]
+async fn get_balances<S: State>(
+    Extension(node): Extension<Node<S>>,
+    Query(params): Query<BalancesReq>,
+) -> Result<impl IntoResponse, HttpError> {
+    info!("📣 >> get_balances by: {:?}", params);
+    let resp = match params.at {
+        Some(at) => {
+            let (block_hash, balances) = node.get_balances_at(&at)?;
+            BalancesResp {
+                last_block_hash: Some(block_hash),
+                balances,
+            }
+        }
+        None => BalancesResp {
+            last_block_hash: node.last_block_hash(),
+            balances: node.get_balances(),
+        },
+    };
     info!("📣 << get_balances response: {:?}", resp);
 
-    Json(resp)
+    Ok(Json(resp))
 }
 
+#[utoipa::path(
+    get,
+    path = "/account/balance",
+    params(AccountAtReq),
+    responses(
+        (
+            status = 200,
+            description = "The balance and the next nonce of the account",
+            body = AccountResp
+        ),
+        (status = 400, description = "Invalid params", body = ErrorResp),
+        (status = 404, description = "The block or its state is not found", body = ErrorResp),
+    )
+)]
+async fn get_account<S: State>(
+    Extension(node): Extension<Node<S>>,
+    Query(params): Query<AccountAtReq>,
+) -> Result<impl IntoResponse, HttpError> {
+    info!("📣 >> get_account by: {:?}", params);
+    let resp = node
+        .get_account(&params.account, params.at.as_ref())
+        .map(|(state, header)| account_resp(state, header));
+    info!("📣 << get_account response: {:?}", resp);
+
+    Ok(Json(resp?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/account/nonce",
+    params(NonceReq),
+    responses(
+        (status = 200, description = "The next nonce of the account", body = NonceResp),
+        (status = 400, description = "Invalid params", body = ErrorResp),
+    )
+)]
 async fn next_account_nonce<S: State>(
     Extension(node): Extension<Node<S>>,
     Query(params): Query<NonceReq>,
 ) -> impl IntoResponse {
     info!("📣 >> next_account_nonce by: {:?}", params);
-    let resp = json!({ "nonce": node.next_account_nonce(&params.account) });
+    let resp = NonceResp {
+        nonce: node.next_account_nonce(&params.account),
+    };
     info!("📣 << next_account_nonce response: {:?}", resp);
 
     Json(resp)
 }
 
+#[utoipa::path(
+    post,
+    path = "/transfer",
+    request_body = TxReq,
+    responses(
+        (
+            status = 200,
+            description = "The tx is signed by the node and accepted",
+            body = TxHashResp
+        ),
+        (status = 400, description = "Invalid body", body = ErrorResp),
+        (status = 404, description = "The node doesn't keep the key", body = ErrorResp),
+        (status = 409, description = "The nonce is not the next one", body = ErrorResp),
+        (status = 422, description = "The tx is invalid or unaffordable", body = ErrorResp),
+        (status = 503, description = "The miner is not running", body = ErrorResp),
+    )
+)]
 async fn transfer<S: State>(
     Extension(node): Extension<Node<S>>,
     Json(tx): Json<TxReq>,
 ) -> Result<impl IntoResponse, HttpError> {
     info!("📣 >> transfer: {:?}", tx);
-    let resp = node.transfer(&tx.from, &tx.to, tx.value, tx.nonce);
+    // Wait for the miner to check the tx with a blocking call.
+    let resp = task::spawn_blocking(move || node.transfer(&tx.from, &tx.to, tx.value, tx.nonce))
+        .await
+        .expect("Failed to transfer");
     info!("📣 << transfer response: {:?}", resp);
 
-    resp?;
-    Ok(Json(json!({"success": true})))
+    Ok(Json(TxHashResp {
+        success: true,
+        tx_hash: resp?,
+    }))
 }
 
-async fn not_found() -> impl IntoResponse {
-    (StatusCode::NOT_FOUND, "Not Found")
+#[utoipa::path(
+    post,
+    path = "/txs",
+    request_body = SubmitTxReq,
+    responses(
+        (status = 200, description = "The tx is accepted", body = TxHashResp),
+        (status = 400, description = "Invalid body", body = ErrorResp),
+        (status = 409, description = "The nonce is not the next one", body = ErrorResp),
+        (status = 422, description = "The tx is invalid or unaffordable", body = ErrorResp),
+        (status = 503, description = "The miner is not running", body = ErrorResp),
+    )
+)]
+async fn submit_tx<S: State>(
+    Extension(node): Extension<Node<S>>,
+    Json(req): Json<SubmitTxReq>,
+) -> Result<impl IntoResponse, HttpError> {
+    info!("📣 >> submit_tx: {:?}", req);
+    // Wait for the miner to check the tx with a blocking call.
+    let resp = task::spawn_blocking(move || {
+        schema::SignedTx::try_from(req).and_then(|tx| node.submit_tx(tx))
+    })
+    .await
+    .expect("Failed to submit the tx");
+    info!("📣 << submit_tx response: {:?}", resp);
+
+    Ok(Json(TxHashResp {
+        success: true,
+        tx_hash: resp?,
+    }))
 }
 
-#[derive(thiserror::Error, Debug)]
-enum HttpError {
-    #[error("Bad request: {0}")]
-    BadRequest(Error),
-    #[error("Internal server error: {0}")]
-    InternalServerError(Error),
+#[utoipa::path(
+    get,
+    path = "/txs/{hash}",
+    params(("hash" = Hash, Path, description = "The tx hash")),
+    responses(
+        (status = 200, description = "The pending or included tx", body = TxResp),
+        (status = 400, description = "Invalid hash", body = ErrorResp),
+        (status = 404, description = "The tx is not found", body = ErrorResp),
+    )
+)]
+async fn get_tx<S: State>(
+    Extension(node): Extension<Node<S>>,
+    Path(hash): Path<Hash>,
+) -> Result<impl IntoResponse, HttpError> {
+    info!("📣 >> get_tx by: {:?}", hash);
+    let tx = node
+        .get_tx(&hash)
+        .map(TxResp::from)
+        .ok_or(Error::UnknownTx(hash));
+    info!("📣 << get_tx response: {:?}", tx);
+
+    Ok(Json(tx?))
 }
 
-impl From<Error> for HttpError {
-    fn from(err: Error) -> Self {
-        match err {
-            Error::BadRequest(..) => HttpError::BadRequest(err),
-            _ => HttpError::InternalServerError(err),
-        }
-    }
+#[utoipa::path(
+    get,
+    path = "/accounts/{address}",
+    params(("address" = String, Path, description = "The account address")),
+    responses(
+        (
+            status = 200,
+            description = "The confirmed and the pending state of the account",
+            body = AccountDetailResp
+        ),
+    )
+)]
+async fn get_account_detail<S: State>(
+    Extension(node): Extension<Node<S>>,
+    Path(address): Path<String>,
+) -> impl IntoResponse {
+    info!("📣 >> get_account_detail by: {:?}", address);
+    let resp = AccountDetailResp::from(node.get_account_detail(&address));
+    info!("📣 << get_account_detail response: {:?}", resp);
+
+    Json(resp)
 }
 
-impl IntoResponse for HttpError {
-    fn into_response(self) -> Response {
-        let status = match self {
-            HttpError::BadRequest(_) => StatusCode::BAD_REQUEST,
-            HttpError::InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+#[utoipa::path(
+    get,
+    path = "/accounts/{address}/txs",
+    params(("address" = String, Path, description = "The account address"), AccountTxsReq),
+    responses(
+        (
+            status = 200,
+            description = "A page of the txs of the account",
+            body = AccountTxsResp
+        ),
+        (status = 400, description = "Invalid params", body = ErrorResp),
+    )
+)]
+async fn get_account_txs<S: State>(
+    Extension(node): Extension<Node<S>>,
+    Path(address): Path<String>,
+    Query(params): Query<AccountTxsReq>,
+) -> impl IntoResponse {
+    info!("📣 >> get_account_txs of {} by: {:?}", address, params);
+    let AccountTxsReq {
+        direction,
+        from_number,
+        from_index,
+        to_number,
+        limit,
+    } = params;
+    let from = TxLocation {
+        number: from_number.unwrap_or(0),
+        index: from_index.unwrap_or(0),
+    };
+    let limit = limit.unwrap_or(MAX_TXS_PER_PAGE).clamp(1, MAX_TXS_PER_PAGE);
+
+    // Fetch one more tx to find out the cursor of the next page.
+    let mut txs = node.get_account_txs(&address, direction, from, to_number, limit + 1);
+    let next = if txs.len() > limit {
+        txs.pop().map(|(location, _)| NextTxs {
+            from_number: location.number,
+            from_index: location.index,
+        })
+    } else {
+        None
+    };
+
+    let resp = AccountTxsResp {
+        txs: txs.into_iter().map(|(_, tx)| TxResp::from(tx)).collect(),
+        next,
+    };
+    info!("📣 << get_account_txs response: {:?}", resp);
+
+    Json(resp)
+}
+
+#[utoipa::path(
+    get,
+    path = "/headers/{number}",
+    params(("number" = u64, Path, description = "The block number")),
+    responses(
+        (status = 200, description = "The header", body = HeaderResp),
+        (status = 400, description = "Invalid params", body = ErrorResp),
+        (status = 404, description = "The header is not synced", body = ErrorResp),
+    )
+)]
+async fn get_header<P: PeerClient>(
+    Extension(node): Extension<LightNode<P>>,
+    Path(number): Path<u64>,
+) -> Result<impl IntoResponse, HttpError> {
+    info!("📣 >> get_header by: {:?}", number);
+    let header = node
+        .get_header(number)
+        .map(HeaderResp::from)
+        .ok_or_else(|| Error::UnknownBlock(number.to_string()));
+    info!("📣 << get_header response: {:?}", header);
+
+    Ok(Json(header?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/account/balance",
+    params(AccountReq),
+    responses(
+        (
+            status = 200,
+            description = "The account proven against the state root of the header",
+            body = AccountResp
+        ),
+        (status = 400, description = "Invalid params", body = ErrorResp),
+        (status = 503, description = "No peer can prove the account", body = ErrorResp),
+    )
+)]
+async fn get_proven_account<P: PeerClient>(
+    Extension(node): Extension<LightNode<P>>,
+    Query(params): Query<AccountReq>,
+) -> Result<impl IntoResponse, HttpError> {
+    info!("📣 >> get_proven_account by: {:?}", params);
+    // The proof is requested from the peers with blocking calls.
+    let resp = task::spawn_blocking(move || node.get_account(&params.account))
+        .await
+        .expect("Failed to get the proven account");
+    info!("📣 << get_proven_account response: {:?}", resp);
+
+    Ok(Json(AccountResp::from(resp?)))
+}
+
+async fn get_openapi() -> impl IntoResponse {
+    Json(openapi::ApiDoc::openapi())
+}
+
+async fn get_light_openapi() -> impl IntoResponse {
+    Json(openapi::LightApiDoc::openapi())
+}
+
+async fn not_found() -> impl IntoResponse {
+    HttpError::not_found()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap};
+
+    use client::Client;
+    use crossbeam_channel::unbounded;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+    use wallet::Wallet;
+
+    use super::*;
+    use crate::{
+        biz::{
+            BlockId, ChainSpec, Checkpoints, EventBus, PeerBook, PendingState, SnapshotStore,
+            TxPool,
+        },
+        data::MemoryState,
+    };
+
+    /// A node without the miner, whose keystore is named after the test, as sled locks it.
+    pub(super) fn new_node(name: &str) -> Node<MemoryState> {
+        let keystore_dir =
+            std::env::temp_dir().join(format!("tinychain-http-{name}-{}", std::process::id()));
+        let chain_spec = ChainSpec {
+            chain_id: "test".to_string(),
+            genesis_hash: Hash::default(),
+            checkpoints: Checkpoints::default(),
         };
+        Node::new(
+            MemoryState::new(HashMap::new()),
+            Wallet::new(keystore_dir.to_str().unwrap()),
+            PeerBook::new(chain_spec, 2),
+            TxPool::new(),
+            PendingState::new(),
+            SnapshotStore::new(),
+            EventBus::new(),
+            unbounded().0,
+            unbounded().0,
+            unbounded().0,
+            unbounded().0,
+        )
+    }
+
+    /// Serve the router on a random port of the localhost.
+    fn serve_router(router: Router) -> SocketAddr {
+        let server =
+            Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(router.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    /// Send a plain GET request, and return the status and the error in the response.
+    async fn get_error(addr: SocketAddr, path: &str) -> (u16, ErrorResp) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let req = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+        stream.write_all(req.as_bytes()).await.unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await.unwrap();
+
+        let (head, body) = resp.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    /// The `(method, path)`s of an OpenAPI document.
+    fn documented_routes(doc: &serde_json::Value) -> BTreeSet<(String, String)> {
+        doc["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| {
+                item.as_object()
+                    .unwrap()
+                    .keys()
+                    .map(move |method| (method.clone(), path.clone()))
+            })
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn openapi_lists_every_route() {
+        let addr = serve_router(new_router(new_node("openapi_lists_every_route")));
+        let client = Client::new(&format!("http://{addr}"));
+        let doc = task::spawn_blocking(move || client.openapi())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // The routes of `new_router`, except `/ws` and `/openapi.json` themselves.
+        let routes: BTreeSet<(String, String)> = [
+            ("get", "/blocks"),
+            ("get", "/blocks/{number}"),
+            ("get", "/balances"),
+            ("get", "/account/balance"),
+            ("get", "/account/nonce"),
+            ("post", "/transfer"),
+            ("post", "/txs"),
+            ("get", "/txs/{hash}"),
+            ("get", "/accounts/{address}"),
+            ("get", "/accounts/{address}/txs"),
+            ("post", "/rpc"),
+        ]
+        .into_iter()
+        .map(|(method, path)| (method.to_string(), path.to_string()))
+        .collect();
+        assert_eq!(documented_routes(&doc), routes);
+
+        for schema in ["WsReq", "WsEvent", "ErrorResp", "EthBlock"] {
+            assert!(
+                doc["components"]["schemas"].get(schema).is_some(),
+                "{schema}"
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn client_round_trips() {
+        let addr = serve_router(new_router(new_node("client_round_trips")));
+        let client = Client::new(&format!("http://{addr}"));
+        task::spawn_blocking(move || {
+            let blocks = client.get_blocks(&GetBlocksReq::default()).unwrap();
+            assert!(blocks.blocks.is_empty());
+            assert_eq!(blocks.next, None);
+            assert!(client.get_block(0).unwrap().is_none());
+            assert!(client.get_tx(&Hash::default()).unwrap().is_none());
+
+            let balances = client.get_balances(None).unwrap();
+            assert_eq!(balances.last_block_hash, None);
+            assert_eq!(client.next_account_nonce("0x01").unwrap(), 0);
+            let detail = client.get_account_detail("0x01").unwrap();
+            assert_eq!((detail.balance, detail.pending_nonce), (0, 0));
+
+            let err = client.get_balances(Some(BlockId::Number(1))).unwrap_err();
+            assert_eq!(err.code(), Some(ErrorCode::UnknownBlock));
+            assert_eq!(client.rpc("eth_blockNumber", vec![]).unwrap(), "0x0");
+            assert!(matches!(
+                client.rpc("eth_mining", vec![]),
+                Err(client::ClientError::Rpc { code: -32601, .. })
+            ));
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn routed_errors_are_responded_as_json() {
+        let addr = serve_router(new_router(new_node("routed_errors_are_responded_as_json")));
 
-        let body = Json(json!({ "error": self.to_string() }));
+        let (status, err) = get_error(addr, "/blocks/x").await;
+        assert_eq!((status, err.code), (400, ErrorCode::InvalidPath));
+        let (status, err) = get_error(addr, "/txs/0x01").await;
+        assert_eq!((status, err.code), (400, ErrorCode::InvalidPath));
+        let (status, err) = get_error(addr, "/blocks?limit=x").await;
+        assert_eq!((status, err.code), (400, ErrorCode::InvalidQuery));
 
-        (status, body).into_response()
+        let (status, err) = get_error(addr, "/blocks/1").await;
+        assert_eq!((status, err.code), (404, ErrorCode::UnknownBlock));
+        assert_eq!(err.details, Some(serde_json::json!({"block": "1"})));
+        let (status, err) = get_error(addr, "/nowhere").await;
+        assert_eq!((status, err.code), (404, ErrorCode::NotFound));
     }
 }
@@ -0,0 +1,37 @@
+//! OpenAPI documents of the HTTP API, served at `/openapi.json`.
+//!
+//! The paths are collected from the handlers, and the schemas from the DTOs they refer to,
+//! except the ones of the query params. The WebSocket messages are listed as schemas, as
+//! OpenAPI can't describe the `/ws` endpoint.
+
+use utoipa::OpenApi;
+
+use super::{dto::*, rpc};
+use crate::biz::{BlockId, TxDirection};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(description = "The HTTP API of a full node."),
+    paths(
+        super::get_blocks,
+        super::get_block,
+        super::get_balances,
+        super::get_account,
+        super::next_account_nonce,
+        super::transfer,
+        super::submit_tx,
+        super::get_tx,
+        super::get_account_detail,
+        super::get_account_txs,
+        rpc::handle,
+    ),
+    components(schemas(BlockId, TxDirection, WsReq, WsEvent, EthBlock, EthTx))
+)]
+pub(super) struct ApiDoc;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(description = "The HTTP API of a light node."),
+    paths(super::get_header, super::get_proven_account)
+)]
+pub(super) struct LightApiDoc;
@@ -0,0 +1,387 @@
+//! JSON-RPC 2.0 endpoint with a subset of the Ethereum `eth_*` methods.
+//!
+//! The addresses, hashes and signatures are already in the Ethereum style, while the numbers are
+//! encoded as hex quantities, and the raw txs are hex-encoded protobuf `SignedTx`s instead of
+//! RLP. A batch of requests is answered with a batch of responses.
+
+use axum::{
+    extract::Extension,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use log::info;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::task;
+
+use super::dto::*;
+use crate::{
+    biz::{BlockId, Node, State},
+    error::Error,
+    schema,
+    types::Hash,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The code of the errors of the node, e.g. a rejected tx, as used by the Ethereum clients.
+const SERVER_ERROR: i64 = -32000;
+
+/// A request, or a batch of requests, of the `eth_*` methods.
+#[utoipa::path(
+    post,
+    path = "/rpc",
+    request_body = RpcReq,
+    responses(
+        (
+            status = 200,
+            description = "The response, or a batch of responses to a batch",
+            body = RpcResp
+        ),
+        (status = 204, description = "Only notifications are received"),
+    )
+)]
+pub(super) async fn handle<S: State>(
+    Extension(node): Extension<Node<S>>,
+    body: String,
+) -> Response {
+    info!("📣 >> rpc: {}", body);
+    // Submitting a tx waits for the miner with a blocking call.
+    let resp = task::spawn_blocking(move || handle_body(&node, &body))
+        .await
+        .expect("Failed to handle the rpc");
+    info!("📣 << rpc response: {:?}", resp);
+
+    match resp {
+        Some(resp) => Json(resp).into_response(),
+        // Only notifications are received.
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+fn handle_body<S: State>(node: &Node<S>, body: &str) -> Option<Value> {
+    let Ok(value) = serde_json::from_str::<Value>(body) else {
+        return Some(error_resp(Value::Null, PARSE_ERROR, "Parse error"));
+    };
+
+    match value {
+        Value::Array(reqs) if reqs.is_empty() => {
+            Some(error_resp(Value::Null, INVALID_REQUEST, "Empty batch"))
+        }
+        Value::Array(reqs) => {
+            let resps: Vec<Value> = reqs
+                .into_iter()
+                .filter_map(|req| handle_req(node, req))
+                .collect();
+            (!resps.is_empty()).then_some(Value::Array(resps))
+        }
+        req => handle_req(node, req),
+    }
+}
+
+fn handle_req<S: State>(node: &Node<S>, req: Value) -> Option<Value> {
+    let req = match serde_json::from_value::<RpcReq>(req) {
+        Ok(req) if req.jsonrpc == "2.0" => req,
+        _ => return Some(error_resp(Value::Null, INVALID_REQUEST, "Invalid request")),
+    };
+
+    let result = call(node, &req.method, &req.params);
+    let id = req.id?;
+    let resp = match result {
+        Ok(result) => RpcResp {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        },
+        Err(error) => RpcResp {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        },
+    };
+
+    Some(serde_json::to_value(resp).unwrap())
+}
+
+fn call<S: State>(node: &Node<S>, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+    let result = match method {
+        "eth_blockNumber" => {
+            let number = node.block_height().saturating_sub(1);
+            to_value(quantity(number))
+        }
+        "eth_getBalance" => {
+            let account: String = param(params, 0)?;
+            let state = get_account(node, &account, param(params, 1)?)?;
+            to_value(quantity(state.balance))
+        }
+        "eth_getTransactionCount" => {
+            let account: String = param(params, 0)?;
+            let state = get_account(node, &account, param(params, 1)?)?;
+            to_value(quantity(state.nonce))
+        }
+        "eth_getBlockByNumber" => {
+            let tag: Option<String> = param(params, 0)?;
+            let full_txs: Option<bool> = param(params, 1)?;
+            let number = match block_tag(tag)? {
+                // There is no pending block, as the txs are only assembled when mining.
+                BlockTag::Pending => return Ok(Value::Null),
+                BlockTag::Latest => node.block_height().checked_sub(1),
+                BlockTag::Number(number) => Some(number),
+            };
+            let block = number.and_then(|number| node.get_block(number));
+            to_value(block.map(|block| eth_block(block, full_txs.unwrap_or_default())))
+        }
+        "eth_getBlockByHash" => {
+            let hash: Hash = param(params, 0)?;
+            let full_txs: Option<bool> = param(params, 1)?;
+            let block = node.get_block_by_hash(&hash);
+            to_value(block.map(|block| eth_block(block, full_txs.unwrap_or_default())))
+        }
+        "eth_getTransactionByHash" => {
+            let hash: Hash = param(params, 0)?;
+            to_value(node.get_tx(&hash).map(EthTx::from))
+        }
+        "eth_sendRawTransaction" => {
+            let raw: String = param(params, 0)?;
+            let tx = schema::SignedTx::try_from(SubmitTxReq::Raw { raw })
+                .map_err(|err| invalid_params(err.to_string()))?;
+            to_value(node.submit_tx(tx).map_err(server_error)?)
+        }
+        _ => {
+            return Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Method not found: {method}"),
+            })
+        }
+    };
+
+    Ok(result)
+}
+
+/// The block parameter of the Ethereum JSON-RPC, which defaults to `latest`.
+enum BlockTag {
+    Latest,
+    Pending,
+    Number(u64),
+}
+
+fn block_tag(tag: Option<String>) -> Result<BlockTag, RpcError> {
+    let Some(tag) = tag else {
+        return Ok(BlockTag::Latest);
+    };
+
+    match tag.as_str() {
+        // Every block is final, as the first block seen at a height wins.
+        "latest" | "safe" | "finalized" => Ok(BlockTag::Latest),
+        "pending" => Ok(BlockTag::Pending),
+        "earliest" => Ok(BlockTag::Number(0)),
+        _ => tag
+            .strip_prefix("0x")
+            .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+            .map(BlockTag::Number)
+            .ok_or_else(|| invalid_params(format!("invalid block: {tag}"))),
+    }
+}
+
+fn get_account<S: State>(
+    node: &Node<S>,
+    account: &str,
+    tag: Option<String>,
+) -> Result<schema::AccountState, RpcError> {
+    match block_tag(tag)? {
+        BlockTag::Latest => Ok(node.get_account(account, None).map_err(server_error)?.0),
+        BlockTag::Pending => Ok(node.get_account_detail(account).pending),
+        BlockTag::Number(number) => Ok(node
+            .get_account(account, Some(&BlockId::Number(number)))
+            .map_err(server_error)?
+            .0),
+    }
+}
+
+/// Decode the positional parameter, which is `null` if it's missing.
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<T, RpcError> {
+    let value = params.get(index).cloned().unwrap_or_default();
+    serde_json::from_value(value).map_err(|err| invalid_params(format!("param {index}: {err}")))
+}
+
+fn to_value(value: impl serde::Serialize) -> Value {
+    serde_json::to_value(value).unwrap()
+}
+
+fn invalid_params(message: String) -> RpcError {
+    RpcError {
+        code: INVALID_PARAMS,
+        message: format!("Invalid params: {message}"),
+    }
+}
+
+fn server_error(err: Error) -> RpcError {
+    RpcError {
+        code: SERVER_ERROR,
+        message: err.to_string(),
+    }
+}
+
+fn error_resp(id: Value, code: i64, message: &str) -> Value {
+    to_value(RpcResp {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result: None,
+        error: Some(RpcError {
+            code,
+            message: message.to_string(),
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{super::tests::new_node, *};
+    use crate::data::MemoryState;
+
+    fn handle(node: &Node<MemoryState>, body: Value) -> Option<Value> {
+        handle_body(node, &body.to_string())
+    }
+
+    fn error_code(resp: &Value) -> i64 {
+        resp["error"]["code"].as_i64().unwrap()
+    }
+
+    #[test]
+    fn invalid_bodies_are_answered_with_errors() {
+        let node = new_node("invalid_bodies_are_answered_with_errors");
+
+        let resp = handle_body(&node, "{").unwrap();
+        assert_eq!(error_code(&resp), PARSE_ERROR);
+        assert_eq!(resp["id"], Value::Null);
+
+        let resp = handle(&node, json!([])).unwrap();
+        assert_eq!(error_code(&resp), INVALID_REQUEST);
+
+        let resp = handle(
+            &node,
+            json!({"jsonrpc": "1.0", "id": 1, "method": "eth_blockNumber"}),
+        );
+        assert_eq!(error_code(&resp.unwrap()), INVALID_REQUEST);
+
+        let resp = handle(&node, json!({"jsonrpc": "2.0", "id": 1}));
+        assert_eq!(error_code(&resp.unwrap()), INVALID_REQUEST);
+    }
+
+    #[test]
+    fn notifications_are_not_responded_to() {
+        let node = new_node("notifications_are_not_responded_to");
+        let notification = json!({"jsonrpc": "2.0", "method": "eth_blockNumber"});
+        assert_eq!(handle(&node, notification.clone()), None);
+        assert_eq!(handle(&node, json!([notification.clone()])), None);
+
+        // A `null` id is not a notification.
+        let resp = handle(
+            &node,
+            json!({"jsonrpc": "2.0", "id": null, "method": "eth_blockNumber"}),
+        );
+        assert_eq!(
+            resp,
+            Some(json!({"jsonrpc": "2.0", "id": null, "result": "0x0"}))
+        );
+
+        // Only the requests of a batch are responded to, and an invalid one is still an error.
+        let resp = handle(
+            &node,
+            json!([
+                {"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber"},
+                notification,
+                {"jsonrpc": "2.0", "id": "b", "method": "eth_blockNumber"},
+                1,
+            ]),
+        )
+        .unwrap();
+        let resps = resp.as_array().unwrap();
+        assert_eq!(resps.len(), 3);
+        assert_eq!(resps[0]["id"], json!(1));
+        assert_eq!(resps[1]["id"], json!("b"));
+        assert_eq!(error_code(&resps[2]), INVALID_REQUEST);
+    }
+
+    #[test]
+    fn calls_are_checked() {
+        let node = new_node("calls_are_checked");
+
+        let resp = handle(
+            &node,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "eth_mining"}),
+        );
+        let resp = resp.unwrap();
+        assert_eq!(error_code(&resp), METHOD_NOT_FOUND);
+        assert_eq!(resp["id"], json!(1));
+        assert_eq!(resp.get("result"), None);
+
+        let resp = handle(
+            &node,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "eth_getBalance", "params": [1, "latest"]}),
+        );
+        assert_eq!(error_code(&resp.unwrap()), INVALID_PARAMS);
+
+        let resp = handle(
+            &node,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "eth_getBlockByNumber", "params": ["0xz"]}),
+        );
+        assert_eq!(error_code(&resp.unwrap()), INVALID_PARAMS);
+
+        // There is no block yet.
+        let resp = handle(
+            &node,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "eth_getBlockByNumber", "params": ["latest"]}),
+        );
+        assert_eq!(resp.unwrap()["result"], Value::Null);
+    }
+
+    #[test]
+    fn raw_txs_are_checked() {
+        let node = new_node("raw_txs_are_checked");
+        let send = |raw: String| {
+            let body = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_sendRawTransaction",
+                "params": [raw],
+            });
+            error_code(&handle(&node, body).unwrap())
+        };
+
+        // The raw txs which can't be decoded are invalid params.
+        assert_eq!(send("0xzz".to_string()), INVALID_PARAMS);
+        assert_eq!(send("0x0a0b".to_string()), INVALID_PARAMS);
+
+        // A decoded tx that the node rejects is a server error.
+        let tx = schema::SignedTx {
+            tx: Some(schema::Tx::new("0x0000aaaa", "0x0000bbbb", 100, 0)),
+            sig: vec![0u8; 65],
+        };
+        let raw = format!("0x{}", hex::encode(prost::Message::encode_to_vec(&tx)));
+        assert_eq!(send(raw), SERVER_ERROR);
+    }
+
+    #[test]
+    fn block_tags_are_parsed() {
+        let parse = |tag: Option<&str>| block_tag(tag.map(str::to_string));
+
+        for tag in [None, Some("latest"), Some("safe"), Some("finalized")] {
+            assert!(matches!(parse(tag), Ok(BlockTag::Latest)));
+        }
+        assert!(matches!(parse(Some("pending")), Ok(BlockTag::Pending)));
+        assert!(matches!(parse(Some("earliest")), Ok(BlockTag::Number(0))));
+        assert!(matches!(parse(Some("0x1f")), Ok(BlockTag::Number(31))));
+        for tag in ["31", "0x", "0xg", "head"] {
+            let err = parse(Some(tag)).err().unwrap();
+            assert_eq!(err.code, INVALID_PARAMS);
+        }
+    }
+}
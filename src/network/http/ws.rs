@@ -0,0 +1,255 @@
+//! WebSocket subscriptions to the chain events.
+//!
+//! A client subscribes to topics by sending `WsReq`s, and receives the `WsEvent`s of its topics
+//! as JSON text messages, which are fed from the event bus of the node.
+
+use std::collections::HashSet;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Extension,
+    },
+    response::IntoResponse,
+};
+use log::{info, warn};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::dto::*;
+use crate::{
+    biz::{Event, Node, State, TxLookup},
+    schema::{self, TxReceipt},
+};
+
+pub(super) async fn subscribe<S: State>(
+    Extension(node): Extension<Node<S>>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| serve(socket, node))
+}
+
+/// Serve a client until it closes the connection.
+async fn serve<S: State>(mut socket: WebSocket, node: Node<S>) {
+    info!("📣 >> WebSocket client connected");
+    let mut events = node.subscribe();
+    let mut topics = HashSet::new();
+
+    loop {
+        let messages = tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => handle_req(&text, &mut topics),
+                // Pings are answered by axum, and binary messages are not supported.
+                Some(Ok(Message::Binary(_) | Message::Ping(_) | Message::Pong(_))) => vec![],
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+            },
+            event = events.recv() => match event {
+                Ok(event) => ws_events(&event, &topics),
+                Err(RecvError::Lagged(missed)) => vec![WsEvent::Lagged { missed }],
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        for msg in messages {
+            let text = serde_json::to_string(&msg).expect("Failed to serialize the event");
+            if socket.send(Message::Text(text)).await.is_err() {
+                warn!("❌ Failed to send to the WebSocket client");
+                return;
+            }
+        }
+    }
+    info!("📣 << WebSocket client disconnected");
+}
+
+fn handle_req(text: &str, topics: &mut HashSet<WsTopic>) -> Vec<WsEvent> {
+    match serde_json::from_str::<WsReq>(text) {
+        Ok(WsReq {
+            action: WsAction::Subscribe,
+            topic,
+        }) => {
+            topics.insert(topic);
+            vec![]
+        }
+        Ok(WsReq {
+            action: WsAction::Unsubscribe,
+            topic,
+        }) => {
+            topics.remove(&topic);
+            vec![]
+        }
+        Err(err) => vec![WsEvent::Error {
+            message: err.to_string(),
+        }],
+    }
+}
+
+/// The messages of the event for the subscribed topics.
+fn ws_events(event: &Event, topics: &HashSet<WsTopic>) -> Vec<WsEvent> {
+    let mut messages = vec![];
+    let accounts: Vec<&String> = topics
+        .iter()
+        .filter_map(|topic| match topic {
+            WsTopic::Account { address } => Some(address),
+            _ => None,
+        })
+        .collect();
+
+    match event {
+        Event::NewBlock(block) => {
+            if topics.contains(&WsTopic::NewHeads) {
+                messages.push(WsEvent::NewHead(block.header().clone().into()));
+            }
+            for (index, tx) in block.txs.iter().enumerate() {
+                let receipt = TxReceipt::new(tx, block.hash(), block.number(), index as u32);
+                // The block is the head, which is the first confirmation.
+                let lookup = TxLookup::Included {
+                    tx: tx.clone(),
+                    receipt,
+                    confirmations: 1,
+                };
+                messages.extend(account_txs(&accounts, tx, lookup));
+            }
+        }
+        Event::PendingTx(tx) => {
+            if topics.contains(&WsTopic::PendingTxs) {
+                messages.push(WsEvent::PendingTx(TxLookup::Pending(tx.clone()).into()));
+            }
+            messages.extend(account_txs(&accounts, tx, TxLookup::Pending(tx.clone())));
+        }
+    }
+
+    messages
+}
+
+fn account_txs(accounts: &[&String], tx: &schema::SignedTx, lookup: TxLookup) -> Vec<WsEvent> {
+    accounts
+        .iter()
+        .filter(|&&address| &tx.from == address || &tx.to == address)
+        .map(|address| WsEvent::AccountTx {
+            address: address.to_string(),
+            tx: lookup.clone().into(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        schema::{Block, SignedTx, Tx},
+        types::Hash,
+    };
+
+    const ALICE: &str = "0x0000aaaa";
+    const BOB: &str = "0x0000bbbb";
+
+    fn transfer(from: &str, to: &str) -> SignedTx {
+        SignedTx {
+            tx: Some(Tx::new(from, to, 100, 0)),
+            sig: vec![0u8; 65],
+        }
+    }
+
+    fn account(address: &str) -> WsTopic {
+        WsTopic::Account {
+            address: address.to_string(),
+        }
+    }
+
+    #[test]
+    fn subscribe_and_unsubscribe() {
+        let mut topics = HashSet::new();
+        let reqs = [
+            r#"{"action": "subscribe", "topic": "new_heads"}"#,
+            r#"{"action": "subscribe", "topic": "pending_txs"}"#,
+            r#"{"action": "subscribe", "topic": "account", "address": "0x0000aaaa"}"#,
+            // Subscribing twice is the same as once.
+            r#"{"action": "subscribe", "topic": "new_heads"}"#,
+        ];
+        for req in reqs {
+            assert!(handle_req(req, &mut topics).is_empty());
+        }
+        assert_eq!(
+            topics,
+            HashSet::from([WsTopic::NewHeads, WsTopic::PendingTxs, account(ALICE)])
+        );
+
+        let req = r#"{"action": "unsubscribe", "topic": "account", "address": "0x0000aaaa"}"#;
+        assert!(handle_req(req, &mut topics).is_empty());
+        // Unsubscribing an unknown topic does nothing.
+        let req = r#"{"action": "unsubscribe", "topic": "account", "address": "0x0000bbbb"}"#;
+        assert!(handle_req(req, &mut topics).is_empty());
+        assert_eq!(
+            topics,
+            HashSet::from([WsTopic::NewHeads, WsTopic::PendingTxs])
+        );
+    }
+
+    #[test]
+    fn invalid_reqs_are_answered_with_errors() {
+        let mut topics = HashSet::new();
+        for req in [
+            "not json",
+            r#"{"action": "watch", "topic": "new_heads"}"#,
+            r#"{"action": "subscribe", "topic": "blocks"}"#,
+            r#"{"action": "subscribe", "topic": "account"}"#,
+        ] {
+            let messages = handle_req(req, &mut topics);
+            assert!(matches!(messages[..], [WsEvent::Error { .. }]), "{req}");
+        }
+        assert!(topics.is_empty());
+    }
+
+    #[test]
+    fn events_are_mapped_to_the_subscribed_topics() {
+        let block = Block::new(
+            Hash::default(),
+            0,
+            "0x01234567".to_string(),
+            vec![transfer(ALICE, BOB), transfer(BOB, "0x0000cccc")],
+        );
+        let new_block = Event::NewBlock(block.clone());
+        let pending = Event::PendingTx(transfer(ALICE, BOB));
+
+        // Nothing is sent without a subscription.
+        let none = HashSet::new();
+        for event in [&new_block, &pending] {
+            assert!(ws_events(event, &none).is_empty());
+        }
+
+        let heads = HashSet::from([WsTopic::NewHeads]);
+        assert!(matches!(
+            &ws_events(&new_block, &heads)[..],
+            [WsEvent::NewHead(head)] if head.hash == block.hash()
+        ));
+        assert!(ws_events(&pending, &heads).is_empty());
+
+        let pending_txs = HashSet::from([WsTopic::PendingTxs]);
+        assert!(ws_events(&new_block, &pending_txs).is_empty());
+        assert!(matches!(
+            &ws_events(&pending, &pending_txs)[..],
+            [WsEvent::PendingTx(tx)] if matches!(tx.status, TxStatus::Pending)
+        ));
+
+        // An account gets the txs it sends or receives.
+        let bob = HashSet::from([account(BOB)]);
+        let messages = ws_events(&new_block, &bob);
+        assert_eq!(messages.len(), 2);
+        for (index, msg) in messages.iter().enumerate() {
+            assert!(matches!(
+                msg,
+                WsEvent::AccountTx { address, tx }
+                    if address == BOB
+                        && matches!(tx.status, TxStatus::Included)
+                        && tx.index == Some(index as u32)
+                        && tx.confirmations == 1
+            ));
+        }
+        assert!(matches!(
+            &ws_events(&pending, &bob)[..],
+            [WsEvent::AccountTx { address, .. }] if address == BOB
+        ));
+        let other = HashSet::from([account("0x0000dddd")]);
+        assert!(ws_events(&new_block, &other).is_empty());
+        assert!(ws_events(&pending, &other).is_empty());
+    }
+}
@@ -3,23 +3,36 @@
 //! - `P2pClient` is a wrapper around `tinyp2p::Client` that implements the `Peer` trait.
 //! - `EventHandlerImpl` is an implementation of `EventHandler` that handles inbound requests and
 //!   broadcasts.
+//! - `LightEventHandler` is the `EventHandler` of a light node, which only serves its headers.
 
 use std::ops::Deref;
 
 use log::{error, info};
-use tinyp2p::{config::P2pConfig, Client, EventHandler, P2pError, Server};
+use tinyp2p::{config::P2pConfig, Client, EventHandler, P2pError, PeerId, PeerInfo, Server};
 
 use crate::{
-    biz::{Node, PeerClient, State},
+    biz::{block_range, LightNode, Node, PeerClient, State},
     error::Error,
     schema::*,
+    types::Hash,
 };
 
+/// The maximum number of blocks served in one response.
+const MAX_BLOCKS_PER_REQUEST: u64 = 64;
+/// The maximum number of pending tx hashes served in one response.
+const MAX_TX_HASHES_PER_REQUEST: usize = 4096;
+/// The maximum number of pending txs or block txs served in one response.
+const MAX_TXS_PER_REQUEST: usize = 256;
+/// The maximum number of headers served in one response.
+const MAX_HEADERS_PER_REQUEST: u64 = 512;
+/// The maximum number of block bodies served in one response.
+const MAX_BODIES_PER_REQUEST: usize = 64;
+
 // Re-export libp2p functions.
 pub use tinyp2p::new_secret_key;
 
 /// Creates a new p2p client, event loop, and server.
-pub fn new<S: State>(config: P2pConfig) -> Result<(P2pClient, Server<EventHandlerImpl<S>>), Error> {
+pub fn new<E: EventHandler>(config: P2pConfig) -> Result<(P2pClient, Server<E>), Error> {
     let (client, p2p_server) = tinyp2p::new(config)?;
     let p2p_client = P2pClient::new(client);
 
@@ -52,33 +65,164 @@ impl PeerClient for P2pClient {
         peers
     }
 
-    fn get_block_height(&self, peer_id: &str) -> Result<u64, Error> {
-        let req = Request::new_block_height_req();
-        info!("📣 >> [OUT] get_block_height from: {}", peer_id);
+    fn get_status(&self, peer_id: &str) -> Result<StatusResp, Error> {
+        let req = Request::new_status_req();
+        info!("📣 >> [OUT] get_status from: {}", peer_id);
+        let resp: Response = self.blocking_request(peer_id, req.into())?.try_into()?;
+        info!("📣 << [IN] get_status response: {:?}", resp);
+
+        StatusResp::try_from(resp)
+    }
+
+    fn disconnect(&self, peer_id: &str) -> Result<(), Error> {
+        info!("📣 >> [OUT] disconnect: {}", peer_id);
+        Ok(self.0.disconnect(peer_id)?)
+    }
+
+    fn get_headers(
+        &self,
+        peer_id: &str,
+        from_number: u64,
+        count: u64,
+    ) -> Result<Vec<BlockHeader>, Error> {
+        let req = Request::new_headers_req(from_number, count);
+        info!("📣 >> [OUT] get_headers from: {}, by: {:?}", peer_id, req);
+        let resp: Response = self.blocking_request(peer_id, req.into())?.try_into()?;
+        let headers = HeadersResp::try_from(resp)?.headers;
+        info!("📣 << [IN] get_headers count: {:?}", headers.len());
+
+        Ok(headers)
+    }
+
+    fn get_block_bodies(&self, peer_id: &str, hashes: &[Hash]) -> Result<Vec<BlockBody>, Error> {
+        let req = Request::new_block_bodies_req(hashes);
+        info!(
+            "📣 >> [OUT] get_block_bodies from: {}, count: {}",
+            peer_id,
+            hashes.len()
+        );
+        let resp: Response = self.blocking_request(peer_id, req.into())?.try_into()?;
+        let bodies = BlockBodiesResp::try_from(resp)?.bodies;
+        info!("📣 << [IN] get_block_bodies count: {:?}", bodies.len());
+
+        Ok(bodies)
+    }
+
+    fn get_block_txs(
+        &self,
+        peer_id: &str,
+        block_hash: Hash,
+        indexes: &[u32],
+    ) -> Result<Vec<SignedTx>, Error> {
+        let req = Request::new_block_txs_req(block_hash, indexes);
+        info!(
+            "📣 >> [OUT] get_block_txs from: {}, block: {}, count: {}",
+            peer_id,
+            block_hash,
+            indexes.len()
+        );
+        let resp: Response = self.blocking_request(peer_id, req.into())?.try_into()?;
+        let txs = BlockTxsResp::try_from(resp)?.txs;
+        info!("📣 << [IN] get_block_txs count: {:?}", txs.len());
+
+        Ok(txs)
+    }
+
+    fn get_pending_tx_hashes(&self, peer_id: &str) -> Result<Vec<Hash>, Error> {
+        let req = Request::new_pending_tx_hashes_req();
+        info!("📣 >> [OUT] get_pending_tx_hashes from: {}", peer_id);
+        let resp: Response = self.blocking_request(peer_id, req.into())?.try_into()?;
+        let hashes: Vec<Hash> = PendingTxHashesResp::try_from(resp)?
+            .hashes
+            .into_iter()
+            .filter(|hash| hash.len() == 32)
+            .map(Hash::from)
+            .collect();
+        info!("📣 << [IN] get_pending_tx_hashes count: {:?}", hashes.len());
+
+        Ok(hashes)
+    }
+
+    fn get_pending_txs(&self, peer_id: &str, hashes: &[Hash]) -> Result<Vec<SignedTx>, Error> {
+        let req = Request::new_pending_txs_req(hashes);
+        info!(
+            "📣 >> [OUT] get_pending_txs from: {}, count: {}",
+            peer_id,
+            hashes.len()
+        );
         let resp: Response = self.blocking_request(peer_id, req.into())?.try_into()?;
-        info!("📣 << [IN] get_block_height response: {:?}", resp);
+        let txs = PendingTxsResp::try_from(resp)?.txs;
+        info!("📣 << [IN] get_pending_txs count: {:?}", txs.len());
 
-        Ok(BlockHeightResp::from(resp).block_height)
+        Ok(txs)
     }
 
-    fn get_blocks(&self, peer_id: &str, from_number: u64) -> Result<Vec<Block>, Error> {
-        let req = Request::new_blocks_req(from_number);
-        info!("📣 >> [OUT] get_blocks from: {}, by: {:?}", peer_id, req);
+    fn get_snapshot_manifest(&self, peer_id: &str) -> Result<Option<SnapshotManifest>, Error> {
+        let req = Request::new_snapshot_manifest_req();
+        info!("📣 >> [OUT] get_snapshot_manifest from: {}", peer_id);
         let resp: Response = self.blocking_request(peer_id, req.into())?.try_into()?;
-        let blocks = BlocksResp::from(resp).blocks;
-        info!("📣 << [IN] get_blocks count: {:?}", blocks.len());
+        let manifest = SnapshotManifestResp::try_from(resp)?.manifest;
+        info!(
+            "📣 << [IN] get_snapshot_manifest number: {:?}",
+            manifest.as_ref().map(|manifest| manifest.number)
+        );
 
-        Ok(blocks)
+        Ok(manifest)
+    }
+
+    fn get_snapshot_chunk(
+        &self,
+        peer_id: &str,
+        block_hash: Hash,
+        index: u32,
+    ) -> Result<Option<StateChunk>, Error> {
+        let req = Request::new_snapshot_chunk_req(block_hash, index);
+        info!(
+            "📣 >> [OUT] get_snapshot_chunk from: {}, block: {}, index: {}",
+            peer_id, block_hash, index
+        );
+        let resp: Response = self.blocking_request(peer_id, req.into())?.try_into()?;
+        let chunk = SnapshotChunkResp::try_from(resp)?.chunk;
+        info!(
+            "📣 << [IN] get_snapshot_chunk accounts: {:?}",
+            chunk.as_ref().map(|chunk| chunk.accounts.len())
+        );
+
+        Ok(chunk)
+    }
+
+    fn get_account_proof(
+        &self,
+        peer_id: &str,
+        account: &str,
+    ) -> Result<Option<AccountProof>, Error> {
+        let req = Request::new_account_proof_req(account);
+        info!(
+            "📣 >> [OUT] get_account_proof from: {}, account: {}",
+            peer_id, account
+        );
+        let resp: Response = self.blocking_request(peer_id, req.into())?.try_into()?;
+        let proof = AccountProofResp::try_from(resp)?.proof;
+        info!(
+            "📣 << [IN] get_account_proof chunks: {:?}",
+            proof.as_ref().map(|proof| proof.chunks.len())
+        );
+
+        Ok(proof)
     }
 
     fn broadcast_tx(&self, tx: SignedTx) {
         info!("📣 >> [OUT-BROADCAST] tx: {}", tx);
-        self.broadcast(Topic::Tx, tx.into());
+        if let Err(err) = self.broadcast(Topic::Tx, tx.into()) {
+            error!("❌ >> [OUT-BROADCAST] Failed to broadcast tx: {:?}", err);
+        }
     }
 
     fn broadcast_block(&self, block: Block) {
         info!("📣 >> [OUT-BROADCAST] block: {}", block);
-        self.broadcast(Topic::Block, Vec::from(&block));
+        if let Err(err) = self.broadcast(Topic::Block, Vec::from(&block.to_compact())) {
+            error!("❌ >> [OUT-BROADCAST] Failed to broadcast block: {:?}", err);
+        }
     }
 }
 
@@ -109,32 +253,114 @@ impl<S: State> EventHandler for EventHandlerImpl<S> {
         let req = req.unwrap();
 
         info!("📣 >> [P2P-IN] {:?}", req);
-        let resp = match req.method() {
-            Method::Height => {
-                let block_height = self.block_height();
-                Response::new_block_height_resp(block_height)
-            }
-            Method::Blocks => {
-                let blocks = match req.body.unwrap() {
-                    request::Body::BlocksReq(req) => self.get_blocks(req.from_number),
-                    _ => vec![],
+        let resp = match (req.method(), req.body) {
+            (Method::Height, _) => Response::new_block_height_resp(self.block_height()),
+            // The blocks before a snapshot are not kept, so the peer has to ask another one.
+            (Method::Blocks, Some(request::Body::BlocksReq(req)))
+                if block_range(req.from_number, req.to_number, req.reverse)
+                    .is_some_and(|(start, _)| start < self.lowest_block_number()) =>
+            {
+                error!(
+                    "❌ >> [P2P-IN] Blocks before {} are not kept",
+                    self.lowest_block_number()
+                );
+                return Err(P2pError::RequestRejected);
+            }
+            (Method::Blocks, Some(request::Body::BlocksReq(req))) => {
+                let limit = match req.limit {
+                    0 => MAX_BLOCKS_PER_REQUEST,
+                    limit => limit.min(MAX_BLOCKS_PER_REQUEST),
                 };
+                let blocks =
+                    self.get_blocks(req.from_number, req.to_number, limit as usize, req.reverse);
                 Response::new_blocks_resp(blocks)
             }
+            (Method::Status, _) => Response::new_status_resp(self.status()),
+            (Method::PendingTxHashes, _) => {
+                let mut hashes = self.pending_tx_hashes();
+                hashes.truncate(MAX_TX_HASHES_PER_REQUEST);
+                Response::new_pending_tx_hashes_resp(hashes)
+            }
+            (Method::PendingTxs, Some(request::Body::PendingTxsReq(req))) => {
+                let hashes: Vec<Hash> = req
+                    .hashes
+                    .into_iter()
+                    .take(MAX_TXS_PER_REQUEST)
+                    .filter(|hash| hash.len() == 32)
+                    .map(Hash::from)
+                    .collect();
+                Response::new_pending_txs_resp(self.get_pending_txs(&hashes))
+            }
+            (Method::BlockTxs, Some(request::Body::BlockTxsReq(req))) => {
+                let txs = if req.block_hash.len() == 32 {
+                    let indexes = &req.indexes[..req.indexes.len().min(MAX_TXS_PER_REQUEST)];
+                    self.get_block_txs(&Hash::from(req.block_hash), indexes)
+                } else {
+                    vec![]
+                };
+                Response::new_block_txs_resp(txs)
+            }
+            (Method::SnapshotManifest, _) => {
+                Response::new_snapshot_manifest_resp(self.snapshot_manifest())
+            }
+            (Method::SnapshotChunk, Some(request::Body::SnapshotChunkReq(req))) => {
+                let chunk = if req.block_hash.len() == 32 {
+                    self.get_snapshot_chunk(&Hash::from(req.block_hash), req.index as usize)
+                } else {
+                    None
+                };
+                Response::new_snapshot_chunk_resp(chunk)
+            }
+            (Method::AccountProof, Some(request::Body::AccountProofReq(req))) => {
+                Response::new_account_proof_resp(self.account_proof(&req.account))
+            }
+            (Method::Headers, Some(request::Body::HeadersReq(req)))
+                if req.from_number < self.lowest_block_number() =>
+            {
+                error!(
+                    "❌ >> [P2P-IN] Blocks before {} are not kept",
+                    self.lowest_block_number()
+                );
+                return Err(P2pError::RequestRejected);
+            }
+            (Method::Headers, Some(request::Body::HeadersReq(req))) => {
+                let count = req.count.min(MAX_HEADERS_PER_REQUEST) as usize;
+                Response::new_headers_resp(self.get_headers(req.from_number, count))
+            }
+            (Method::BlockBodies, Some(request::Body::BlockBodiesReq(req))) => {
+                let hashes: Vec<Hash> = req
+                    .hashes
+                    .into_iter()
+                    .take(MAX_BODIES_PER_REQUEST)
+                    .map_while(|hash| (hash.len() == 32).then(|| Hash::from(hash)))
+                    .collect();
+                Response::new_block_bodies_resp(self.get_block_bodies(&hashes))
+            }
+            (method, _) => {
+                error!(
+                    "❌ >> [P2P-IN] Invalid request body of method: {:?}",
+                    method
+                );
+                return Err(P2pError::RequestRejected);
+            }
         };
         info!("📣 << [P2P-OUT] {:?}", resp);
 
         Ok(resp.into())
     }
 
-    fn handle_broadcast(&self, topic: &str, message: Vec<u8>) {
+    fn handle_broadcast(&self, topic: &str, message: Vec<u8>, source: PeerId) {
         match Topic::from(topic) {
             Topic::Block => {
-                if let Ok(block) = Block::try_from(message) {
-                    info!("📣 >> [P2P-IN-BROADCAST] {}", block);
-                    self.handle_broadcast_block(block);
+                if let Ok(compact) = CompactBlock::try_from(message) {
+                    info!(
+                        "📣 >> [P2P-IN-BROADCAST] compact block: {}, txs: {}",
+                        compact.header(),
+                        compact.short_tx_ids.len()
+                    );
+                    self.handle_broadcast_compact_block(compact, source.to_base58());
                 } else {
-                    error!("❌ >> [P2P-IN-BROADCAST] Invalid block");
+                    error!("❌ >> [P2P-IN-BROADCAST] Invalid compact block");
                 }
             }
             Topic::Tx => {
@@ -147,6 +373,84 @@ impl<S: State> EventHandler for EventHandlerImpl<S> {
             }
         }
     }
+
+    fn on_peer_connected(&self, info: &PeerInfo) {
+        self.handle_new_peer(info.peer_id.to_base58());
+    }
+
+    fn on_peer_disconnected(&self, peer_id: &PeerId) {
+        self.handle_peer_disconnected(&peer_id.to_base58());
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LightEventHandler(LightNode<P2pClient>);
+
+impl LightEventHandler {
+    pub fn new(node: LightNode<P2pClient>) -> Self {
+        Self(node)
+    }
+}
+
+impl Deref for LightEventHandler {
+    type Target = LightNode<P2pClient>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl EventHandler for LightEventHandler {
+    fn handle_inbound_request(&self, request: Vec<u8>) -> Result<Vec<u8>, P2pError> {
+        let req = Request::try_from(request);
+        if req.is_err() {
+            error!("❌ >> [P2P-IN] Invalid request: {:?}", req.err());
+            return Err(P2pError::RequestRejected);
+        }
+        let req = req.unwrap();
+
+        info!("📣 >> [P2P-IN] {:?}", req);
+        // A light node has neither blocks nor state, the peers only ask for the headers to
+        // verify its status.
+        let resp = match (req.method(), req.body) {
+            (Method::Status, _) => Response::new_status_resp(self.status()),
+            (Method::Height, _) => Response::new_block_height_resp(self.block_height()),
+            (Method::Headers, Some(request::Body::HeadersReq(req))) => {
+                let count = req.count.min(MAX_HEADERS_PER_REQUEST) as usize;
+                Response::new_headers_resp(self.get_headers(req.from_number, count))
+            }
+            (method, _) => {
+                error!(
+                    "❌ >> [P2P-IN] Unsupported method of light node: {:?}",
+                    method
+                );
+                return Err(P2pError::RequestRejected);
+            }
+        };
+        info!("📣 << [P2P-OUT] {:?}", resp);
+
+        Ok(resp.into())
+    }
+
+    fn handle_broadcast(&self, topic: &str, message: Vec<u8>, source: PeerId) {
+        // The txs are left to the full nodes.
+        if let Topic::Block = Topic::from(topic) {
+            if let Ok(compact) = CompactBlock::try_from(message) {
+                info!("📣 >> [P2P-IN-BROADCAST] header: {}", compact.header());
+                self.handle_broadcast_header(compact.header(), source.to_base58());
+            } else {
+                error!("❌ >> [P2P-IN-BROADCAST] Invalid compact block");
+            }
+        }
+    }
+
+    fn on_peer_connected(&self, info: &PeerInfo) {
+        self.handle_new_peer(info.peer_id.to_base58());
+    }
+
+    fn on_peer_disconnected(&self, peer_id: &PeerId) {
+        self.handle_peer_disconnected(&peer_id.to_base58());
+    }
 }
 
 #[derive(Debug)]
@@ -4,7 +4,7 @@ use prost::Message;
 
 use crate::{error::Error, types::Hash, utils};
 
-use super::{Block, BlockHeader, SignedTx};
+use super::{Block, BlockBody, BlockHeader, CompactBlock, SignedTx};
 
 impl Block {
     pub fn new(parent_hash: Hash, number: u64, author: String, txs: Vec<SignedTx>) -> Self {
@@ -15,13 +15,34 @@ impl Block {
                 nonce: utils::gen_random_number(),
                 timestamp: utils::unix_timestamp(),
                 author,
+                txs_hash: Self::compute_txs_hash(&txs).into(),
+                // Set by the miner, which knows the state that the block is applied to.
+                state_root: Hash::default().into(),
             }),
             txs,
         }
     }
 
+    /// Assemble a block from a header and a body downloaded separately.
+    pub fn from_parts(header: BlockHeader, body: BlockBody) -> Self {
+        Self {
+            header: Some(header),
+            txs: body.txs,
+        }
+    }
+
+    /// The hash of a block is the hash of its header, which commits to the txs by `txs_hash`.
     pub fn hash(&self) -> Hash {
-        utils::hash_message(&self.encode_to_vec())
+        self.header.as_ref().unwrap().hash()
+    }
+
+    /// Compute the hash of the txs, which is the hash of the concatenated tx hashes.
+    pub fn compute_txs_hash(txs: &[SignedTx]) -> Hash {
+        let mut msg = Vec::with_capacity(txs.len() * 32);
+        for tx in txs {
+            msg.extend_from_slice(&tx.hash()[..]);
+        }
+        utils::hash_message(&msg)
     }
 
     /// Get the reward which the author will get.
@@ -29,12 +50,21 @@ impl Block {
         self.txs.iter().map(|tx| tx.gas_cost()).sum()
     }
 
+    /// Commit to the state after applying the block, which must be done before mining.
+    pub fn set_state_root(&mut self, state_root: Hash) {
+        self.header.as_mut().unwrap().state_root = state_root.into();
+    }
+
     /// Update the nonce and timestamp of the block, which is used for mining.
     pub fn update_nonce_and_time(&mut self) {
         self.header.as_mut().unwrap().nonce = utils::gen_random_number();
         self.header.as_mut().unwrap().timestamp = utils::unix_timestamp();
     }
 
+    pub fn header(&self) -> &BlockHeader {
+        self.header.as_ref().unwrap()
+    }
+
     pub fn nonce(&self) -> u64 {
         self.header.as_ref().unwrap().nonce
     }
@@ -54,6 +84,87 @@ impl Block {
     pub fn author(&self) -> &str {
         self.header.as_ref().unwrap().author.as_str()
     }
+
+    pub fn txs_hash(&self) -> Hash {
+        self.header.as_ref().unwrap().txs_hash()
+    }
+
+    pub fn state_root(&self) -> Hash {
+        self.header.as_ref().unwrap().state_root()
+    }
+
+    /// Announce the block by its header and the short IDs of its txs.
+    pub fn to_compact(&self) -> CompactBlock {
+        CompactBlock {
+            header: self.header.clone(),
+            short_tx_ids: self.txs.iter().map(|tx| tx.short_id()).collect(),
+        }
+    }
+}
+
+impl CompactBlock {
+    /// Check that the header is present and well-formed, which must be done before using a
+    /// compact block received from a peer, as the accessors below assume it.
+    pub fn check(&self) -> Result<(), Error> {
+        match &self.header {
+            Some(header) => header.check_hashes(),
+            None => Err(Error::InvalidCompactBlock("missing header".to_string())),
+        }
+    }
+
+    pub fn header(&self) -> &BlockHeader {
+        self.header.as_ref().unwrap()
+    }
+
+    pub fn hash(&self) -> Hash {
+        self.header().hash()
+    }
+
+    pub fn number(&self) -> u64 {
+        self.header().number
+    }
+}
+
+impl BlockHeader {
+    pub fn hash(&self) -> Hash {
+        utils::hash_message(&self.encode_to_vec())
+    }
+
+    pub fn parent_hash(&self) -> Hash {
+        Hash::from(self.parent_hash.clone())
+    }
+
+    pub fn txs_hash(&self) -> Hash {
+        Hash::from(self.txs_hash.clone())
+    }
+
+    pub fn state_root(&self) -> Hash {
+        Hash::from(self.state_root.clone())
+    }
+
+    /// Check that the hashes are well-formed, which must be done before using a header received
+    /// from a peer, as the accessors above assume it.
+    pub fn check_hashes(&self) -> Result<(), Error> {
+        let hashes = [
+            ("parent_hash", &self.parent_hash),
+            ("txs_hash", &self.txs_hash),
+            ("state_root", &self.state_root),
+        ];
+        for (name, hash) in hashes {
+            if hash.len() != 32 {
+                return Err(Error::InvalidBlockHeader(
+                    self.number,
+                    format!("malformed {}", name),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if the body belongs to this header.
+    pub fn matches_body(&self, body: &BlockBody) -> bool {
+        Block::compute_txs_hash(&body.txs) == self.txs_hash()
+    }
 }
 
 impl TryFrom<Vec<u8>> for Block {
@@ -70,6 +181,22 @@ impl From<&Block> for Vec<u8> {
     }
 }
 
+impl TryFrom<Vec<u8>> for CompactBlock {
+    type Error = Error;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        let compact = Self::decode(value.as_slice())?;
+        compact.check()?;
+        Ok(compact)
+    }
+}
+
+impl From<&CompactBlock> for Vec<u8> {
+    fn from(value: &CompactBlock) -> Self {
+        value.encode_to_vec()
+    }
+}
+
 // For better logging.
 // `fmt::Debug` is implemented by prost, we can't implement it manually.
 impl fmt::Display for Block {
@@ -92,16 +219,27 @@ impl fmt::Display for BlockHeader {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "BlockHeader {{ number: {}, parent_hash: {}, nonce: {}, timestamp: {}, author: \"{}\" }}",
+            "BlockHeader {{ number: {}, parent_hash: {}, nonce: {}, timestamp: {}, author: \"{}\", txs_hash: {}, state_root: {} }}",
             self.number,
-            Hash::from(self.parent_hash.clone()),
+            HexBytes(&self.parent_hash),
             self.nonce,
             self.timestamp,
             self.author,
+            HexBytes(&self.txs_hash),
+            HexBytes(&self.state_root),
         )
     }
 }
 
+/// Format the raw bytes of a hash as hex, which doesn't panic on a malformed header from a peer.
+struct HexBytes<'a>(&'a [u8]);
+
+impl fmt::Display for HexBytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::schema::Tx;
@@ -133,4 +271,80 @@ mod tests {
         block.update_nonce_and_time();
         assert_ne!(block.nonce(), old_nonce);
     }
+
+    #[test]
+    fn block_hash_commits_to_txs() {
+        let tx = Tx::new("0x00000000", "0x11111111", 100, 100);
+        let signed_tx = SignedTx {
+            tx: Some(tx),
+            sig: vec![0u8; 65],
+        };
+
+        let block = Block::new(
+            Hash::default(),
+            1,
+            "0x01234567".to_string(),
+            vec![signed_tx.clone()],
+        );
+        let header = block.header.clone().unwrap();
+        assert_eq!(block.hash(), header.hash());
+        assert_eq!(block.txs_hash(), Block::compute_txs_hash(&block.txs));
+
+        let body = BlockBody {
+            txs: vec![signed_tx],
+        };
+        assert!(header.matches_body(&body));
+        assert!(!header.matches_body(&BlockBody::default()));
+
+        let assembled = Block::from_parts(header, body);
+        assert_eq!(assembled, block);
+    }
+
+    #[test]
+    fn compact_block_roundtrip() {
+        let txs: Vec<SignedTx> = (0..3)
+            .map(|nonce| SignedTx {
+                tx: Some(Tx::new("0x00000000", "0x11111111", 100, nonce)),
+                sig: vec![0u8; 65],
+            })
+            .collect();
+        let block = Block::new(Hash::default(), 1, "0x01234567".to_string(), txs.clone());
+
+        let compact = CompactBlock::try_from(Vec::from(&block.to_compact())).unwrap();
+        assert_eq!(compact.hash(), block.hash());
+        assert_eq!(compact.number(), 1);
+        let short_ids: Vec<u64> = txs.iter().map(|tx| tx.short_id()).collect();
+        assert_eq!(compact.short_tx_ids, short_ids);
+
+        assert!(CompactBlock::try_from(vec![]).is_err());
+
+        let mut malformed = block.to_compact();
+        malformed.header.as_mut().unwrap().txs_hash.truncate(8);
+        assert!(CompactBlock::try_from(Vec::from(&malformed)).is_err());
+        // Logging a malformed header doesn't panic.
+        let txs_hash = hex::encode(&block.txs_hash()[..8]);
+        assert!(malformed
+            .header()
+            .to_string()
+            .contains(&format!("txs_hash: 0x{txs_hash},")));
+    }
+
+    #[test]
+    fn check_header_hashes() {
+        let block = Block::new(Hash::default(), 1, "0x01234567".to_string(), vec![]);
+        let header = block.header().clone();
+        assert!(header.check_hashes().is_ok());
+
+        let mut short_parent = header.clone();
+        short_parent.parent_hash = vec![0u8; 31];
+        assert!(short_parent.check_hashes().is_err());
+
+        let mut missing_txs_hash = header.clone();
+        missing_txs_hash.txs_hash = vec![];
+        assert!(missing_txs_hash.check_hashes().is_err());
+
+        let mut long_state_root = header;
+        long_state_root.state_root = vec![0u8; 33];
+        assert!(long_state_root.check_hashes().is_err());
+    }
 }
@@ -2,10 +2,10 @@
 
 mod block;
 mod req_resp;
+mod snapshot;
 mod tx;
+// The code is generated by prost, the variants of `oneof` are named after the messages.
+#[allow(clippy::enum_variant_names)]
 mod v1;
 
-pub use block::*;
-pub use req_resp::*;
-pub use tx::*;
 pub use v1::*;
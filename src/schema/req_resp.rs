@@ -1,6 +1,6 @@
 use prost::Message;
 
-use crate::error::Error;
+use crate::{error::Error, types::Hash};
 
 use super::*;
 
@@ -13,11 +13,107 @@ impl Request {
         }
     }
 
-    /// Build a new request to get blocks from the given number.
-    pub fn new_blocks_req(from_number: u64) -> Self {
+    /// Build a new request to get the status of a peer.
+    pub fn new_status_req() -> Self {
+        Self {
+            method: Method::Status as i32,
+            body: Some(request::Body::StatusReq(StatusReq {})),
+        }
+    }
+
+    /// Build a new request to get the hashes of the pending txs.
+    pub fn new_pending_tx_hashes_req() -> Self {
+        Self {
+            method: Method::PendingTxHashes as i32,
+            body: Some(request::Body::PendingTxHashesReq(PendingTxHashesReq {})),
+        }
+    }
+
+    /// Build a new request to get the pending txs by the hashes.
+    pub fn new_pending_txs_req(hashes: &[Hash]) -> Self {
+        Self {
+            method: Method::PendingTxs as i32,
+            body: Some(request::Body::PendingTxsReq(PendingTxsReq {
+                hashes: hashes.iter().map(|hash| hash.to_vec()).collect(),
+            })),
+        }
+    }
+
+    /// Build a new request to get at most `limit` blocks from the given number.
+    /// The blocks stop at `to_number` (inclusive) if given, and are walked towards the genesis
+    /// block if `reverse` is set.
+    pub fn new_blocks_req(
+        from_number: u64,
+        to_number: Option<u64>,
+        limit: u64,
+        reverse: bool,
+    ) -> Self {
         Self {
             method: Method::Blocks as i32,
-            body: Some(request::Body::BlocksReq(BlocksReq { from_number })),
+            body: Some(request::Body::BlocksReq(BlocksReq {
+                from_number,
+                to_number,
+                limit,
+                reverse,
+            })),
+        }
+    }
+
+    /// Build a new request to get at most `count` headers from the given number.
+    pub fn new_headers_req(from_number: u64, count: u64) -> Self {
+        Self {
+            method: Method::Headers as i32,
+            body: Some(request::Body::HeadersReq(HeadersReq { from_number, count })),
+        }
+    }
+
+    /// Build a new request to get the txs of a block by their positions in the block.
+    pub fn new_block_txs_req(block_hash: Hash, indexes: &[u32]) -> Self {
+        Self {
+            method: Method::BlockTxs as i32,
+            body: Some(request::Body::BlockTxsReq(BlockTxsReq {
+                block_hash: block_hash.to_vec(),
+                indexes: indexes.to_vec(),
+            })),
+        }
+    }
+
+    /// Build a new request to get the manifest of the latest state snapshot.
+    pub fn new_snapshot_manifest_req() -> Self {
+        Self {
+            method: Method::SnapshotManifest as i32,
+            body: Some(request::Body::SnapshotManifestReq(SnapshotManifestReq {})),
+        }
+    }
+
+    /// Build a new request to get a chunk of the state snapshot taken at the block.
+    pub fn new_snapshot_chunk_req(block_hash: Hash, index: u32) -> Self {
+        Self {
+            method: Method::SnapshotChunk as i32,
+            body: Some(request::Body::SnapshotChunkReq(SnapshotChunkReq {
+                block_hash: block_hash.to_vec(),
+                index,
+            })),
+        }
+    }
+
+    /// Build a new request to get the proof of an account's state at the peer's head.
+    pub fn new_account_proof_req(account: &str) -> Self {
+        Self {
+            method: Method::AccountProof as i32,
+            body: Some(request::Body::AccountProofReq(AccountProofReq {
+                account: account.to_string(),
+            })),
+        }
+    }
+
+    /// Build a new request to get the block bodies by the block hashes.
+    pub fn new_block_bodies_req(hashes: &[Hash]) -> Self {
+        Self {
+            method: Method::BlockBodies as i32,
+            body: Some(request::Body::BlockBodiesReq(BlockBodiesReq {
+                hashes: hashes.iter().map(|hash| hash.to_vec()).collect(),
+            })),
         }
     }
 }
@@ -33,6 +129,32 @@ impl Response {
         }
     }
 
+    /// Build a new response to get the status.
+    pub fn new_status_resp(status: StatusResp) -> Self {
+        Self {
+            method: Method::Status as i32,
+            body: Some(response::Body::StatusResp(status)),
+        }
+    }
+
+    /// Build a new response to get the hashes of the pending txs.
+    pub fn new_pending_tx_hashes_resp(hashes: Vec<Hash>) -> Self {
+        Self {
+            method: Method::PendingTxHashes as i32,
+            body: Some(response::Body::PendingTxHashesResp(PendingTxHashesResp {
+                hashes: hashes.into_iter().map(Vec::from).collect(),
+            })),
+        }
+    }
+
+    /// Build a new response to get the pending txs.
+    pub fn new_pending_txs_resp(txs: Vec<SignedTx>) -> Self {
+        Self {
+            method: Method::PendingTxs as i32,
+            body: Some(response::Body::PendingTxsResp(PendingTxsResp { txs })),
+        }
+    }
+
     /// Build a new response to get blocks.
     pub fn new_blocks_resp(blocks: Vec<Block>) -> Self {
         Self {
@@ -40,6 +162,58 @@ impl Response {
             body: Some(response::Body::BlocksResp(BlocksResp { blocks })),
         }
     }
+
+    /// Build a new response to get headers.
+    pub fn new_headers_resp(headers: Vec<BlockHeader>) -> Self {
+        Self {
+            method: Method::Headers as i32,
+            body: Some(response::Body::HeadersResp(HeadersResp { headers })),
+        }
+    }
+
+    /// Build a new response to get the txs of a block.
+    pub fn new_block_txs_resp(txs: Vec<SignedTx>) -> Self {
+        Self {
+            method: Method::BlockTxs as i32,
+            body: Some(response::Body::BlockTxsResp(BlockTxsResp { txs })),
+        }
+    }
+
+    /// Build a new response to get the snapshot manifest.
+    pub fn new_snapshot_manifest_resp(manifest: Option<SnapshotManifest>) -> Self {
+        Self {
+            method: Method::SnapshotManifest as i32,
+            body: Some(response::Body::SnapshotManifestResp(SnapshotManifestResp {
+                manifest,
+            })),
+        }
+    }
+
+    /// Build a new response to get a snapshot chunk.
+    pub fn new_snapshot_chunk_resp(chunk: Option<StateChunk>) -> Self {
+        Self {
+            method: Method::SnapshotChunk as i32,
+            body: Some(response::Body::SnapshotChunkResp(SnapshotChunkResp {
+                chunk,
+            })),
+        }
+    }
+
+    /// Build a new response to get an account proof.
+    pub fn new_account_proof_resp(proof: Option<AccountProof>) -> Self {
+        Self {
+            method: Method::AccountProof as i32,
+            body: Some(response::Body::AccountProofResp(AccountProofResp { proof })),
+        }
+    }
+
+    /// Build a new response to get block bodies.
+    pub fn new_block_bodies_resp(bodies: Vec<BlockBody>) -> Self {
+        Self {
+            method: Method::BlockBodies as i32,
+            body: Some(response::Body::BlockBodiesResp(BlockBodiesResp { bodies })),
+        }
+    }
 }
 
 impl TryFrom<Vec<u8>> for Request {
@@ -70,20 +244,132 @@ impl From<Response> for Vec<u8> {
     }
 }
 
-impl From<Response> for BlockHeightResp {
-    fn from(value: Response) -> Self {
-        match value.body.unwrap() {
-            response::Body::BlockHeightResp(resp) => resp,
-            _ => BlockHeightResp { block_height: 0 },
+impl TryFrom<Response> for BlockHeightResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::BlockHeightResp(resp)) => Ok(resp),
+            _ => Err(Error::InvalidResponse("missing block height".to_string())),
         }
     }
 }
 
-impl From<Response> for BlocksResp {
-    fn from(value: Response) -> Self {
-        match value.body.unwrap() {
-            response::Body::BlocksResp(resp) => resp,
-            _ => BlocksResp { blocks: vec![] },
+impl TryFrom<Response> for BlocksResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::BlocksResp(resp)) => Ok(resp),
+            _ => Err(Error::InvalidResponse("missing blocks".to_string())),
+        }
+    }
+}
+
+impl TryFrom<Response> for HeadersResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::HeadersResp(resp)) => {
+                for header in &resp.headers {
+                    header.check_hashes()?;
+                }
+                Ok(resp)
+            }
+            _ => Err(Error::InvalidResponse("missing headers".to_string())),
+        }
+    }
+}
+
+impl TryFrom<Response> for BlockBodiesResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::BlockBodiesResp(resp)) => Ok(resp),
+            _ => Err(Error::InvalidResponse("missing block bodies".to_string())),
+        }
+    }
+}
+
+impl TryFrom<Response> for StatusResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::StatusResp(resp)) => Ok(resp),
+            _ => Err(Error::InvalidPeerStatus("missing status".to_string())),
+        }
+    }
+}
+
+impl TryFrom<Response> for PendingTxHashesResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::PendingTxHashesResp(resp)) => Ok(resp),
+            _ => Err(Error::InvalidResponse(
+                "missing pending tx hashes".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<Response> for PendingTxsResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::PendingTxsResp(resp)) => Ok(resp),
+            _ => Err(Error::InvalidResponse("missing pending txs".to_string())),
+        }
+    }
+}
+
+impl TryFrom<Response> for BlockTxsResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::BlockTxsResp(resp)) => Ok(resp),
+            _ => Err(Error::InvalidResponse("missing block txs".to_string())),
+        }
+    }
+}
+
+impl TryFrom<Response> for SnapshotManifestResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::SnapshotManifestResp(resp)) => Ok(resp),
+            _ => Err(Error::InvalidResponse(
+                "missing snapshot manifest".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<Response> for SnapshotChunkResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::SnapshotChunkResp(resp)) => Ok(resp),
+            _ => Err(Error::InvalidResponse("missing snapshot chunk".to_string())),
+        }
+    }
+}
+
+impl TryFrom<Response> for AccountProofResp {
+    type Error = Error;
+
+    fn try_from(value: Response) -> Result<Self, Self::Error> {
+        match value.body {
+            Some(response::Body::AccountProofResp(resp)) => Ok(resp),
+            _ => Err(Error::InvalidResponse("missing account proof".to_string())),
         }
     }
 }
@@ -0,0 +1,12 @@
+use prost::Message;
+
+use crate::{types::Hash, utils};
+
+use super::StateChunk;
+
+impl StateChunk {
+    /// The hash of the encoded chunk, which is listed in the snapshot manifest.
+    pub fn hash(&self) -> Hash {
+        utils::hash_message(&self.encode_to_vec())
+    }
+}
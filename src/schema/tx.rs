@@ -8,10 +8,11 @@ use crate::{
     utils,
 };
 
-use super::{SignedTx, Tx};
+use super::{SignedTx, Tx, TxReceipt};
 
 const GAS: u64 = 21;
 const GAS_PRICE: u64 = 1;
+const SIGNATURE_LEN: usize = 65;
 
 impl Tx {
     pub fn new(from: &str, to: &str, value: u64, nonce: u64) -> Self {
@@ -43,6 +44,74 @@ impl Tx {
     pub fn hash(&self) -> Hash {
         utils::hash_message(&self.as_bytes())
     }
+
+    /// The first 8 bytes of the hash, which identifies the tx in a compact block.
+    pub fn short_id(&self) -> u64 {
+        let hash = self.hash();
+        u64::from_le_bytes(hash[..8].try_into().unwrap())
+    }
+}
+
+impl TxReceipt {
+    pub fn new(tx: &Tx, block_hash: Hash, block_number: u64, index: u32) -> Self {
+        Self {
+            tx_hash: tx.hash().to_vec(),
+            block_hash: block_hash.to_vec(),
+            block_number,
+            index,
+            fee: tx.gas_cost(),
+        }
+    }
+}
+
+impl TryFrom<Vec<u8>> for TxReceipt {
+    type Error = Error;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Ok(Self::decode(value.as_slice())?)
+    }
+}
+
+impl From<&TxReceipt> for Vec<u8> {
+    fn from(value: &TxReceipt) -> Self {
+        value.encode_to_vec()
+    }
+}
+
+impl SignedTx {
+    /// Check that the tx is well-formed and signed by its sender, which is required before a tx
+    /// from the outside world is dereferenced or its cost is computed.
+    pub fn validate(&self) -> Result<(), Error> {
+        let Some(tx) = &self.tx else {
+            return Err(Error::InvalidTx("the tx is missing".to_string()));
+        };
+        if self.sig.len() != SIGNATURE_LEN {
+            return Err(Error::InvalidTx(format!(
+                "the signature has {} bytes instead of {SIGNATURE_LEN}",
+                self.sig.len()
+            )));
+        }
+        if tx
+            .gas
+            .checked_mul(tx.gas_price)
+            .and_then(|gas_cost| gas_cost.checked_add(tx.value))
+            .is_none()
+        {
+            return Err(Error::InvalidTx("the cost overflows".to_string()));
+        }
+
+        let sig: [u8; SIGNATURE_LEN] = Signature::from(self.sig.clone()).into();
+        let signer = wallet::recover_address(&tx.as_bytes(), sig)
+            .map_err(|err| Error::InvalidTx(err.to_string()))?;
+        if signer != tx.from {
+            return Err(Error::InvalidTx(format!(
+                "signed by '{signer}' instead of '{}'",
+                tx.from
+            )));
+        }
+
+        Ok(())
+    }
 }
 
 impl Deref for SignedTx {
@@ -96,4 +165,34 @@ mod tests {
         assert_eq!(tx.gas_cost(), 21);
         assert_eq!(tx.cost(), 121);
     }
+
+    #[test]
+    fn malformed_txs_are_invalid() {
+        let tx = Tx::new("from", "to", 100, 0);
+        let cases = [
+            SignedTx {
+                tx: None,
+                sig: vec![0; SIGNATURE_LEN],
+            },
+            SignedTx {
+                tx: Some(tx.clone()),
+                sig: vec![0; 64],
+            },
+            SignedTx {
+                tx: Some(Tx {
+                    value: u64::MAX,
+                    ..tx.clone()
+                }),
+                sig: vec![0; SIGNATURE_LEN],
+            },
+            SignedTx {
+                tx: Some(tx),
+                sig: vec![0; SIGNATURE_LEN],
+            },
+        ];
+
+        for signed_tx in cases {
+            assert!(matches!(signed_tx.validate(), Err(Error::InvalidTx(_))));
+        }
+    }
 }
@@ -19,6 +19,48 @@ pub struct BlockHeader {
     pub timestamp: u64,
     #[prost(string, tag = "5")]
     pub author: ::prost::alloc::string::String,
+    /// The hash of the transactions, which binds the body to the header.
+    #[prost(bytes = "vec", tag = "6")]
+    pub txs_hash: ::prost::alloc::vec::Vec<u8>,
+    /// The hash of the state after applying the block, see `StateChunk`.
+    #[prost(bytes = "vec", tag = "7")]
+    pub state_root: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BlockBody {
+    #[prost(message, repeated, tag = "1")]
+    pub txs: ::prost::alloc::vec::Vec<SignedTx>,
+}
+/// A block announced with short tx IDs instead of the txs, which peers most likely
+/// already have in their tx pools.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CompactBlock {
+    #[prost(message, optional, tag = "1")]
+    pub header: ::core::option::Option<BlockHeader>,
+    /// The first 8 bytes of the tx hashes, in the same order as the txs of the block.
+    #[prost(fixed64, repeated, tag = "2")]
+    pub short_tx_ids: ::prost::alloc::vec::Vec<u64>,
+}
+/// The state of an account, which is exported in the state snapshots.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AccountState {
+    #[prost(string, tag = "1")]
+    pub account: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub balance: u64,
+    #[prost(uint64, tag = "3")]
+    pub nonce: u64,
+}
+/// The state is split into chunks of accounts sorted by the address, and the state root
+/// is the hash of the concatenated chunk hashes.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StateChunk {
+    #[prost(message, repeated, tag = "1")]
+    pub accounts: ::prost::alloc::vec::Vec<AccountState>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -46,12 +88,29 @@ pub struct Tx {
     #[prost(uint64, tag = "7")]
     pub timestamp: u64,
 }
+/// The result of an included tx, which is stored by the tx hash.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TxReceipt {
+    #[prost(bytes = "vec", tag = "1")]
+    pub tx_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub block_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub block_number: u64,
+    /// The index of the tx in the block.
+    #[prost(uint32, tag = "4")]
+    pub index: u32,
+    /// The gas cost paid to the author of the block.
+    #[prost(uint64, tag = "5")]
+    pub fee: u64,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Request {
     #[prost(enumeration = "Method", tag = "1")]
     pub method: i32,
-    #[prost(oneof = "request::Body", tags = "2, 3")]
+    #[prost(oneof = "request::Body", tags = "2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12")]
     pub body: ::core::option::Option<request::Body>,
 }
 /// Nested message and enum types in `Request`.
@@ -63,6 +122,24 @@ pub mod request {
         BlockHeightReq(super::BlockHeightReq),
         #[prost(message, tag = "3")]
         BlocksReq(super::BlocksReq),
+        #[prost(message, tag = "4")]
+        HeadersReq(super::HeadersReq),
+        #[prost(message, tag = "5")]
+        BlockBodiesReq(super::BlockBodiesReq),
+        #[prost(message, tag = "6")]
+        StatusReq(super::StatusReq),
+        #[prost(message, tag = "7")]
+        PendingTxHashesReq(super::PendingTxHashesReq),
+        #[prost(message, tag = "8")]
+        PendingTxsReq(super::PendingTxsReq),
+        #[prost(message, tag = "9")]
+        BlockTxsReq(super::BlockTxsReq),
+        #[prost(message, tag = "10")]
+        SnapshotManifestReq(super::SnapshotManifestReq),
+        #[prost(message, tag = "11")]
+        SnapshotChunkReq(super::SnapshotChunkReq),
+        #[prost(message, tag = "12")]
+        AccountProofReq(super::AccountProofReq),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -70,7 +147,7 @@ pub mod request {
 pub struct Response {
     #[prost(enumeration = "Method", tag = "1")]
     pub method: i32,
-    #[prost(oneof = "response::Body", tags = "2, 3")]
+    #[prost(oneof = "response::Body", tags = "2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12")]
     pub body: ::core::option::Option<response::Body>,
 }
 /// Nested message and enum types in `Response`.
@@ -82,6 +159,24 @@ pub mod response {
         BlockHeightResp(super::BlockHeightResp),
         #[prost(message, tag = "3")]
         BlocksResp(super::BlocksResp),
+        #[prost(message, tag = "4")]
+        HeadersResp(super::HeadersResp),
+        #[prost(message, tag = "5")]
+        BlockBodiesResp(super::BlockBodiesResp),
+        #[prost(message, tag = "6")]
+        StatusResp(super::StatusResp),
+        #[prost(message, tag = "7")]
+        PendingTxHashesResp(super::PendingTxHashesResp),
+        #[prost(message, tag = "8")]
+        PendingTxsResp(super::PendingTxsResp),
+        #[prost(message, tag = "9")]
+        BlockTxsResp(super::BlockTxsResp),
+        #[prost(message, tag = "10")]
+        SnapshotManifestResp(super::SnapshotManifestResp),
+        #[prost(message, tag = "11")]
+        SnapshotChunkResp(super::SnapshotChunkResp),
+        #[prost(message, tag = "12")]
+        AccountProofResp(super::AccountProofResp),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -99,6 +194,15 @@ pub struct BlocksReq {
     /// Start with given block number.
     #[prost(uint64, tag = "2")]
     pub from_number: u64,
+    /// Stop at the given block number (inclusive), defaults to the tip (or 0 if `reverse`).
+    #[prost(uint64, optional, tag = "3")]
+    pub to_number: ::core::option::Option<u64>,
+    /// The maximum number of blocks to return, bounded by the server. 0 means the server maximum.
+    #[prost(uint64, tag = "4")]
+    pub limit: u64,
+    /// Walk towards the genesis block instead of the tip.
+    #[prost(bool, tag = "5")]
+    pub reverse: bool,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -106,12 +210,192 @@ pub struct BlocksResp {
     #[prost(message, repeated, tag = "1")]
     pub blocks: ::prost::alloc::vec::Vec<Block>,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HeadersReq {
+    /// Start with given block number.
+    #[prost(uint64, tag = "1")]
+    pub from_number: u64,
+    /// The maximum number of headers to return, bounded by the server.
+    #[prost(uint64, tag = "2")]
+    pub count: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HeadersResp {
+    #[prost(message, repeated, tag = "1")]
+    pub headers: ::prost::alloc::vec::Vec<BlockHeader>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BlockBodiesReq {
+    /// The hashes of the blocks, bounded by the server.
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub hashes: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BlockBodiesResp {
+    /// The bodies in the same order as the requested hashes.
+    /// The response stops at the first unknown block.
+    #[prost(message, repeated, tag = "1")]
+    pub bodies: ::prost::alloc::vec::Vec<BlockBody>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatusReq {}
+/// The status of a node, which is exchanged when peers are connected.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatusResp {
+    #[prost(uint32, tag = "1")]
+    pub protocol_version: u32,
+    /// Peers with a different chain ID or genesis hash are on another chain.
+    #[prost(string, tag = "2")]
+    pub chain_id: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "3")]
+    pub genesis_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub head_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "5")]
+    pub height: u64,
+    #[prost(uint64, tag = "6")]
+    pub total_work: u64,
+    /// A light node only has the headers, so it is never asked for blocks or proofs.
+    #[prost(bool, tag = "7")]
+    pub light: bool,
+    /// The first block the peer has, which is above 0 if it started from a snapshot.
+    #[prost(uint64, tag = "8")]
+    pub lowest_number: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PendingTxHashesReq {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PendingTxHashesResp {
+    /// The hashes of the pending txs, bounded by the server.
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub hashes: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PendingTxsReq {
+    /// The hashes of the pending txs, bounded by the server.
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub hashes: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PendingTxsResp {
+    /// The unknown txs are skipped.
+    #[prost(message, repeated, tag = "1")]
+    pub txs: ::prost::alloc::vec::Vec<SignedTx>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BlockTxsReq {
+    #[prost(bytes = "vec", tag = "1")]
+    pub block_hash: ::prost::alloc::vec::Vec<u8>,
+    /// The positions of the txs in the block, bounded by the server.
+    #[prost(uint32, repeated, tag = "2")]
+    pub indexes: ::prost::alloc::vec::Vec<u32>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BlockTxsResp {
+    /// The txs in the same order as the requested indexes, empty if the block is unknown.
+    #[prost(message, repeated, tag = "1")]
+    pub txs: ::prost::alloc::vec::Vec<SignedTx>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SnapshotManifestReq {}
+/// The latest state snapshot that a node serves.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SnapshotManifest {
+    /// The snapshot is the state after applying this block.
+    #[prost(uint64, tag = "1")]
+    pub number: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub block_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub chunk_hashes: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SnapshotManifestResp {
+    /// Missing if the node has no snapshot yet.
+    #[prost(message, optional, tag = "1")]
+    pub manifest: ::core::option::Option<SnapshotManifest>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SnapshotChunkReq {
+    #[prost(bytes = "vec", tag = "1")]
+    pub block_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint32, tag = "2")]
+    pub index: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SnapshotChunkResp {
+    /// Missing if the snapshot or the chunk is unknown.
+    #[prost(message, optional, tag = "1")]
+    pub chunk: ::core::option::Option<StateChunk>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AccountProofReq {
+    #[prost(string, tag = "1")]
+    pub account: ::prost::alloc::string::String,
+}
+/// A state chunk with its position in the state.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IndexedStateChunk {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(message, optional, tag = "2")]
+    pub chunk: ::core::option::Option<StateChunk>,
+}
+/// Proves the state of an account after applying a block, which is checked against the
+/// `state_root` of the block header.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AccountProof {
+    #[prost(bytes = "vec", tag = "1")]
+    pub block_hash: ::prost::alloc::vec::Vec<u8>,
+    /// All the chunk hashes of the state.
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub chunk_hashes: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+    /// The chunk that holds the account, or the neighbouring chunks that prove its absence.
+    #[prost(message, repeated, tag = "3")]
+    pub chunks: ::prost::alloc::vec::Vec<IndexedStateChunk>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AccountProofResp {
+    /// Missing if the node has no block yet.
+    #[prost(message, optional, tag = "1")]
+    pub proof: ::core::option::Option<AccountProof>,
+}
 /// Request/response methods.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum Method {
     Height = 0,
     Blocks = 1,
+    Headers = 2,
+    BlockBodies = 3,
+    Status = 4,
+    PendingTxHashes = 5,
+    PendingTxs = 6,
+    BlockTxs = 7,
+    SnapshotManifest = 8,
+    SnapshotChunk = 9,
+    AccountProof = 10,
 }
 impl Method {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -122,6 +406,15 @@ impl Method {
         match self {
             Method::Height => "HEIGHT",
             Method::Blocks => "BLOCKS",
+            Method::Headers => "HEADERS",
+            Method::BlockBodies => "BLOCK_BODIES",
+            Method::Status => "STATUS",
+            Method::PendingTxHashes => "PENDING_TX_HASHES",
+            Method::PendingTxs => "PENDING_TXS",
+            Method::BlockTxs => "BLOCK_TXS",
+            Method::SnapshotManifest => "SNAPSHOT_MANIFEST",
+            Method::SnapshotChunk => "SNAPSHOT_CHUNK",
+            Method::AccountProof => "ACCOUNT_PROOF",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
@@ -129,6 +422,15 @@ impl Method {
         match value {
             "HEIGHT" => Some(Self::Height),
             "BLOCKS" => Some(Self::Blocks),
+            "HEADERS" => Some(Self::Headers),
+            "BLOCK_BODIES" => Some(Self::BlockBodies),
+            "STATUS" => Some(Self::Status),
+            "PENDING_TX_HASHES" => Some(Self::PendingTxHashes),
+            "PENDING_TXS" => Some(Self::PendingTxs),
+            "BLOCK_TXS" => Some(Self::BlockTxs),
+            "SNAPSHOT_MANIFEST" => Some(Self::SnapshotManifest),
+            "SNAPSHOT_CHUNK" => Some(Self::SnapshotChunk),
+            "ACCOUNT_PROOF" => Some(Self::AccountProof),
             _ => None,
         }
     }
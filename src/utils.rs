@@ -3,11 +3,7 @@ use std::time::SystemTime;
 use rand::{thread_rng, Rng};
 use tiny_keccak::{Hasher, Keccak};
 
-use crate::{
-    error::Error,
-    schema::SignedTx,
-    types::{Hash, Signature},
-};
+use crate::types::Hash;
 
 pub fn unix_timestamp() -> u64 {
     SystemTime::now()
@@ -34,9 +30,3 @@ pub fn is_valid_hash(hash: &Hash, mining_difficulty: usize) -> bool {
     let hash_prefix = vec![0u8; mining_difficulty];
     hash_prefix[..] == hash[..mining_difficulty]
 }
-
-pub fn verify_tx_signature(tx: &SignedTx) -> Result<(), Error> {
-    let sig = Signature::from(tx.sig.clone());
-    wallet::verify_signature(&tx.as_bytes(), sig)?;
-    Ok(())
-}
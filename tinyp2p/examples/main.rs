@@ -1,7 +1,7 @@
 use std::{thread, time::Duration};
 
 use log::info;
-use tinyp2p::{config::P2pConfig, Client, EventHandler};
+use tinyp2p::{config::P2pConfig, Client, EventHandler, PeerId};
 use tokio::task;
 
 #[tokio::main]
@@ -13,7 +13,6 @@ async fn main() {
         pubsub_topics: vec!["block".to_string(), "tx".to_string()],
         ..Default::default()
     };
-    config.pubsub_topics = vec!["block".to_string(), "tx".to_string()];
     if let Some(addr) = std::env::args().nth(1) {
         config.boot_node = addr.parse().ok();
     }
@@ -28,6 +27,10 @@ async fn main() {
     let client_clone = client.clone();
     thread::spawn(move || get_node_status(client_clone));
 
+    // Print the peer connection lifecycle events.
+    let client_clone = client.clone();
+    thread::spawn(move || peer_events(client_clone));
+
     // Periodically send a request to one of the known peers.
     let client_clone = client.clone();
     thread::spawn(move || request(client_clone));
@@ -48,11 +51,12 @@ impl EventHandler for Handler {
         Ok(request)
     }
 
-    fn handle_broadcast(&self, topic: &str, message: Vec<u8>) {
+    fn handle_broadcast(&self, topic: &str, message: Vec<u8>, source: PeerId) {
         info!(
-            "📣 <<<< Inbound broadcast: {:?} {:?}",
+            "📣 <<<< Inbound broadcast: {:?} {:?} from {}",
             topic,
-            String::from_utf8_lossy(message.as_slice())
+            String::from_utf8_lossy(message.as_slice()),
+            source
         );
     }
 }
@@ -66,12 +70,19 @@ fn get_node_status(client: Client) {
     }
 }
 
+fn peer_events(client: Client) {
+    let mut events = client.peer_events();
+    while let Ok(event) = events.blocking_recv() {
+        info!("📣 Peer event: {:?}", event);
+    }
+}
+
 fn request(client: Client) {
     let dur = Duration::from_secs(11);
     loop {
         thread::sleep(dur);
         let known_peers = client.get_known_peers();
-        if known_peers.len() > 0 {
+        if !known_peers.is_empty() {
             let target = &known_peers[0];
             let request = "Hello, request!";
             info!("📣 >>>> Outbound request: {:?}", request);
@@ -93,6 +104,8 @@ fn broadcast(client: Client) {
         let topic = "block";
         let message = "Hello, a new block!";
         info!("📣 >>>> Outbound broadcast: {:?} {:?}", topic, message);
-        let _ = client.broadcast(topic, message.as_bytes().to_vec());
+        if let Err(err) = client.broadcast(topic, message.as_bytes().to_vec()) {
+            info!("📣 >>>> Outbound broadcast failed: {:?}", err);
+        }
     }
 }
@@ -25,6 +25,10 @@ pub struct P2pConfig {
     pub pubsub_topics: Vec<String>,
     /// Configuration for the request-response protocol.
     pub req_resp: Option<ReqRespConfig>,
+    /// Configuration for connection limits and inbound rate limiting.
+    pub limits: Option<LimitsConfig>,
+    /// Configuration for NAT traversal.
+    pub nat: Option<NatConfig>,
 }
 
 /// Configuration for the request-response protocol.
@@ -40,6 +44,44 @@ pub struct ReqRespConfig {
     pub max_response_size: Option<usize>,
 }
 
+/// Configuration for connection limits and inbound rate limiting.
+/// Any limit that is not provided is unlimited.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct LimitsConfig {
+    /// Maximum number of established inbound connections.
+    pub max_inbound_connections: Option<u32>,
+    /// Maximum number of established outbound connections.
+    pub max_outbound_connections: Option<u32>,
+    /// Maximum number of established connections with a single peer.
+    pub max_connections_per_peer: Option<u32>,
+    /// Maximum number of inbound requests per second from a single peer.
+    /// Unlimited if it's not set or 0.
+    pub requests_per_second: Option<u32>,
+    /// Maximum burst of inbound requests from a single peer.
+    /// Defaults to `requests_per_second`.
+    pub request_burst: Option<u32>,
+}
+
+/// Configuration for NAT traversal.
+///
+/// The public reachability of the node is determined by AutoNAT. A node behind a NAT
+/// can be reached through the relay node, and will try to upgrade the relayed
+/// connections to direct connections by hole punching (DCUtR).
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct NatConfig {
+    /// The circuit relay v2 node to reserve a relayed address on.
+    pub relay_node: Option<PeerIdWithMultiaddr>,
+    /// Whether to act as a circuit relay v2 server for other peers. Defaults to `false`.
+    pub enable_relay_server: Option<bool>,
+    /// Addresses that are known to be publicly reachable, e.g. the public address of a relay
+    /// server. Other external addresses are only used once confirmed by AutoNAT.
+    #[serde(default)]
+    pub external_addresses: Vec<String>,
+    /// Whether AutoNAT only accepts global IP addresses. Defaults to `true`,
+    /// disable it for testing in a local network.
+    pub autonat_only_global_ips: Option<bool>,
+}
+
 impl P2pConfig {
     /// Generate a keypair from the secret.
     pub fn gen_keypair(&self) -> Result<Keypair, P2pError> {
@@ -157,12 +199,6 @@ mod tests {
     }
 
     fn secret_bytes(kp: Keypair) -> Vec<u8> {
-        kp.try_into_ed25519()
-            .unwrap()
-            .secret()
-            .as_ref()
-            .iter()
-            .cloned()
-            .collect()
+        kp.try_into_ed25519().unwrap().secret().as_ref().to_vec()
     }
 }
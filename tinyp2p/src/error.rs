@@ -25,4 +25,6 @@ pub enum P2pError {
     SubscribeError(#[from] gossipsub::SubscriptionError),
     #[error(transparent)]
     PublishError(#[from] gossipsub::PublishError),
+    #[error("Not subscribed to topic: {0}")]
+    TopicNotSubscribed(String),
 }
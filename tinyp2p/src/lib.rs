@@ -6,12 +6,15 @@ pub mod config;
 pub mod error;
 
 mod protocol;
+mod rate_limiter;
 mod service;
 mod transport;
 
 pub use config::*;
 pub use error::P2pError;
-pub use service::{new, new_secret_key, Client, EventHandler, Server};
+pub use service::{
+    new, new_secret_key, Client, EventHandler, NodeStatus, PeerEvent, PeerInfo, Server,
+};
 
 // Re-export libp2p types.
 pub use libp2p::{Multiaddr, PeerId};
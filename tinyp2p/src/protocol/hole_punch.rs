@@ -0,0 +1,161 @@
+use std::{
+    collections::HashSet,
+    task::{Context, Poll},
+};
+
+use libp2p::{
+    core::Endpoint,
+    dcutr,
+    swarm::{
+        behaviour::{ExternalAddrConfirmed, ExternalAddrExpired},
+        ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, PollParameters, THandler,
+        THandlerInEvent, THandlerOutEvent, ToSwarm,
+    },
+    Multiaddr, PeerId,
+};
+
+/// DCUtR, which also punches holes with the candidate addresses given to it.
+///
+/// DCUtR only sends the confirmed external addresses of the swarm to the remote peer, but when we
+/// are behind a NAT, the address observed by the relay node is what the remote peer needs. That
+/// address is not reachable directly, so it's only given to DCUtR instead of being confirmed,
+/// which would make identify and kad advertise it as well.
+pub struct Behaviour {
+    inner: dcutr::Behaviour,
+    /// The addresses that DCUtR is told about, but the swarm is not.
+    candidates: HashSet<Multiaddr>,
+}
+
+impl Behaviour {
+    pub fn new(local_peer_id: PeerId) -> Self {
+        Self {
+            inner: dcutr::Behaviour::new(local_peer_id),
+            candidates: HashSet::new(),
+        }
+    }
+
+    pub fn add_candidate(&mut self, addr: Multiaddr) {
+        if self.candidates.insert(addr.clone()) {
+            self.inner
+                .on_swarm_event(FromSwarm::ExternalAddrConfirmed(ExternalAddrConfirmed {
+                    addr: &addr,
+                }));
+        }
+    }
+
+    pub fn clear_candidates(&mut self) {
+        for addr in self.candidates.drain() {
+            self.inner
+                .on_swarm_event(FromSwarm::ExternalAddrExpired(ExternalAddrExpired {
+                    addr: &addr,
+                }));
+        }
+    }
+
+    pub fn candidates(&self) -> impl Iterator<Item = &Multiaddr> {
+        self.candidates.iter()
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = THandler<dcutr::Behaviour>;
+    type ToSwarm = dcutr::Event;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.inner
+            .handle_pending_inbound_connection(connection_id, local_addr, remote_addr)
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner.handle_established_inbound_connection(
+            connection_id,
+            peer,
+            local_addr,
+            remote_addr,
+        )
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        addresses: &[Multiaddr],
+        effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        self.inner.handle_pending_outbound_connection(
+            connection_id,
+            maybe_peer,
+            addresses,
+            effective_role,
+        )
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner
+            .handle_established_outbound_connection(connection_id, peer, addr, role_override)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
+        // A candidate stays with DCUtR even if the swarm no longer has it as an external address.
+        if let FromSwarm::ExternalAddrExpired(ExternalAddrExpired { addr }) = &event {
+            if self.candidates.contains(*addr) {
+                return;
+            }
+        }
+
+        self.inner.on_swarm_event(event)
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        self.inner
+            .on_connection_handler_event(peer_id, connection_id, event)
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        params: &mut impl PollParameters,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        self.inner.poll(cx, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candidates_are_added_once_and_cleared() {
+        let mut behaviour = Behaviour::new(PeerId::random());
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+
+        behaviour.add_candidate(addr.clone());
+        behaviour.add_candidate(addr.clone());
+        assert_eq!(behaviour.candidates().collect::<Vec<_>>(), vec![&addr]);
+
+        behaviour.clear_candidates();
+        assert_eq!(behaviour.candidates().count(), 0);
+    }
+}
@@ -1,38 +1,44 @@
 use std::{
     collections::{hash_map::DefaultHasher, HashMap},
     hash::{Hash, Hasher},
-    io,
     net::IpAddr,
     time::Duration,
 };
 
-use either::Either;
 use libp2p::{
-    gossipsub::{self, IdentTopic},
+    autonat,
+    connection_limits::{self, ConnectionLimits},
+    gossipsub::{self, IdentTopic, TopicHash},
     identify,
     identity::Keypair,
-    kad::{store::MemoryStore, Kademlia},
+    kad::{store::MemoryStore, Kademlia, Mode},
     multiaddr::Protocol,
-    ping,
+    ping, relay,
     request_response::{self, RequestId, ResponseChannel},
-    swarm::NetworkBehaviour,
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour, THandlerErr},
     Multiaddr, PeerId,
 };
 use log::debug;
-use void::Void;
 
-use crate::{config::ReqRespConfig, error::P2pError};
+use crate::{
+    config::{LimitsConfig, NatConfig, ReqRespConfig},
+    error::P2pError,
+};
 
+mod hole_punch;
 mod req_resp;
 
 use req_resp::GenericCodec;
 pub use req_resp::ResponseType;
 
 /// BehaviourErr combines all possible errors generated by the subbehaviours.
-pub type BehaviourErr = Either<Either<Either<Either<io::Error, io::Error>, Void>, Void>, Void>;
+pub type BehaviourErr = THandlerErr<Behaviour>;
 
 #[derive(NetworkBehaviour)]
 pub struct Behaviour {
+    // `limits` is used for denying connections that exceed the configured limits.
+    limits: connection_limits::Behaviour,
+
     // `kad`, `identify`, and `ping` are used for peer discovery.
     kad: Kademlia<MemoryStore>,
     identify: identify::Behaviour,
@@ -43,18 +49,31 @@ pub struct Behaviour {
 
     // `pubsub` is used for broadcasting messages.
     pubsub: gossipsub::Behaviour,
+
+    // `autonat`, `relay_client`, `relay_server` and `dcutr` are used for NAT traversal.
+    autonat: autonat::Behaviour,
+    relay_client: relay::client::Behaviour,
+    relay_server: Toggle<relay::Behaviour>,
+    dcutr: hole_punch::Behaviour,
 }
 
 impl Behaviour {
     pub fn new(
         local_key: Keypair,
-        pubsub_topics: Vec<String>,
+        relay_client: relay::client::Behaviour,
         req_resp_config: Option<ReqRespConfig>,
+        limits_config: Option<LimitsConfig>,
+        nat_config: Option<NatConfig>,
     ) -> Result<Self, P2pError> {
         let local_pubkey = local_key.public();
         let local_id = local_pubkey.to_peer_id();
+        let nat_config = nat_config.unwrap_or_default();
 
-        let kad_behaviour = Kademlia::new(local_id, MemoryStore::new(local_id));
+        // The external addresses are no longer assumed, so we need to switch to server mode
+        // explicitly, otherwise the DHT would only work in client mode until AutoNAT confirms
+        // a public address.
+        let mut kad_behaviour = Kademlia::new(local_id, MemoryStore::new(local_id));
+        kad_behaviour.set_mode(Some(Mode::Server));
 
         let id_behaviour = identify::Behaviour::new(identify::Config::new(
             "/tinychain/identify/1.0.0".to_string(),
@@ -62,11 +81,20 @@ impl Behaviour {
         ));
 
         Ok(Self {
+            limits: Self::new_connection_limits(limits_config),
             kad: kad_behaviour,
             identify: id_behaviour,
             ping: ping::Behaviour::default(),
             req_resp: Self::new_req_resp(req_resp_config),
-            pubsub: Self::new_gossipsub(local_key, pubsub_topics)?,
+            pubsub: Self::new_gossipsub(local_key)?,
+            autonat: Self::new_autonat(local_id, &nat_config),
+            relay_client,
+            relay_server: nat_config
+                .enable_relay_server
+                .unwrap_or(false)
+                .then(|| relay::Behaviour::new(local_id, Default::default()))
+                .into(),
+            dcutr: hole_punch::Behaviour::new(local_id),
         })
     }
 
@@ -99,12 +127,27 @@ impl Behaviour {
     }
 
     pub fn broadcast(&mut self, topic: String, message: Vec<u8>) -> Result<(), P2pError> {
-        let topic = gossipsub::IdentTopic::new(topic);
+        let topic = IdentTopic::new(topic);
         self.pubsub.publish(topic, message)?;
 
         Ok(())
     }
 
+    /// Subscribe to the topic, returns the hash of the topic.
+    pub fn subscribe(&mut self, topic: &str) -> Result<TopicHash, P2pError> {
+        let topic = IdentTopic::new(topic);
+        self.pubsub.subscribe(&topic)?;
+
+        Ok(topic.hash())
+    }
+
+    pub fn unsubscribe(&mut self, topic: &str) -> Result<(), P2pError> {
+        let topic = IdentTopic::new(topic);
+        self.pubsub.unsubscribe(&topic)?;
+
+        Ok(())
+    }
+
     pub fn add_address(&mut self, peer_id: &PeerId, addr: Multiaddr) {
         if can_add_to_dht(&addr) {
             debug!("☕ Adding address {} from {:?} to the DHT.", addr, peer_id);
@@ -117,6 +160,34 @@ impl Behaviour {
         self.kad.remove_peer(peer_id);
     }
 
+    /// Use the peer (e.g. the relay node) as a server for AutoNAT probes.
+    pub fn add_autonat_server(&mut self, peer_id: PeerId, addr: Multiaddr) {
+        self.autonat.add_server(peer_id, Some(addr));
+    }
+
+    /// Returns the public address confirmed by AutoNAT, if any.
+    pub fn public_address(&self) -> Option<Multiaddr> {
+        self.autonat.public_address().cloned()
+    }
+
+    /// Punch holes with the address, without advertising it as an external address.
+    pub fn add_hole_punch_candidate(&mut self, addr: Multiaddr) {
+        self.dcutr.add_candidate(addr);
+    }
+
+    pub fn clear_hole_punch_candidates(&mut self) {
+        self.dcutr.clear_candidates();
+    }
+
+    pub fn hole_punch_candidates(&self) -> Vec<Multiaddr> {
+        self.dcutr.candidates().cloned().collect()
+    }
+
+    /// Returns `true` if AutoNAT has determined that we are behind a NAT.
+    pub fn is_private(&self) -> bool {
+        self.autonat.nat_status() == autonat::NatStatus::Private
+    }
+
     fn new_req_resp(config: Option<ReqRespConfig>) -> request_response::Behaviour<GenericCodec> {
         if let Some(config) = config {
             return req_resp::BehaviourBuilder::new()
@@ -130,10 +201,26 @@ impl Behaviour {
         req_resp::BehaviourBuilder::default().build()
     }
 
-    fn new_gossipsub(
-        local_key: Keypair,
-        topics: Vec<String>,
-    ) -> Result<gossipsub::Behaviour, P2pError> {
+    fn new_autonat(local_id: PeerId, config: &NatConfig) -> autonat::Behaviour {
+        let autonat_config = autonat::Config {
+            only_global_ips: config.autonat_only_global_ips.unwrap_or(true),
+            ..Default::default()
+        };
+
+        autonat::Behaviour::new(local_id, autonat_config)
+    }
+
+    fn new_connection_limits(config: Option<LimitsConfig>) -> connection_limits::Behaviour {
+        let config = config.unwrap_or_default();
+        let limits = ConnectionLimits::default()
+            .with_max_established_incoming(config.max_inbound_connections)
+            .with_max_established_outgoing(config.max_outbound_connections)
+            .with_max_established_per_peer(config.max_connections_per_peer);
+
+        connection_limits::Behaviour::new(limits)
+    }
+
+    fn new_gossipsub(local_key: Keypair) -> Result<gossipsub::Behaviour, P2pError> {
         let message_id_fn = |message: &gossipsub::Message| {
             let mut s = DefaultHasher::new();
             message.data.hash(&mut s);
@@ -147,18 +234,11 @@ impl Behaviour {
             .build()
             .map_err(|err| P2pError::PubsubBuildError(err.to_string()))?;
 
-        let mut gossipsub = gossipsub::Behaviour::new(
+        gossipsub::Behaviour::new(
             gossipsub::MessageAuthenticity::Signed(local_key),
             gossipsub_config,
         )
-        .map_err(|err| P2pError::PubsubBuildError(err.to_string()))?;
-
-        for t in topics {
-            let topic = IdentTopic::new(t);
-            gossipsub.subscribe(&topic)?;
-        }
-
-        Ok(gossipsub)
+        .map_err(|err| P2pError::PubsubBuildError(err.to_string()))
     }
 }
 
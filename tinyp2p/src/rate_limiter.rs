@@ -0,0 +1,118 @@
+//! A per-peer [token bucket](https://en.wikipedia.org/wiki/Token_bucket) rate limiter,
+//! which is used to limit the inbound requests from each remote peer.
+
+use std::{collections::HashMap, time::Instant};
+
+use libp2p::PeerId;
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// The number of tokens added to a bucket per second.
+    rate: f64,
+    /// The capacity of a bucket, i.e. the maximum burst of requests.
+    burst: f64,
+    /// The buckets of the remote peers.
+    buckets: HashMap<PeerId, Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Create a new `RateLimiter`, or `None` if `requests_per_second` is 0, which means
+    /// unlimited, as a zero rate would never refill the buckets.
+    /// If `burst` is not provided, it defaults to `requests_per_second`.
+    pub fn new(requests_per_second: u32, burst: Option<u32>) -> Option<Self> {
+        if requests_per_second == 0 {
+            return None;
+        }
+        let burst = burst.unwrap_or(requests_per_second).max(1);
+
+        Some(Self {
+            rate: requests_per_second as f64,
+            burst: burst as f64,
+            buckets: HashMap::new(),
+        })
+    }
+
+    /// Try to take a token from the bucket of the given peer.
+    /// Returns `false` if the peer has exceeded the rate limit.
+    pub fn try_acquire(&mut self, peer: &PeerId) -> bool {
+        self.try_acquire_at(peer, Instant::now())
+    }
+
+    /// Forget the bucket of the given peer, e.g. when the peer is disconnected.
+    pub fn remove_peer(&mut self, peer: &PeerId) {
+        self.buckets.remove(peer);
+    }
+
+    fn try_acquire_at(&mut self, peer: &PeerId, now: Instant) -> bool {
+        let bucket = self.buckets.entry(*peer).or_insert(Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.rate).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn rate_limiter_allows_burst_then_rejects() {
+        let mut limiter = RateLimiter::new(1, Some(3)).unwrap();
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire_at(&peer, now));
+        assert!(limiter.try_acquire_at(&peer, now));
+        assert!(limiter.try_acquire_at(&peer, now));
+        assert!(!limiter.try_acquire_at(&peer, now));
+
+        // Another peer has its own bucket.
+        assert!(limiter.try_acquire_at(&PeerId::random(), now));
+    }
+
+    #[test]
+    fn rate_limiter_refills_over_time() {
+        let mut limiter = RateLimiter::new(2, None).unwrap();
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire_at(&peer, now));
+        assert!(limiter.try_acquire_at(&peer, now));
+        assert!(!limiter.try_acquire_at(&peer, now));
+
+        let later = now + Duration::from_millis(500);
+        assert!(limiter.try_acquire_at(&peer, later));
+        assert!(!limiter.try_acquire_at(&peer, later));
+
+        // The bucket never holds more than `burst` tokens.
+        let much_later = later + Duration::from_secs(60);
+        assert!(limiter.try_acquire_at(&peer, much_later));
+        assert!(limiter.try_acquire_at(&peer, much_later));
+        assert!(!limiter.try_acquire_at(&peer, much_later));
+    }
+
+    #[test]
+    fn zero_rate_is_unlimited() {
+        assert!(RateLimiter::new(0, Some(5)).is_none());
+        assert!(RateLimiter::new(0, None).is_none());
+    }
+}
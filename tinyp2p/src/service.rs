@@ -8,31 +8,45 @@
 //!
 //! - `EventHandler`: the trait that defines how to handle requests / broadcast-messages from remote peers.
 //!   The application should implement this trait and pass it to the `Server`.
+//!
+//! Besides the `EventHandler`, the application can also receive the `PeerEvent`s through the `Client`.
 
-use std::{cell::OnceCell, collections::HashMap, fmt::Debug, io, time::Duration};
+use std::{cell::OnceCell, collections::HashMap, fmt::Debug, io, num::NonZeroU32, time::Duration};
 
 use itertools::Itertools;
 use libp2p::{
+    autonat::{self, NatStatus},
+    connection_limits,
+    core::transport::ListenerId,
+    dcutr,
     futures::prelude::*,
     gossipsub::{self, TopicHash},
     identify,
     identity::ed25519,
-    ping,
+    multiaddr::Protocol,
+    ping, relay,
     request_response::{self, OutboundFailure, RequestId, ResponseChannel},
-    swarm::{SwarmBuilder, SwarmEvent},
+    swarm::{ConnectionDenied, DialError, ListenError, SwarmBuilder, SwarmEvent},
     Multiaddr, PeerId, Swarm,
 };
 use log::{error, info, warn};
 use tokio::{
     select,
     sync::{
+        broadcast,
         mpsc::{self, UnboundedReceiver, UnboundedSender},
         oneshot,
     },
     time::{self, Interval},
 };
 
-use crate::{config::P2pConfig, error::P2pError, protocol::*, transport};
+use crate::{
+    config::{P2pConfig, PeerIdWithMultiaddr},
+    error::P2pError,
+    protocol::*,
+    rate_limiter::RateLimiter,
+    transport,
+};
 
 /// `EventHandler` is the trait that defines how to handle requests / broadcast-messages from remote peers.
 pub trait EventHandler: Debug + Send + 'static {
@@ -40,12 +54,26 @@ pub trait EventHandler: Debug + Send + 'static {
     fn handle_inbound_request(&self, request: Vec<u8>) -> Result<Vec<u8>, P2pError>;
 
     /// Handles an broadcast message from a remote peer.
-    fn handle_broadcast(&self, topic: &str, message: Vec<u8>);
+    /// The `source` is the peer that forwarded the message to us, which is not necessarily
+    /// the original publisher.
+    fn handle_broadcast(&self, topic: &str, message: Vec<u8>, source: PeerId);
+
+    /// Called when a remote peer is connected and identified.
+    fn on_peer_connected(&self, _info: &PeerInfo) {}
+
+    /// Called when all connections to an identified remote peer are closed.
+    fn on_peer_disconnected(&self, _peer_id: &PeerId) {}
 }
 
+/// The capacity of the `PeerEvent` channel. Slow receivers will miss the oldest events.
+const PEER_EVENT_CAPACITY: usize = 64;
+/// The maximum time to wait for the connections to be closed when shutting down.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Clone, Debug)]
 pub struct Client {
     cmd_sender: UnboundedSender<Command>,
+    peer_event_sender: broadcast::Sender<PeerEvent>,
 }
 
 pub struct Server<E: EventHandler> {
@@ -54,6 +82,8 @@ pub struct Server<E: EventHandler> {
 
     /// The local peer id.
     local_peer_id: PeerId,
+    /// The listeners of the configured address and the relayed address.
+    listener_ids: Vec<ListenerId>,
     /// The addresses that the server is listening on.
     listened_addresses: Vec<Multiaddr>,
     /// The receiver of commands from the client.
@@ -65,9 +95,24 @@ pub struct Server<E: EventHandler> {
     discovery_ticker: Interval,
     /// The pending outbound requests, awaiting for a response from the remote.
     pending_outbound_requests: HashMap<RequestId, oneshot::Sender<ResponseType>>,
+    /// The connected peers, the value is `None` until the peer is identified.
+    connected_peers: HashMap<PeerId, Option<PeerInfo>>,
+    /// The sender of peer connection lifecycle events to the `Client`s.
+    peer_event_sender: broadcast::Sender<PeerEvent>,
+    /// The relay node to reserve a relayed address on.
+    relay_node: Option<PeerIdWithMultiaddr>,
+    /// Our address as observed by the relay node.
+    relay_observed_addr: Option<Multiaddr>,
     /// The topics will be hashed when subscribing to the gossipsub protocol,
-    /// but we need to keep the original topic names for broadcasting.
-    pubsub_topics: Vec<String>,
+    /// but we need to keep the original topic names for the `EventHandler`.
+    pubsub_topics: HashMap<TopicHash, String>,
+
+    /// The limiter of inbound requests from each remote peer.
+    rate_limiter: Option<RateLimiter>,
+    /// The number of connections denied due to the connection limits.
+    rejected_connections: u64,
+    /// The number of inbound requests rejected due to the rate limit.
+    rejected_requests: u64,
 }
 
 /// Create a new secret key for the p2p node.
@@ -79,9 +124,13 @@ pub fn new_secret_key() -> String {
 /// Create a new p2p node, which consists of a `Client` and a `Server`.
 pub fn new<E: EventHandler>(config: P2pConfig) -> Result<(Client, Server<E>), P2pError> {
     let (cmd_sender, cmd_receiver) = mpsc::unbounded_channel();
+    let (peer_event_sender, _) = broadcast::channel(PEER_EVENT_CAPACITY);
 
-    let server = Server::new(config, cmd_receiver)?;
-    let client = Client { cmd_sender };
+    let server = Server::new(config, cmd_receiver, peer_event_sender.clone())?;
+    let client = Client {
+        cmd_sender,
+        peer_event_sender,
+    };
 
     Ok((client, server))
 }
@@ -103,11 +152,44 @@ impl Client {
     }
 
     /// Publish a message to the given topic.
-    pub fn broadcast(&self, topic: impl Into<String>, message: Vec<u8>) {
+    /// The node must be subscribed to the topic.
+    pub fn broadcast(&self, topic: impl Into<String>, message: Vec<u8>) -> Result<(), P2pError> {
+        let (responder, receiver) = oneshot::channel();
         let _ = self.cmd_sender.send(Command::Broadcast {
             topic: topic.into(),
             message,
+            responder,
+        });
+        receiver.blocking_recv()?
+    }
+
+    /// Subscribe to the given topic.
+    /// Subscribing to a topic that is already subscribed to has no effect.
+    pub fn subscribe(&self, topic: impl Into<String>) -> Result<(), P2pError> {
+        let (responder, receiver) = oneshot::channel();
+        let _ = self.cmd_sender.send(Command::Subscribe {
+            topic: topic.into(),
+            responder,
         });
+        receiver.blocking_recv()?
+    }
+
+    /// Unsubscribe from the given topic.
+    pub fn unsubscribe(&self, topic: impl Into<String>) -> Result<(), P2pError> {
+        let (responder, receiver) = oneshot::channel();
+        let _ = self.cmd_sender.send(Command::Unsubscribe {
+            topic: topic.into(),
+            responder,
+        });
+        receiver.blocking_recv()?
+    }
+
+    /// Close all connections to the given peer, and remove it from the routing table.
+    /// The peer may still be rediscovered and connected later.
+    pub fn disconnect(&self, peer_id: &str) -> Result<(), P2pError> {
+        let peer_id = peer_id.parse().map_err(|_| P2pError::InvalidPeerId)?;
+        let _ = self.cmd_sender.send(Command::Disconnect(peer_id));
+        Ok(())
     }
 
     /// Get known peers of the node.
@@ -125,6 +207,12 @@ impl Client {
         let _ = self.cmd_sender.send(Command::GetStatus(responder));
         receiver.blocking_recv().unwrap_or_default()
     }
+
+    /// Subscribe to the peer connection lifecycle events.
+    /// Only the events that occur after subscribing will be received.
+    pub fn peer_events(&self) -> broadcast::Receiver<PeerEvent> {
+        self.peer_event_sender.subscribe()
+    }
 }
 
 /// The commands sent by the `Client` to the `Server`.
@@ -137,7 +225,17 @@ pub enum Command {
     Broadcast {
         topic: String,
         message: Vec<u8>,
+        responder: oneshot::Sender<Result<(), P2pError>>,
+    },
+    Subscribe {
+        topic: String,
+        responder: oneshot::Sender<Result<(), P2pError>>,
+    },
+    Unsubscribe {
+        topic: String,
+        responder: oneshot::Sender<Result<(), P2pError>>,
     },
+    Disconnect(PeerId),
     GetStatus(oneshot::Sender<NodeStatus>),
 }
 
@@ -146,26 +244,64 @@ impl<E: EventHandler> Server<E> {
     pub fn new(
         config: P2pConfig,
         cmd_receiver: UnboundedReceiver<Command>,
+        peer_event_sender: broadcast::Sender<PeerEvent>,
     ) -> Result<Self, P2pError> {
         let addr: Multiaddr = config.addr.parse()?;
         let local_key = config.gen_keypair()?;
         let local_peer_id = local_key.public().to_peer_id();
         info!("📣 Local peer id: {local_peer_id:?}");
 
-        let pubsub_topics = config.pubsub_topics;
+        let rate_limiter = config.limits.as_ref().and_then(|limits| {
+            limits
+                .requests_per_second
+                .and_then(|rate| RateLimiter::new(rate, limits.request_burst))
+        });
+
         // Build the [swarm](https://docs.rs/libp2p/latest/libp2p/struct.Swarm.html)
+        let nat_config = config.nat.unwrap_or_default();
         let mut swarm = {
-            let transport = transport::build_transport(local_key.clone());
-            let behaviour = Behaviour::new(local_key, pubsub_topics.clone(), config.req_resp)?;
+            let (relay_transport, relay_client) = relay::client::new(local_peer_id);
+            let transport = transport::build_transport(local_key.clone(), relay_transport);
+            let behaviour = Behaviour::new(
+                local_key,
+                relay_client,
+                config.req_resp,
+                config.limits,
+                Some(nat_config.clone()),
+            )?;
             SwarmBuilder::with_tokio_executor(transport, behaviour, local_peer_id).build()
         };
-        // Switch to server mode.
-        swarm.add_external_address(addr.clone());
-        swarm.listen_on(addr)?;
+        let mut listener_ids = vec![swarm.listen_on(addr)?];
+
+        // The addresses that are known to be publicly reachable.
+        for external_addr in nat_config.external_addresses {
+            swarm.add_external_address(external_addr.parse()?);
+        }
+
+        // Listen on a relayed address so that we can be reached through the relay node,
+        // and use the relay node to probe our public reachability.
+        if let Some(relay_node) = &nat_config.relay_node {
+            let relayed_addr = relay_node
+                .address()
+                .with(Protocol::P2p(relay_node.peer_id()))
+                .with(Protocol::P2pCircuit);
+            swarm
+                .behaviour_mut()
+                .add_autonat_server(relay_node.peer_id(), relay_node.address());
+            listener_ids.push(swarm.listen_on(relayed_addr)?);
+        }
+
+        // Subscribe to the configured topics.
+        let mut pubsub_topics = HashMap::new();
+        for topic in config.pubsub_topics {
+            let topic_hash = swarm.behaviour_mut().subscribe(&topic)?;
+            pubsub_topics.insert(topic_hash, topic);
+        }
 
         // Connect to the boot node if specified.
+        // The peer id is part of the address, as a relayed address must end with it.
         if let Some(boot_node) = config.boot_node {
-            swarm.dial(boot_node.address())?;
+            swarm.dial(boot_node.address().with(Protocol::P2p(boot_node.peer_id())))?;
         }
 
         // Create a ticker to periodically discover new peers.
@@ -176,12 +312,20 @@ impl<E: EventHandler> Server<E> {
         Ok(Self {
             network_service: swarm,
             local_peer_id,
+            listener_ids,
             listened_addresses: Vec::new(),
             cmd_receiver,
             event_handler: OnceCell::new(),
             discovery_ticker,
             pending_outbound_requests: HashMap::new(),
+            connected_peers: HashMap::new(),
+            peer_event_sender,
+            relay_node: nat_config.relay_node,
+            relay_observed_addr: None,
             pubsub_topics,
+            rate_limiter,
+            rejected_connections: 0,
+            rejected_requests: 0,
         })
     }
 
@@ -190,10 +334,23 @@ impl<E: EventHandler> Server<E> {
         self.event_handler.set(handler).unwrap();
     }
 
-    /// Run the `Server`.
-    pub async fn run(mut self) {
+    /// Run the `Server` forever.
+    pub async fn run(self) {
+        self.run_with_shutdown(future::pending()).await
+    }
+
+    /// Run the `Server` until the `signal` completes, then close all connections gracefully.
+    pub async fn run_with_shutdown(mut self, signal: impl Future<Output = ()>) {
+        tokio::pin!(signal);
+
         loop {
             select! {
+                // Time to shut down.
+                _ = &mut signal => {
+                    return self.shutdown().await;
+                },
+
+
                 // Next discovery process.
                 _ = self.discovery_ticker.tick() => {
                     self.network_service.behaviour_mut().discover_peers();
@@ -213,6 +370,32 @@ impl<E: EventHandler> Server<E> {
         }
     }
 
+    // Stop listening and close all connections. The connections are closed asynchronously,
+    // so we keep driving the `Swarm` until all of them are closed or the timeout is reached.
+    async fn shutdown(&mut self) {
+        info!("📣 Shutting down the p2p server");
+        for listener_id in self.listener_ids.drain(..) {
+            self.network_service.remove_listener(listener_id);
+        }
+
+        let peers: Vec<PeerId> = self.network_service.connected_peers().cloned().collect();
+        for peer in peers {
+            let _ = self.network_service.disconnect_peer_id(peer);
+        }
+
+        let closing = async {
+            while self.network_service.connected_peers().next().is_some() {
+                let event = self.network_service.select_next_some().await;
+                self.handle_swarm_event(event);
+            }
+        };
+        if time::timeout(SHUTDOWN_TIMEOUT, closing).await.is_err() {
+            warn!("❗ Timed out waiting for the connections to be closed");
+        }
+
+        info!("📣 P2P server stopped");
+    }
+
     // Process the next command coming from `Client`.
     fn handle_command(&mut self, cmd: Command) {
         match cmd {
@@ -221,7 +404,20 @@ impl<E: EventHandler> Server<E> {
                 request,
                 responder,
             } => self.handle_outbound_request(target, request, responder),
-            Command::Broadcast { topic, message } => self.handle_outbound_broadcast(topic, message),
+            Command::Broadcast {
+                topic,
+                message,
+                responder,
+            } => {
+                let _ = responder.send(self.handle_outbound_broadcast(topic, message));
+            }
+            Command::Subscribe { topic, responder } => {
+                let _ = responder.send(self.subscribe(topic));
+            }
+            Command::Unsubscribe { topic, responder } => {
+                let _ = responder.send(self.unsubscribe(topic));
+            }
+            Command::Disconnect(peer_id) => self.disconnect(peer_id),
             Command::GetStatus(responder) => responder.send(self.get_status()).unwrap(),
         }
     }
@@ -240,12 +436,37 @@ impl<E: EventHandler> Server<E> {
                 reason, addresses, ..
             } => return Self::log_listener_close(reason, addresses),
 
+            SwarmEvent::IncomingConnectionError {
+                error: ListenError::Denied { cause },
+                ..
+            } => return self.count_denied_connection(&cause),
+
+            SwarmEvent::OutgoingConnectionError {
+                error: DialError::Denied { cause },
+                ..
+            } => return self.count_denied_connection(&cause),
+
             // Can't connect to the `peer`, remove it from the DHT.
             SwarmEvent::OutgoingConnectionError {
                 peer_id: Some(peer),
                 ..
             } => return self.network_service.behaviour_mut().remove_peer(&peer),
 
+            SwarmEvent::ConnectionEstablished {
+                peer_id,
+                num_established,
+                ..
+            } if num_established == NonZeroU32::MIN => {
+                self.connected_peers.insert(peer_id, None);
+                return;
+            }
+
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established: 0,
+                ..
+            } => return self.handle_peer_disconnected(peer_id),
+
             _ => return,
         };
 
@@ -255,10 +476,9 @@ impl<E: EventHandler> Server<E> {
     fn handle_behaviour_event(&mut self, ev: BehaviourEvent) {
         match ev {
             // See https://docs.rs/libp2p/latest/libp2p/kad/index.html#important-discrepancies
-            BehaviourEvent::Identify(identify::Event::Received {
-                peer_id,
-                info: identify::Info { listen_addrs, .. },
-            }) => self.add_addresses(&peer_id, listen_addrs),
+            BehaviourEvent::Identify(identify::Event::Received { peer_id, info }) => {
+                self.handle_peer_identified(peer_id, info)
+            }
 
             // The remote peer is unreachable, remove it from the DHT.
             BehaviourEvent::Ping(ping::Event {
@@ -268,12 +488,12 @@ impl<E: EventHandler> Server<E> {
             }) => self.network_service.behaviour_mut().remove_peer(&peer),
 
             BehaviourEvent::ReqResp(request_response::Event::Message {
+                peer,
                 message:
                     request_response::Message::Request {
                         request, channel, ..
                     },
-                ..
-            }) => self.handle_inbound_request(request, channel),
+            }) => self.handle_inbound_request(peer, request, channel),
 
             BehaviourEvent::ReqResp(request_response::Event::Message {
                 message:
@@ -291,17 +511,56 @@ impl<E: EventHandler> Server<E> {
             }) => self.handle_outbound_failure(request_id, error),
 
             BehaviourEvent::Pubsub(gossipsub::Event::Message {
-                propagation_source: _,
+                propagation_source,
                 message_id: _,
                 message,
-            }) => self.handle_inbound_broadcast(message),
+            }) => self.handle_inbound_broadcast(propagation_source, message),
+
+            BehaviourEvent::Autonat(autonat::Event::StatusChanged { old, new }) => {
+                self.handle_nat_status_changed(old, new)
+            }
+
+            BehaviourEvent::RelayClient(relay::client::Event::ReservationReqAccepted {
+                relay_peer_id,
+                renewal: false,
+                ..
+            }) => info!("📣 Reservation accepted by relay node {}", relay_peer_id),
+
+            BehaviourEvent::Dcutr(dcutr::Event::DirectConnectionUpgradeSucceeded {
+                remote_peer_id,
+            }) => info!("📣 Hole punching to {} succeeded", remote_peer_id),
+
+            BehaviourEvent::Dcutr(dcutr::Event::DirectConnectionUpgradeFailed {
+                remote_peer_id,
+                error,
+            }) => warn!("❗ Hole punching to {} failed: {}", remote_peer_id, error),
 
             _ => {}
         }
     }
 
     // Inbound requests are handled by the `EventHandler` which is provided by the application layer.
-    fn handle_inbound_request(&mut self, request: Vec<u8>, ch: ResponseChannel<ResponseType>) {
+    // Requests from peers that exceed the rate limit are rejected without reaching the handler.
+    fn handle_inbound_request(
+        &mut self,
+        peer: PeerId,
+        request: Vec<u8>,
+        ch: ResponseChannel<ResponseType>,
+    ) {
+        if let Some(limiter) = self.rate_limiter.as_mut() {
+            if !limiter.try_acquire(&peer) {
+                warn!(
+                    "❗ Rejected inbound request from {}: rate limit exceeded",
+                    peer
+                );
+                self.rejected_requests += 1;
+                return self
+                    .network_service
+                    .behaviour_mut()
+                    .send_response(ch, Err(()));
+            }
+        }
+
         if let Some(handler) = self.event_handler.get() {
             let response = handler.handle_inbound_request(request).map_err(|_| ());
             self.network_service
@@ -346,11 +605,11 @@ impl<E: EventHandler> Server<E> {
     }
 
     // Inbound broadcasts are handled by the `EventHandler` which is provided by the application layer.
-    fn handle_inbound_broadcast(&mut self, message: gossipsub::Message) {
+    fn handle_inbound_broadcast(&mut self, source: PeerId, message: gossipsub::Message) {
         if let Some(handler) = self.event_handler.get() {
             let topic_hash = message.topic;
-            match self.get_topic(&topic_hash) {
-                Some(topic) => handler.handle_broadcast(&topic, message.data),
+            match self.pubsub_topics.get(&topic_hash) {
+                Some(topic) => handler.handle_broadcast(topic, message.data, source),
                 None => {
                     warn!("❗ Received broadcast for unknown topic: {:?}", topic_hash);
                     debug_assert!(false);
@@ -360,11 +619,145 @@ impl<E: EventHandler> Server<E> {
     }
 
     // Broadcast a message to all peers subscribed to the given topic.
-    fn handle_outbound_broadcast(&mut self, topic: String, message: Vec<u8>) {
-        let _ = self
-            .network_service
+    fn handle_outbound_broadcast(
+        &mut self,
+        topic: String,
+        message: Vec<u8>,
+    ) -> Result<(), P2pError> {
+        let topic_hash = gossipsub::IdentTopic::new(&topic).hash();
+        if !self.pubsub_topics.contains_key(&topic_hash) {
+            return Err(P2pError::TopicNotSubscribed(topic));
+        }
+
+        self.network_service
             .behaviour_mut()
-            .broadcast(topic, message);
+            .broadcast(topic, message)
+    }
+
+    fn subscribe(&mut self, topic: String) -> Result<(), P2pError> {
+        let topic_hash = self.network_service.behaviour_mut().subscribe(&topic)?;
+        info!("📣 Subscribed to topic: {}", topic);
+        self.pubsub_topics.insert(topic_hash, topic);
+
+        Ok(())
+    }
+
+    fn unsubscribe(&mut self, topic: String) -> Result<(), P2pError> {
+        let topic_hash = gossipsub::IdentTopic::new(&topic).hash();
+        if self.pubsub_topics.remove(&topic_hash).is_none() {
+            return Err(P2pError::TopicNotSubscribed(topic));
+        }
+
+        self.network_service.behaviour_mut().unsubscribe(&topic)?;
+        info!("📣 Unsubscribed from topic: {}", topic);
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self, peer_id: PeerId) {
+        info!("📣 Disconnecting peer: {}", peer_id);
+        self.network_service.behaviour_mut().remove_peer(&peer_id);
+        let _ = self.network_service.disconnect_peer_id(peer_id);
+    }
+
+    // The first identification of a connected peer is reported as a `PeerEvent::Connected`,
+    // later ones (e.g. identify pushes) only update the peer info.
+    fn handle_peer_identified(&mut self, peer_id: PeerId, info: identify::Info) {
+        self.add_addresses(&peer_id, info.listen_addrs.clone());
+        if self.is_relay_node(&peer_id) {
+            self.handle_relay_observed_addr(info.observed_addr.clone());
+        }
+
+        let Some(entry) = self.connected_peers.get_mut(&peer_id) else {
+            return;
+        };
+        let is_new = entry.is_none();
+        let peer_info = PeerInfo::new(peer_id, info);
+        *entry = Some(peer_info.clone());
+
+        if is_new {
+            info!("📣 Peer connected: {:?}", peer_info);
+            if let Some(handler) = self.event_handler.get() {
+                handler.on_peer_connected(&peer_info);
+            }
+            let _ = self.peer_event_sender.send(PeerEvent::Connected(peer_info));
+        }
+    }
+
+    fn handle_peer_disconnected(&mut self, peer_id: PeerId) {
+        // Forget the rate limit state of a peer once all its connections are closed.
+        if let Some(limiter) = self.rate_limiter.as_mut() {
+            limiter.remove_peer(&peer_id);
+        }
+
+        // Only the identified peers have been reported as connected.
+        if let Some(Some(_)) = self.connected_peers.remove(&peer_id) {
+            info!("📣 Peer disconnected: {}", peer_id);
+            if let Some(handler) = self.event_handler.get() {
+                handler.on_peer_disconnected(&peer_id);
+            }
+            let _ = self
+                .peer_event_sender
+                .send(PeerEvent::Disconnected(peer_id));
+        }
+    }
+
+    // Only the addresses confirmed by AutoNAT are used as our public addresses, the address
+    // observed by the relay node is only used for hole punching while we are behind a NAT.
+    fn handle_nat_status_changed(&mut self, old: NatStatus, new: NatStatus) {
+        info!("📣 NAT status changed: {:?} -> {:?}", old, new);
+
+        if let NatStatus::Public(addr) = old {
+            self.network_service.remove_external_address(&addr);
+        }
+        self.network_service
+            .behaviour_mut()
+            .clear_hole_punch_candidates();
+
+        match new {
+            NatStatus::Public(addr) => {
+                info!("📣 External address confirmed by AutoNAT: {}", addr);
+                self.network_service.add_external_address(addr)
+            }
+            NatStatus::Private => {
+                if let Some(addr) = self.relay_observed_addr.clone() {
+                    self.punch_holes_with(addr);
+                }
+            }
+            NatStatus::Unknown => {}
+        }
+    }
+
+    fn handle_relay_observed_addr(&mut self, addr: Multiaddr) {
+        if self.relay_observed_addr.as_ref() == Some(&addr) {
+            return;
+        }
+        self.relay_observed_addr = Some(addr.clone());
+
+        let behaviour = self.network_service.behaviour_mut();
+        if behaviour.public_address().is_none() && behaviour.is_private() {
+            behaviour.clear_hole_punch_candidates();
+            self.punch_holes_with(addr);
+        }
+    }
+
+    // When we are behind a NAT, the address observed by the relay node is the NAT mapping
+    // of our port. It's not reachable directly, so it's not an external address, but it's
+    // what the remote peer needs for hole punching.
+    fn punch_holes_with(&mut self, addr: Multiaddr) {
+        info!(
+            "📣 Using the address observed by the relay node for hole punching: {}",
+            addr
+        );
+        self.network_service
+            .behaviour_mut()
+            .add_hole_punch_candidate(addr);
+    }
+
+    fn is_relay_node(&self, peer_id: &PeerId) -> bool {
+        self.relay_node
+            .as_ref()
+            .is_some_and(|relay_node| relay_node.peer_id() == *peer_id)
     }
 
     fn add_addresses(&mut self, peer_id: &PeerId, addresses: Vec<Multiaddr>) {
@@ -375,6 +768,14 @@ impl<E: EventHandler> Server<E> {
         }
     }
 
+    // Only the connections denied by the `connection_limits` behaviour are counted.
+    fn count_denied_connection(&mut self, cause: &ConnectionDenied) {
+        if let Some(exceeded) = cause.downcast_ref::<connection_limits::Exceeded>() {
+            warn!("❗ Connection denied: {}", exceeded);
+            self.rejected_connections += 1;
+        }
+    }
+
     fn get_status(&mut self) -> NodeStatus {
         let known_peers = self.network_service.behaviour_mut().known_peers();
         NodeStatus {
@@ -382,6 +783,13 @@ impl<E: EventHandler> Server<E> {
             listened_addresses: self.listened_addresses.clone(),
             known_peers_count: known_peers.len(),
             known_peers,
+            connected_peers: self.connected_peers.values().flatten().cloned().collect(),
+            external_addresses: self.network_service.external_addresses().cloned().collect(),
+            hole_punch_candidates: self.network_service.behaviour().hole_punch_candidates(),
+            public_address: self.network_service.behaviour().public_address(),
+            pubsub_topics: self.pubsub_topics.values().cloned().collect(),
+            rejected_connections: self.rejected_connections,
+            rejected_requests: self.rejected_requests,
         }
     }
 
@@ -393,18 +801,6 @@ impl<E: EventHandler> Server<E> {
             .collect();
     }
 
-    /// Returns the topic name for the given topic hash.
-    fn get_topic(&self, topic_hash: &TopicHash) -> Option<String> {
-        for t in &self.pubsub_topics {
-            let topic = gossipsub::IdentTopic::new(t);
-            if topic.hash() == *topic_hash {
-                return Some(t.clone());
-            }
-        }
-
-        None
-    }
-
     fn log_listener_close(reason: io::Result<()>, addresses: Vec<Multiaddr>) {
         let addrs = addresses
             .into_iter()
@@ -429,11 +825,64 @@ pub struct NodeStatus {
     pub listened_addresses: Vec<Multiaddr>,
     pub known_peers_count: usize,
     pub known_peers: HashMap<PeerId, Vec<Multiaddr>>,
+    /// The connected and identified peers.
+    pub connected_peers: Vec<PeerInfo>,
+    /// The confirmed external addresses.
+    pub external_addresses: Vec<Multiaddr>,
+    /// The addresses used for hole punching but not advertised, e.g. the one observed by the
+    /// relay node while we are behind a NAT.
+    pub hole_punch_candidates: Vec<Multiaddr>,
+    /// The public address confirmed by AutoNAT, `None` if the node is not publicly reachable.
+    pub public_address: Option<Multiaddr>,
+    /// The topics that the node is subscribed to.
+    pub pubsub_topics: Vec<String>,
+    /// The number of connections denied due to the connection limits.
+    pub rejected_connections: u64,
+    /// The number of inbound requests rejected due to the rate limit.
+    pub rejected_requests: u64,
+}
+
+/// Information about a connected peer, learned from the identify protocol.
+#[derive(Clone, Debug)]
+pub struct PeerInfo {
+    pub peer_id: PeerId,
+    /// Name and version of the peer, e.g. `rust-libp2p/0.52.1`.
+    pub agent_version: String,
+    /// Version of the protocol family used by the peer, e.g. `/tinychain/identify/1.0.0`.
+    pub protocol_version: String,
+    /// The protocols supported by the peer.
+    pub protocols: Vec<String>,
+    /// The addresses that the peer is listening on.
+    pub listen_addrs: Vec<Multiaddr>,
+}
+
+impl PeerInfo {
+    fn new(peer_id: PeerId, info: identify::Info) -> Self {
+        Self {
+            peer_id,
+            agent_version: info.agent_version,
+            protocol_version: info.protocol_version,
+            protocols: info.protocols.iter().map(ToString::to_string).collect(),
+            listen_addrs: info.listen_addrs,
+        }
+    }
+}
+
+/// Peer connection lifecycle events, which can be received from `Client::peer_events`.
+#[derive(Clone, Debug)]
+pub enum PeerEvent {
+    /// A remote peer is connected and identified.
+    Connected(PeerInfo),
+    /// All connections to a remote peer are closed.
+    Disconnected(PeerId),
 }
 
 #[cfg(test)]
 mod tests {
+    use libp2p::identity;
+
     use super::*;
+    use crate::config::NatConfig;
 
     #[test]
     fn new_secret_key_works() {
@@ -444,4 +893,252 @@ mod tests {
         let secret = bs58::decode(key1).into_vec().unwrap();
         assert_eq!(secret.len(), 32);
     }
+
+    #[tokio::test]
+    async fn subscribe_and_unsubscribe_topics() {
+        let config = P2pConfig {
+            addr: "/ip4/127.0.0.1/tcp/0".to_string(),
+            pubsub_topics: vec!["block".to_string()],
+            ..Default::default()
+        };
+        let (_, receiver) = mpsc::unbounded_channel();
+        let (peer_event_sender, _) = broadcast::channel(PEER_EVENT_CAPACITY);
+        let mut server = Server::<Handler>::new(config, receiver, peer_event_sender).unwrap();
+        let tx_hash = gossipsub::IdentTopic::new("tx").hash();
+
+        // Broadcasting to a topic that is not subscribed to is an error.
+        let result = server.handle_outbound_broadcast("tx".to_string(), vec![1]);
+        assert!(matches!(result, Err(P2pError::TopicNotSubscribed(_))));
+
+        server.subscribe("tx".to_string()).unwrap();
+        assert_eq!(server.pubsub_topics.get(&tx_hash).unwrap(), "tx");
+        assert_eq!(server.get_status().pubsub_topics.len(), 2);
+
+        server.unsubscribe("tx".to_string()).unwrap();
+        assert!(!server.pubsub_topics.contains_key(&tx_hash));
+
+        let result = server.unsubscribe("tx".to_string());
+        assert!(matches!(result, Err(P2pError::TopicNotSubscribed(_))));
+    }
+
+    #[tokio::test]
+    async fn peer_lifecycle_events() {
+        let config = P2pConfig {
+            addr: "/ip4/127.0.0.1/tcp/0".to_string(),
+            ..Default::default()
+        };
+        let (_, receiver) = mpsc::unbounded_channel();
+        let (peer_event_sender, _) = broadcast::channel(PEER_EVENT_CAPACITY);
+        let mut server =
+            Server::<Handler>::new(config, receiver, peer_event_sender.clone()).unwrap();
+        let mut events = peer_event_sender.subscribe();
+
+        let peer_key = identity::Keypair::generate_ed25519();
+        let peer_id = peer_key.public().to_peer_id();
+        let info = identify::Info {
+            public_key: peer_key.public(),
+            protocol_version: "/tinychain/identify/1.0.0".to_string(),
+            agent_version: "test/1.0.0".to_string(),
+            listen_addrs: vec![],
+            protocols: vec![],
+            observed_addr: Multiaddr::empty(),
+        };
+
+        // Identify info from a peer that is not connected is ignored.
+        server.handle_peer_identified(peer_id, info.clone());
+        assert!(events.try_recv().is_err());
+
+        // Only the first identification is reported.
+        server.connected_peers.insert(peer_id, None);
+        server.handle_peer_identified(peer_id, info.clone());
+        server.handle_peer_identified(peer_id, info);
+        match events.try_recv().unwrap() {
+            PeerEvent::Connected(info) => {
+                assert_eq!(info.peer_id, peer_id);
+                assert_eq!(info.agent_version, "test/1.0.0");
+            }
+            ev => panic!("unexpected event: {:?}", ev),
+        }
+        assert!(events.try_recv().is_err());
+        assert_eq!(server.get_status().connected_peers.len(), 1);
+
+        server.handle_peer_disconnected(peer_id);
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            PeerEvent::Disconnected(id) if id == peer_id
+        ));
+        assert!(server.get_status().connected_peers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn relay_observed_addr_is_only_a_hole_punch_candidate() {
+        let config = P2pConfig {
+            addr: "/ip4/127.0.0.1/tcp/0".to_string(),
+            ..Default::default()
+        };
+        let (_, receiver) = mpsc::unbounded_channel();
+        let (peer_event_sender, _) = broadcast::channel(PEER_EVENT_CAPACITY);
+        let mut server = Server::<Handler>::new(config, receiver, peer_event_sender).unwrap();
+        let observed: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        let public: Multiaddr = "/ip4/5.6.7.8/tcp/4001".parse().unwrap();
+        server.relay_observed_addr = Some(observed.clone());
+
+        // Behind a NAT, the observed address is not advertised.
+        server.handle_nat_status_changed(NatStatus::Unknown, NatStatus::Private);
+        assert!(server.get_status().external_addresses.is_empty());
+        assert_eq!(
+            server.get_status().hole_punch_candidates,
+            vec![observed.clone()]
+        );
+
+        // Once public, only the confirmed address is used.
+        server.handle_nat_status_changed(NatStatus::Private, NatStatus::Public(public.clone()));
+        assert_eq!(server.get_status().external_addresses, vec![public.clone()]);
+        assert!(server.get_status().hole_punch_candidates.is_empty());
+
+        server.handle_nat_status_changed(NatStatus::Public(public), NatStatus::Unknown);
+        assert!(server.get_status().external_addresses.is_empty());
+    }
+
+    #[tokio::test]
+    async fn server_stops_on_shutdown_signal() {
+        let config = P2pConfig {
+            addr: "/ip4/127.0.0.1/tcp/0".to_string(),
+            ..Default::default()
+        };
+        let (_, mut server) = new::<Handler>(config).unwrap();
+        server.set_event_handler(Handler);
+
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+        let task = tokio::spawn(server.run_with_shutdown(async {
+            let _ = shutdown_receiver.await;
+        }));
+
+        shutdown_sender.send(()).unwrap();
+        time::timeout(Duration::from_secs(1), task)
+            .await
+            .expect("server should stop")
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reserve_relayed_address_on_in_process_relay_node() {
+        let relay_node = start_relay_node();
+        let (client, _) = start_relayed_node(&relay_node);
+
+        assert!(
+            wait_for_relayed_address(client).await,
+            "the relayed address should be listened on"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn connect_through_in_process_relay_node() {
+        let relay_node = start_relay_node();
+        let (listener, listener_id) = start_relayed_node(&relay_node);
+        assert!(wait_for_relayed_address(listener).await);
+
+        // The dialer only knows the circuit address of the listener, which goes through the
+        // relay node.
+        let circuit_addr = format!("{relay_node}/p2p-circuit/p2p/{listener_id}");
+        let config = P2pConfig {
+            addr: "/ip4/127.0.0.1/tcp/0".to_string(),
+            boot_node: Some(circuit_addr.parse().unwrap()),
+            ..Default::default()
+        };
+        let (dialer, mut server) = new::<Handler>(config).unwrap();
+        server.set_event_handler(Handler);
+        tokio::spawn(server.run());
+
+        let resp = tokio::task::spawn_blocking(move || {
+            let target = listener_id.to_string();
+            for _ in 0..100 {
+                if let Ok(resp) = dialer.blocking_request(&target, b"ping".to_vec()) {
+                    return Some(resp);
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            None
+        })
+        .await
+        .unwrap();
+        assert_eq!(resp.as_deref(), Some(&b"ping"[..]));
+    }
+
+    /// Start a relay node, and return its address with the peer id.
+    fn start_relay_node() -> PeerIdWithMultiaddr {
+        let relay_addr = format!("/ip4/127.0.0.1/tcp/{}", free_port());
+        let relay_config = P2pConfig {
+            addr: relay_addr.clone(),
+            secret: Some(new_secret_key()),
+            nat: Some(NatConfig {
+                enable_relay_server: Some(true),
+                external_addresses: vec![relay_addr.clone()],
+                autonat_only_global_ips: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let relay_peer_id = relay_config.gen_keypair().unwrap().public().to_peer_id();
+        let (_, mut relay_server) = new::<Handler>(relay_config).unwrap();
+        relay_server.set_event_handler(Handler);
+        tokio::spawn(relay_server.run());
+
+        format!("{relay_addr}/p2p/{relay_peer_id}").parse().unwrap()
+    }
+
+    /// Start a node that reserves a relayed address on the relay node.
+    fn start_relayed_node(relay_node: &PeerIdWithMultiaddr) -> (Client, PeerId) {
+        let config = P2pConfig {
+            addr: "/ip4/127.0.0.1/tcp/0".to_string(),
+            secret: Some(new_secret_key()),
+            nat: Some(NatConfig {
+                relay_node: Some(relay_node.to_string().parse().unwrap()),
+                autonat_only_global_ips: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let peer_id = config.gen_keypair().unwrap().public().to_peer_id();
+        let (client, mut server) = new::<Handler>(config).unwrap();
+        server.set_event_handler(Handler);
+        tokio::spawn(server.run());
+
+        (client, peer_id)
+    }
+
+    async fn wait_for_relayed_address(client: Client) -> bool {
+        tokio::task::spawn_blocking(move || {
+            for _ in 0..100 {
+                let status = client.get_node_status();
+                let is_relayed = |addr: &Multiaddr| addr.iter().any(|p| p == Protocol::P2pCircuit);
+                if status.listened_addresses.iter().any(is_relayed) {
+                    return true;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            false
+        })
+        .await
+        .unwrap()
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[derive(Debug)]
+    struct Handler;
+
+    impl EventHandler for Handler {
+        fn handle_inbound_request(&self, request: Vec<u8>) -> Result<Vec<u8>, P2pError> {
+            Ok(request)
+        }
+
+        fn handle_broadcast(&self, _: &str, _: Vec<u8>, _: PeerId) {}
+    }
 }
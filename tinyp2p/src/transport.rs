@@ -1,16 +1,30 @@
 //! [What is a transport](https://docs.libp2p.io/concepts/transports/overview/)
 
 use libp2p::{
-    core::{muxing::StreamMuxerBox, transport::Boxed, upgrade::Version},
-    identity, noise, tcp, yamux, PeerId, Transport,
+    core::{
+        muxing::StreamMuxerBox,
+        transport::{Boxed, OrTransport},
+        upgrade::Version,
+    },
+    identity, noise, relay, tcp, yamux, PeerId, Transport,
 };
 
 /// Create a tokio-based TCP transport use noise for authenticated
 /// encryption and Yamux for multiplexing of substreams on a TCP stream.
-pub fn build_transport(keypair: identity::Keypair) -> Boxed<(PeerId, StreamMuxerBox)> {
+///
+/// The TCP transport is combined with the relay client transport,
+/// so that the node can also be reached through a circuit relay.
+pub fn build_transport(
+    keypair: identity::Keypair,
+    relay_transport: relay::client::Transport,
+) -> Boxed<(PeerId, StreamMuxerBox)> {
     let noise_config = noise::Config::new(&keypair).expect("failed to construct the noise config");
 
-    tcp::tokio::Transport::default()
+    // Port reuse is required for hole punching, so that the outbound connections use
+    // the same port as the listener.
+    let tcp_transport = tcp::tokio::Transport::new(tcp::Config::default().port_reuse(true));
+
+    OrTransport::new(relay_transport, tcp_transport)
         .upgrade(Version::V1Lazy)
         .authenticate(noise_config)
         .multiplex(yamux::Config::default())
@@ -74,8 +74,24 @@ pub fn verify_signature(msg: &[u8], signature: impl Into<Signature>) -> Result<(
     Ok(())
 }
 
+/// Recover the address of the account that signed the message.
+pub fn recover_address(msg: &[u8], signature: impl Into<Signature>) -> Result<String, WalletError> {
+    let signature = signature.into();
+    let (sig, recid) = signature.try_into()?;
+    let digest = Keccak256::new_with_prefix(msg);
+
+    let recovered_key = VerifyingKey::recover_from_digest(digest, &sig, recid)
+        .map_err(|_| WalletError::InvalidSignature)?;
+
+    Ok(pubkey_to_address(&recovered_key))
+}
+
 fn gen_address(privkey: &SigningKey) -> String {
-    let pubkey = privkey.verifying_key().to_encoded_point(false);
+    pubkey_to_address(privkey.verifying_key())
+}
+
+fn pubkey_to_address(pubkey: &VerifyingKey) -> String {
+    let pubkey = pubkey.to_encoded_point(false);
     let pubkey = pubkey.as_bytes();
     let hash = Keccak256::digest(&pubkey[1..]);
 
@@ -106,6 +122,8 @@ mod tests {
         let sig = wallet.sign(msg, &addr).unwrap();
 
         assert!(verify_signature(msg, sig).is_ok());
+        assert_eq!(recover_address(msg, sig).unwrap(), addr);
+        assert_ne!(recover_address(b"hello there", sig).unwrap(), addr);
     }
 
     fn tempdir_with_prefix(prefix: &str) -> String {